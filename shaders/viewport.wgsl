@@ -0,0 +1,27 @@
+// Vertex shader
+
+struct VertexOutput {
+    [[location(0)]] tex_coord: vec2<f32>;
+    [[builtin(position)]] position: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    [[location(0)]] position: vec2<f32>,
+    [[location(1)]] tex_coord: vec2<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coord = tex_coord;
+    out.position = vec4<f32>(position, 0.0, 1.0);
+    return out;
+}
+
+// Fragment shader
+
+[[group(0), binding(0)]] var r_tex_color: texture_2d<f32>;
+[[group(0), binding(1)]] var r_tex_sampler: sampler;
+
+[[stage(fragment)]]
+fn fs_main([[location(0)]] tex_coord: vec2<f32>) -> [[location(0)]] vec4<f32> {
+    return textureSample(r_tex_color, r_tex_sampler, tex_coord);
+}
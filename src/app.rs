@@ -0,0 +1,564 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use log::error;
+use pixels::{Pixels, PixelsBuilder, SurfaceTexture};
+use winit::dpi::{LogicalSize, PhysicalSize};
+use winit::event::{Event, Touch, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::{Window, WindowBuilder};
+
+use crate::config::AppConfig;
+use crate::frame::Frame;
+use crate::gesture::{Gesture, GestureRecognizer};
+use crate::ime::ImeOptions;
+use crate::recording::{recording_path, FrameRecorder, RecordingOptions};
+use crate::renderer::ViewportRenderer;
+use crate::replay::{InputRecorder, RecordedEvent};
+use crate::resize::{ResizeDebouncer, ResizeEvent};
+use crate::save_state::StateStore;
+use crate::screenshot::Screenshots;
+use crate::soft_input::{platform_soft_keyboard, KeyboardEvent, KeyboardState};
+use crate::stats::FrameStats;
+use crate::text_input::{TextInput, TextInputState};
+use crate::timing::{ControlFlowPolicy, FixedTimestep};
+use crate::touch::TouchTracker;
+use crate::viewport::{ScalingMode, Viewport};
+
+/// An application driven by [`run_app`].
+///
+/// The runner owns the event loop and the `Pixels` instance; the app only sees its own state,
+/// the events forwarded from the window and the frame buffer to draw into.
+pub trait PixelsApp {
+    /// Create the initial application state.
+    fn init(config: &AppConfig) -> Self
+    where
+        Self: Sized;
+
+    /// Advance the application state by one tick of the fixed timestep.
+    fn update(&mut self);
+
+    /// Draw the current state to the frame buffer.
+    ///
+    /// The frame is `width * height * 4` bytes in `wgpu::TextureFormat::Rgba8UnormSrgb`.
+    /// `alpha` is the progress towards the next update in `0.0..1.0`, for interpolating motion.
+    fn draw(&self, frame: &mut [u8], alpha: f64);
+
+    /// Called once per frame before [`update`](Self::update) with the fingers on the screen.
+    fn touches(&mut self, _touches: &TouchTracker) {}
+
+    /// Called for every gesture recognized from touch input.
+    fn gesture(&mut self, _gesture: Gesture) {}
+
+    /// Called when the soft keyboard appears, disappears or changes its height.
+    fn keyboard_event(&mut self, _event: KeyboardEvent) {}
+
+    /// Options for the soft keyboard, queried whenever it is shown.
+    fn ime_options(&self) -> ImeOptions {
+        ImeOptions::default()
+    }
+
+    /// Handle an edit produced from keyboard and IME input.
+    fn text_input(&mut self, _input: TextInput) {}
+
+    /// Handle a window event. Called for every event while the surface exists.
+    fn handle_event(&mut self, _event: &WindowEvent) {}
+
+    /// Called when the area of the window showing the buffer changed, e.g. after the surface
+    /// was created. Use it to map touch and cursor positions to buffer coordinates.
+    fn viewport_changed(&mut self, _viewport: Viewport) {}
+
+    /// Called after the surface was resized to a new window size, following
+    /// [`viewport_changed`](Self::viewport_changed).
+    fn resized(&mut self, _event: ResizeEvent) {}
+
+    /// Called after the surface was destroyed, e.g. when the activity is paused.
+    fn on_suspend(&mut self) {}
+
+    /// Called after the surface was (re)created.
+    fn on_resume(&mut self) {}
+
+    /// Serialize the state to keep when the app is suspended or closed. Android may kill the
+    /// process while it is suspended; the state is handed to
+    /// [`restore_state`](Self::restore_state) on the next start. Returns `None` to save nothing.
+    fn save_state(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Version stored along with the saved state. Bump it when the format of
+    /// [`save_state`](Self::save_state) changes.
+    fn state_version(&self) -> u32 {
+        0
+    }
+
+    /// Restore state saved by a previous run, right after [`init`](Self::init). `version` is
+    /// the [`state_version`](Self::state_version) it was saved with. On error the state is
+    /// discarded and the app starts fresh.
+    fn restore_state(&mut self, _version: u32, _data: &[u8]) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Polled once per frame after the updates. Return `true` to save the next frame as a
+    /// screenshot, in addition to the configured
+    /// [`screenshot_trigger`](crate::config::AppConfigBuilder::screenshot_trigger)s.
+    fn take_screenshot(&mut self) -> bool {
+        false
+    }
+
+    /// Called after a screenshot was saved to `path`.
+    fn screenshot_saved(&mut self, _path: &Path) {}
+
+    /// Polled once per frame after the updates while no recording is running. Return options
+    /// to record the following frames to an animated GIF or APNG next to the screenshots.
+    fn start_recording(&mut self) -> Option<RecordingOptions> {
+        None
+    }
+
+    /// Called after a recording was written to `path`.
+    fn recording_saved(&mut self, _path: &Path) {}
+}
+
+/// Restore the state saved by a previous run, if any.
+fn restore_state(app: &mut impl PixelsApp, store: &StateStore) {
+    let restored = store.load().and_then(|state| match state {
+        Some(state) => app.restore_state(state.version, &state.data).map(|()| true),
+        None => Ok(false),
+    });
+    match restored {
+        Ok(true) => log::info!("restored state from {}", store.path().display()),
+        Ok(false) => (),
+        Err(e) => {
+            log::warn!("discarding saved state: {:#}", e);
+            if let Err(e) = store.clear() {
+                log::warn!("{:#}", e);
+            }
+        }
+    }
+}
+
+/// Write the app's state to `store`, if it has any.
+fn save_state(app: &impl PixelsApp, store: &StateStore) {
+    if let Some(data) = app.save_state() {
+        if let Err(e) = store.save(app.state_version(), &data) {
+            error!("failed to save state: {:#}", e);
+        }
+    }
+}
+
+/// Append an event to the recording, if any. Recording stops on the first error.
+fn record(recorder: &mut Option<InputRecorder>, now: Instant, event: RecordedEvent) {
+    if let Some(r) = recorder.as_mut() {
+        if let Err(e) = r.record(now, event) {
+            error!("failed to record input, stopping: {:#}", e);
+            *recorder = None;
+        }
+    }
+}
+
+fn init_logging(config: &AppConfig) {
+    #[cfg(target_os = "android")]
+    if let Some(level) = config.log_level().to_level() {
+        android_logger::init_once(
+            android_logger::Config::default()
+                .with_min_level(level)
+                .with_tag(config.log_tag()),
+        );
+    }
+    log::set_max_level(config.log_level());
+}
+
+/// The renderer for the configured scaling mode, or `None` for the built-in integer scaling of
+/// `Pixels`.
+fn create_renderer(
+    config: &AppConfig,
+    pixels: &Pixels,
+    viewport: &Viewport,
+) -> Option<ViewportRenderer> {
+    match config.scaling_mode() {
+        ScalingMode::Integer => None,
+        _ => {
+            let context = pixels.context();
+            Some(ViewportRenderer::new(
+                &context.device,
+                &context.texture,
+                pixels.render_texture_format(),
+                config.clear_color(),
+                viewport,
+            ))
+        }
+    }
+}
+
+fn create_pixels(config: &AppConfig, window: &Window) -> anyhow::Result<Pixels> {
+    let window_size = window.inner_size();
+    let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, window);
+    let pixels = PixelsBuilder::new(config.width(), config.height(), surface_texture)
+        .enable_vsync(config.vsync())
+        .clear_color(config.clear_color())
+        .build()?;
+    Ok(pixels)
+}
+
+/// Run `A` inside a window until the window is closed.
+///
+/// Returns an error if `config` is invalid or the window can't be created.
+pub fn run_app<A: PixelsApp + 'static>(config: AppConfig) -> anyhow::Result<()> {
+    config.validate()?;
+    init_logging(&config);
+
+    let event_loop = EventLoop::new();
+    let window = {
+        let (width, height) = config.window_size();
+        let (min_width, min_height) = config.min_window_size();
+        WindowBuilder::new()
+            .with_title(config.title())
+            .with_inner_size(LogicalSize::new(width as f64, height as f64))
+            .with_min_inner_size(LogicalSize::new(min_width as f64, min_height as f64))
+            .build(&event_loop)?
+    };
+
+    let mut pixels: Option<Pixels> = None;
+    let mut renderer: Option<ViewportRenderer> = None;
+    let mut app = A::init(&config);
+    let state_store = StateStore::for_platform(config.state_dir());
+    let mut recorder = match config.record_input() {
+        Some(path) => {
+            log::info!("recording input to {}", path.display());
+            Some(InputRecorder::create(path, &config)?)
+        }
+        None => None,
+    };
+    if recorder.is_none() {
+        restore_state(&mut app, &state_store);
+    }
+
+    let mut soft_keyboard = platform_soft_keyboard();
+    let mut keyboard = KeyboardState::default();
+    let mut text_input = TextInputState::for_platform();
+    let mut touches = TouchTracker::default();
+    let mut gestures = GestureRecognizer::new(*config.gesture_config());
+    let mut resizer = ResizeDebouncer::new(config.resize_debounce());
+    let mut viewport = Viewport::new(
+        config.scaling_mode(),
+        PhysicalSize::new(config.width(), config.height()),
+        window.inner_size(),
+    );
+
+    let screenshots =
+        Screenshots::for_platform(config.screenshot_dir()).with_scale(config.screenshot_scale());
+    let mut screenshot_pending = false;
+    let mut frame_recorder: Option<FrameRecorder> = None;
+
+    let mut timestep = FixedTimestep::new(config.tick_rate(), config.max_catch_up_steps());
+    let mut stats = FrameStats::new(120, timestep.tick());
+    let mut update_time = Duration::ZERO;
+    let mut last_stats_log = Instant::now();
+
+    event_loop.run(move |event, _, control_flow| {
+        if let Event::Resumed = event {
+            log::info!("resumed");
+            timestep.reset();
+            stats.reset_interval();
+            // The new surface is created with the current window size
+            resizer.reset();
+
+            match create_pixels(&config, &window) {
+                Ok(new_pixels) => {
+                    viewport = Viewport::new(
+                        config.scaling_mode(),
+                        PhysicalSize::new(config.width(), config.height()),
+                        window.inner_size(),
+                    );
+                    renderer = create_renderer(&config, &new_pixels, &viewport);
+                    pixels = Some(new_pixels);
+                    app.viewport_changed(viewport);
+                    let size = window.inner_size();
+                    record(
+                        &mut recorder,
+                        Instant::now(),
+                        RecordedEvent::Resumed {
+                            window_size: (size.width, size.height),
+                        },
+                    );
+                }
+                Err(e) => {
+                    error!("failed to create pixels: {}", e);
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
+            }
+            app.on_resume();
+        }
+
+        if let Event::Suspended = event {
+            pixels = None;
+            renderer = None;
+            touches.clear();
+            gestures.reset();
+            // Nothing to update or draw until the surface is back
+            control_flow.set_wait();
+            app.on_suspend();
+            save_state(&app, &state_store);
+            if let Some(recorder) = frame_recorder.as_mut() {
+                recorder.stop();
+            }
+            record(&mut recorder, Instant::now(), RecordedEvent::Suspended);
+            if let Some(Err(e)) = recorder.as_mut().map(InputRecorder::flush) {
+                error!("failed to flush input recording: {:#}", e);
+            }
+        }
+
+        if let Some(pixels) = pixels.as_mut() {
+            if let Event::WindowEvent { event, .. } = &event {
+                text_input.handle_window_event(event);
+                let now = Instant::now();
+                touches.handle_window_event(event, &viewport, now);
+                gestures.handle_window_event(event, &viewport, now);
+                resizer.handle_window_event(event, now);
+                match event {
+                    WindowEvent::Touch(Touch {
+                        id,
+                        phase,
+                        location,
+                        ..
+                    }) => {
+                        let event = RecordedEvent::Touch {
+                            id: *id,
+                            phase: (*phase).into(),
+                            location: (location.x, location.y),
+                        };
+                        record(&mut recorder, now, event);
+                    }
+                    WindowEvent::KeyboardInput { input, .. } => {
+                        let event = RecordedEvent::Key {
+                            virtual_keycode: input.virtual_keycode,
+                            scancode: input.scancode,
+                            state: input.state,
+                        };
+                        record(&mut recorder, now, event);
+                    }
+                    _ => (),
+                }
+                for input in text_input.drain() {
+                    record(&mut recorder, now, RecordedEvent::TextInput(input.clone()));
+                    app.text_input(input);
+                }
+                if config
+                    .screenshot_triggers()
+                    .iter()
+                    .any(|trigger| trigger.matches_event(event))
+                {
+                    screenshot_pending = true;
+                }
+                app.handle_event(event);
+            }
+
+            // Draw the current frame
+            match event {
+                Event::RedrawRequested(_) => {
+                    let draw_start = Instant::now();
+                    let frame = pixels.get_frame();
+                    let alpha = timestep.alpha();
+                    record(&mut recorder, draw_start, RecordedEvent::Draw { alpha });
+                    app.draw(frame, alpha);
+                    let draw_time = draw_start.elapsed();
+                    // Taken before the overlay, which is not part of the app's image
+                    if screenshot_pending {
+                        screenshot_pending = false;
+                        let frame = Frame {
+                            width: config.width(),
+                            height: config.height(),
+                            data: frame.to_vec(),
+                        };
+                        match screenshots.save(&frame) {
+                            Ok(path) => {
+                                log::info!("saved screenshot to {}", path.display());
+                                app.screenshot_saved(&path);
+                            }
+                            Err(e) => error!("failed to save screenshot: {:#}", e),
+                        }
+                    }
+                    if let Some(recorder) = frame_recorder.as_mut() {
+                        recorder.capture(frame, draw_start);
+                    }
+                    if config.frame_stats_overlay() {
+                        stats.draw_overlay(frame, config.width(), config.height());
+                    }
+
+                    let render_start = Instant::now();
+                    let rendered = match &renderer {
+                        Some(renderer) => pixels.render_with(|encoder, render_target, _context| {
+                            renderer.render(encoder, render_target);
+                        }),
+                        None => pixels.render(),
+                    };
+                    if rendered
+                        .map_err(|e| error!("pixels.render() failed: {}", e))
+                        .is_err()
+                    {
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+
+                    let now = Instant::now();
+                    stats.record(now, update_time, draw_time, now - render_start);
+                    update_time = Duration::ZERO;
+                    if let Some(interval) = config.frame_stats_log_interval() {
+                        if now - last_stats_log >= interval {
+                            stats.log();
+                            last_stats_log = now;
+                        }
+                    }
+                }
+                Event::MainEventsCleared => {
+                    // One timestamp for the whole batch, so a replay sees the same times
+                    let now = Instant::now();
+                    if let Some(size) = resizer.update(now) {
+                        pixels.resize_surface(size.width, size.height);
+                        viewport = Viewport::new(
+                            config.scaling_mode(),
+                            PhysicalSize::new(config.width(), config.height()),
+                            size,
+                        );
+                        if let Some(renderer) = &renderer {
+                            renderer.set_viewport(&pixels.context().queue, &viewport);
+                        }
+                        let resize = ResizeEvent {
+                            window_size: size,
+                            buffer_size: viewport.buffer_size,
+                            scale_factor: window.scale_factor(),
+                        };
+                        record(
+                            &mut recorder,
+                            now,
+                            RecordedEvent::Resized {
+                                window_size: (size.width, size.height),
+                                scale_factor: resize.scale_factor,
+                            },
+                        );
+                        app.viewport_changed(viewport);
+                        app.resized(resize);
+                        window.request_redraw();
+                    }
+
+                    keyboard.update(soft_keyboard.as_mut(), now);
+                    for event in keyboard.drain() {
+                        record(&mut recorder, now, RecordedEvent::Keyboard(event));
+                        app.keyboard_event(event);
+                    }
+
+                    app.touches(&touches);
+                    touches.end_frame();
+                    gestures.update(now);
+                    for gesture in gestures.drain() {
+                        if config
+                            .screenshot_triggers()
+                            .iter()
+                            .any(|trigger| trigger.matches_gesture(&gesture))
+                        {
+                            screenshot_pending = true;
+                        }
+                        app.gesture(gesture);
+                    }
+
+                    // Update internal state and request a redraw
+                    let steps = timestep.advance(now);
+                    record(&mut recorder, now, RecordedEvent::Frame { steps });
+                    let update_start = Instant::now();
+                    for _ in 0..steps {
+                        app.update();
+                    }
+                    update_time += update_start.elapsed();
+                    if app.take_screenshot() {
+                        screenshot_pending = true;
+                    }
+
+                    if let Some(recorder) = frame_recorder.as_mut() {
+                        recorder.update(now);
+                    }
+                    if let Some(result) = frame_recorder.as_mut().and_then(FrameRecorder::poll) {
+                        frame_recorder = None;
+                        match result {
+                            Ok(path) => {
+                                log::info!("saved recording to {}", path.display());
+                                app.recording_saved(&path);
+                            }
+                            Err(e) => error!("recording failed: {:#}", e),
+                        }
+                    }
+                    if frame_recorder.is_none() {
+                        if let Some(options) = app.start_recording() {
+                            let path = recording_path(screenshots.dir(), options.format);
+                            match FrameRecorder::start(
+                                path,
+                                config.width(),
+                                config.height(),
+                                options,
+                            ) {
+                                Ok(recorder) => frame_recorder = Some(recorder),
+                                Err(e) => error!("failed to start recording: {:#}", e),
+                            }
+                        }
+                    }
+
+                    match config.control_flow() {
+                        ControlFlowPolicy::Poll => {
+                            control_flow.set_poll();
+                            window.request_redraw();
+                        }
+                        ControlFlowPolicy::Wait => {
+                            control_flow.set_wait();
+                            window.request_redraw();
+                        }
+                        ControlFlowPolicy::WaitUntilNextFrame => {
+                            control_flow.set_wait_until(timestep.next_tick(now));
+                            if steps > 0 {
+                                window.request_redraw();
+                            }
+                        }
+                    }
+                    // Wake up to apply a pending resize
+                    if let Some(deadline) = resizer.deadline() {
+                        match *control_flow {
+                            ControlFlow::Wait => control_flow.set_wait_until(deadline),
+                            ControlFlow::WaitUntil(next) => {
+                                control_flow.set_wait_until(next.min(deadline))
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. },
+                    ..
+                } => {
+                    keyboard.invalidate();
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::CloseRequested,
+                    ..
+                } => {
+                    save_state(&app, &state_store);
+                    if let Some(recorder) = frame_recorder.take() {
+                        match recorder.finish() {
+                            Ok(path) => log::info!("saved recording to {}", path.display()),
+                            Err(e) => error!("recording failed: {:#}", e),
+                        }
+                    }
+                    if let Some(Err(e)) = recorder.as_mut().map(InputRecorder::flush) {
+                        error!("failed to flush input recording: {:#}", e);
+                    }
+                    *control_flow = ControlFlow::Exit;
+                }
+
+                Event::WindowEvent {
+                    event: WindowEvent::Touch(touch),
+                    ..
+                } => {
+                    keyboard.handle_touch(touch.phase, soft_keyboard.as_mut(), &app.ime_options());
+                }
+                _ => (),
+            }
+        }
+    });
+}
@@ -0,0 +1,374 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::image::Image;
+
+/// Errors raised while reading an asset.
+#[derive(Debug)]
+pub enum AssetError {
+    /// No asset exists at the path.
+    NotFound(String),
+    /// The path is absolute or leaves the asset root.
+    InvalidPath(String),
+    /// The asset exists but could not be read.
+    Io(String, io::Error),
+    /// The asset was read as text but is not valid UTF-8.
+    InvalidUtf8(String, std::string::FromUtf8Error),
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound(path) => write!(f, "asset not found: {}", path),
+            Self::InvalidPath(path) => write!(f, "invalid asset path: {}", path),
+            Self::Io(path, e) => write!(f, "failed to read asset {}: {}", path, e),
+            Self::InvalidUtf8(path, e) => write!(f, "asset {} is not UTF-8: {}", path, e),
+        }
+    }
+}
+
+impl std::error::Error for AssetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(_, e) => Some(e),
+            Self::InvalidUtf8(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Streaming access to an asset, returned by [`Assets::open`].
+pub struct AssetReader {
+    inner: Box<dyn ReadSeek>,
+    len: u64,
+}
+
+impl AssetReader {
+    /// Size of the asset in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the asset is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl fmt::Debug for AssetReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AssetReader")
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl Read for AssetReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Seek for AssetReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[cfg(target_os = "android")]
+mod android {
+    use std::io;
+    use std::ptr::NonNull;
+    use std::sync::OnceLock;
+
+    use jni::objects::{GlobalRef, JObject};
+    use ndk::asset::AssetManager;
+
+    /// The native `AssetManager` with a global reference to the Java object it belongs to, as it
+    /// is only valid while the Java object is.
+    struct ApkManager {
+        manager: AssetManager,
+        _java_manager: GlobalRef,
+    }
+
+    // SAFETY: `AAssetManager` may be used from any thread, only the assets it opens may not.
+    unsafe impl Send for ApkManager {}
+    unsafe impl Sync for ApkManager {}
+
+    static MANAGER: OnceLock<ApkManager> = OnceLock::new();
+
+    fn jni_error(e: jni::errors::Error) -> io::Error {
+        io::Error::other(e.to_string())
+    }
+
+    /// The `AssetManager` of the app's context, from `ndk_context`.
+    ///
+    /// It is fetched through JNI on first use and kept for the lifetime of the process.
+    pub fn asset_manager() -> io::Result<&'static AssetManager> {
+        if let Some(cached) = MANAGER.get() {
+            return Ok(&cached.manager);
+        }
+
+        let ctx = ndk_context::android_context();
+        let vm = unsafe { jni::JavaVM::from_raw(ctx.vm().cast()) }.map_err(jni_error)?;
+        let env = vm.attach_current_thread().map_err(jni_error)?;
+
+        let context = JObject::from(ctx.context().cast());
+        let java_manager = env
+            .call_method(
+                context,
+                "getAssets",
+                "()Landroid/content/res/AssetManager;",
+                &[],
+            )
+            .and_then(|value| value.l())
+            .map_err(jni_error)?;
+        let java_manager = env.new_global_ref(java_manager).map_err(jni_error)?;
+
+        let ptr = unsafe {
+            ndk_sys::AAssetManager_fromJava(
+                env.get_native_interface().cast(),
+                java_manager.as_obj().into_inner().cast(),
+            )
+        };
+        let ptr = NonNull::new(ptr).ok_or_else(|| io::Error::other("no native AssetManager"))?;
+        // Another thread may have got there first, in which case this manager is dropped
+        let cached = MANAGER.get_or_init(|| ApkManager {
+            manager: unsafe { AssetManager::from_ptr(ptr) },
+            _java_manager: java_manager,
+        });
+        Ok(&cached.manager)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Source {
+    Dir(PathBuf),
+    #[cfg(target_os = "android")]
+    Apk,
+}
+
+/// Read-only files bundled with the app.
+///
+/// On Android these are the `assets` of the APK, read through the `AssetManager` of the app's
+/// context. Elsewhere they are read from a directory, `assets` in the working directory by
+/// default. Paths are relative and separated by `/`, the same on every platform.
+#[derive(Debug, Clone)]
+pub struct Assets {
+    source: Source,
+}
+
+impl Assets {
+    /// The APK assets on Android, the files in `dir` elsewhere.
+    #[cfg(target_os = "android")]
+    pub fn for_platform(_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            source: Source::Apk,
+        }
+    }
+
+    /// The APK assets on Android, the files in `dir` elsewhere.
+    #[cfg(not(target_os = "android"))]
+    pub fn for_platform(dir: impl Into<PathBuf>) -> Self {
+        Self::from_dir(dir)
+    }
+
+    /// The files in `dir`, on every platform. Useful in tests.
+    pub fn from_dir(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            source: Source::Dir(dir.into()),
+        }
+    }
+
+    /// Open an asset for streaming reads.
+    pub fn open(&self, path: &str) -> Result<AssetReader, AssetError> {
+        let relative = Self::validate(path)?;
+
+        match &self.source {
+            Source::Dir(root) => {
+                let file = File::open(root.join(relative)).map_err(|e| match e.kind() {
+                    io::ErrorKind::NotFound => AssetError::NotFound(path.to_string()),
+                    _ => AssetError::Io(path.to_string(), e),
+                })?;
+                let metadata = file
+                    .metadata()
+                    .map_err(|e| AssetError::Io(path.to_string(), e))?;
+                if metadata.is_dir() {
+                    return Err(AssetError::NotFound(path.to_string()));
+                }
+
+                Ok(AssetReader {
+                    inner: Box::new(BufReader::new(file)),
+                    len: metadata.len(),
+                })
+            }
+            #[cfg(target_os = "android")]
+            Source::Apk => {
+                let name = std::ffi::CString::new(path)
+                    .map_err(|_| AssetError::InvalidPath(path.to_string()))?;
+                let asset = android::asset_manager()
+                    .map_err(|e| AssetError::Io(path.to_string(), e))?
+                    .open(&name)
+                    .ok_or_else(|| AssetError::NotFound(path.to_string()))?;
+                let len = asset.get_length() as u64;
+
+                Ok(AssetReader {
+                    inner: Box::new(asset),
+                    len,
+                })
+            }
+        }
+    }
+
+    /// Read a whole asset.
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, AssetError> {
+        let mut reader = self.open(path)?;
+        let mut data = Vec::with_capacity(reader.len() as usize);
+        reader
+            .read_to_end(&mut data)
+            .map_err(|e| AssetError::Io(path.to_string(), e))?;
+        Ok(data)
+    }
+
+    /// Read a whole asset as UTF-8 text.
+    pub fn read_to_string(&self, path: &str) -> Result<String, AssetError> {
+        let data = self.read(path)?;
+        String::from_utf8(data).map_err(|e| AssetError::InvalidUtf8(path.to_string(), e))
+    }
+
+    /// Read and decode an image, see [`Image::decode`].
+    pub fn image(&self, path: &str) -> anyhow::Result<Image> {
+        let data = self.read(path)?;
+        Image::decode(&data).with_context(|| format!("failed to decode asset {}", path))
+    }
+
+    /// Whether an asset exists at `path`.
+    pub fn exists(&self, path: &str) -> bool {
+        self.open(path).is_ok()
+    }
+
+    /// Check that `path` stays inside the asset root and names the same file everywhere.
+    ///
+    /// The APK's `AssetManager` takes the path as is, so `.` and empty segments are rejected
+    /// rather than normalized away like `Path` would.
+    fn validate(path: &str) -> Result<&Path, AssetError> {
+        let relative = Path::new(path);
+        let valid = !path.contains('\\')
+            && path
+                .split('/')
+                .all(|segment| !segment.is_empty() && segment != ".")
+            && relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        if valid {
+            Ok(relative)
+        } else {
+            Err(AssetError::InvalidPath(path.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Assets in a fresh directory with a few files.
+    fn assets(name: &str) -> Assets {
+        let dir = std::env::temp_dir().join(format!(
+            "pixels-android-assets-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("levels")).unwrap();
+        std::fs::write(dir.join("hello.txt"), "hello").unwrap();
+        std::fs::write(dir.join("levels/1.bin"), [0, 1, 2, 3, 4, 5, 6, 7]).unwrap();
+        std::fs::write(dir.join("bad.txt"), [b'a', 0xff, b'b']).unwrap();
+        Assets::from_dir(dir)
+    }
+
+    #[test]
+    fn read() {
+        let assets = assets("read");
+        assert_eq!(assets.read("hello.txt").unwrap(), b"hello");
+        assert_eq!(assets.read_to_string("hello.txt").unwrap(), "hello");
+        assert_eq!(
+            assets.read("levels/1.bin").unwrap(),
+            [0, 1, 2, 3, 4, 5, 6, 7]
+        );
+        assert!(assets.exists("levels/1.bin"));
+    }
+
+    #[test]
+    fn streaming_read() {
+        let assets = assets("stream");
+        let mut reader = assets.open("levels/1.bin").unwrap();
+        assert_eq!(reader.len(), 8);
+        assert!(!reader.is_empty());
+
+        let mut buf = [0; 3];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [0, 1, 2]);
+        reader.seek(SeekFrom::End(-2)).unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, [6, 7]);
+        reader.seek(SeekFrom::Start(4)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [4, 5, 6]);
+    }
+
+    #[test]
+    fn not_found() {
+        let assets = assets("not-found");
+        assert!(matches!(
+            assets.read("missing.txt"),
+            Err(AssetError::NotFound(path)) if path == "missing.txt"
+        ));
+        // Directories are not assets
+        assert!(matches!(
+            assets.open("levels"),
+            Err(AssetError::NotFound(_))
+        ));
+        assert!(!assets.exists("levels/2.bin"));
+    }
+
+    #[test]
+    fn invalid_paths() {
+        let assets = assets("invalid");
+        for path in [
+            "",
+            "/hello.txt",
+            "../hello.txt",
+            "levels/../hello.txt",
+            "levels\\1.bin",
+            "./hello.txt",
+            "levels/./1.bin",
+            "levels//1.bin",
+            "levels/",
+        ] {
+            assert!(
+                matches!(assets.open(path), Err(AssetError::InvalidPath(_))),
+                "{:?}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let assets = assets("utf8");
+        assert_eq!(assets.read("bad.txt").unwrap(), [b'a', 0xff, b'b']);
+        let error = assets.read_to_string("bad.txt").unwrap_err();
+        assert!(matches!(error, AssetError::InvalidUtf8(ref path, _) if path == "bad.txt"));
+        assert!(error.to_string().contains("not UTF-8"), "{}", error);
+    }
+}
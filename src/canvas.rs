@@ -0,0 +1,844 @@
+use crate::image::Image;
+
+/// How drawn colors are combined with the pixels already in the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// Overwrite the pixel, including its alpha.
+    Replace,
+    /// Blend by the alpha of the drawn color. Opaque colors overwrite the pixel.
+    #[default]
+    Alpha,
+}
+
+/// A rectangle with its top left corner at `(x, y)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Whether the rectangle covers no pixels.
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Whether `(x, y)` lies inside the rectangle.
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        let (x, y) = (x as i64, y as i64);
+        x >= self.x as i64
+            && y >= self.y as i64
+            && x < self.x as i64 + self.width as i64
+            && y < self.y as i64 + self.height as i64
+    }
+
+    /// The area covered by both rectangles, empty if they don't overlap.
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let x0 = self.x.max(other.x);
+        let y0 = self.y.max(other.y);
+        let x1 = (self.x as i64 + self.width as i64).min(other.x as i64 + other.width as i64);
+        let y1 = (self.y as i64 + self.height as i64).min(other.y as i64 + other.height as i64);
+
+        Rect {
+            x: x0,
+            y: y0,
+            width: (x1 - x0 as i64).max(0) as u32,
+            height: (y1 - y0 as i64).max(0) as u32,
+        }
+    }
+}
+
+/// How an [`Image`] is drawn by [`Canvas::blit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlitOptions {
+    /// Part of the image to draw, clipped to the image. Defaults to the whole image.
+    pub source: Option<Rect>,
+    /// Size in the frame, scaled with nearest neighbor sampling. Defaults to the source size.
+    pub size: Option<(u32, u32)>,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    /// Pixels of this color are skipped.
+    pub color_key: Option<[u8; 3]>,
+    /// Opacity multiplied with the alpha of the image.
+    pub alpha: u8,
+}
+
+impl Default for BlitOptions {
+    fn default() -> Self {
+        Self {
+            source: None,
+            size: None,
+            flip_x: false,
+            flip_y: false,
+            color_key: None,
+            alpha: 0xff,
+        }
+    }
+}
+
+impl BlitOptions {
+    pub fn with_source(mut self, source: Rect) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.size = Some((width, height));
+        self
+    }
+
+    pub fn with_flip(mut self, flip_x: bool, flip_y: bool) -> Self {
+        self.flip_x = flip_x;
+        self.flip_y = flip_y;
+        self
+    }
+
+    pub fn with_color_key(mut self, rgb: [u8; 3]) -> Self {
+        self.color_key = Some(rgb);
+        self
+    }
+
+    pub fn with_alpha(mut self, alpha: u8) -> Self {
+        self.alpha = alpha;
+        self
+    }
+}
+
+/// Drawing primitives on an RGBA frame buffer.
+///
+/// Coordinates are in pixels with the origin at the top left. Shapes may extend past the edges
+/// of the buffer and are clipped, and every pixel of a shape is drawn exactly once, so
+/// translucent shapes blend evenly.
+#[derive(Debug)]
+pub struct Canvas<'a> {
+    data: &'a mut [u8],
+    width: u32,
+    height: u32,
+    blend_mode: BlendMode,
+}
+
+impl<'a> Canvas<'a> {
+    /// Wrap a frame of `width * height` RGBA pixels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` is not `width * height * 4` bytes long.
+    pub fn new(data: &'a mut [u8], width: u32, height: u32) -> Self {
+        assert_eq!(
+            data.len(),
+            width as usize * height as usize * 4,
+            "frame size does not match {}x{}",
+            width,
+            height
+        );
+
+        Self {
+            data,
+            width,
+            height,
+            blend_mode: BlendMode::default(),
+        }
+    }
+
+    /// Width of the frame in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the frame in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The RGBA pixel data.
+    pub fn data(&self) -> &[u8] {
+        self.data
+    }
+
+    /// The frame bounds, at the origin.
+    pub fn rect(&self) -> Rect {
+        Rect::new(0, 0, self.width, self.height)
+    }
+
+    /// How drawn colors are combined with the frame.
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    /// Set how drawn colors are combined with the frame.
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
+
+    /// Color of the pixel at `(x, y)`, or `None` outside of the frame.
+    pub fn pixel(&self, x: i32, y: i32) -> Option<[u8; 4]> {
+        let i = self.index(x, y)?;
+        let mut rgba = [0; 4];
+        rgba.copy_from_slice(&self.data[i..i + 4]);
+        Some(rgba)
+    }
+
+    /// Overwrite every pixel, regardless of the blend mode.
+    pub fn clear(&mut self, color: [u8; 4]) {
+        for pixel in self.data.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&color);
+        }
+    }
+
+    /// Draw a single pixel.
+    pub fn plot(&mut self, x: i32, y: i32, color: [u8; 4]) {
+        if let Some(i) = self.index(x, y) {
+            blend(self.blend_mode, &mut self.data[i..i + 4], color);
+        }
+    }
+
+    /// Fill a rectangle with its top left corner at `(x, y)`.
+    pub fn fill_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: [u8; 4]) {
+        let x0 = (x as i64).clamp(0, self.width as i64) as usize;
+        let y0 = (y as i64).clamp(0, self.height as i64) as usize;
+        let x1 = (x as i64 + width as i64).clamp(0, self.width as i64) as usize;
+        let y1 = (y as i64 + height as i64).clamp(0, self.height as i64) as usize;
+
+        let stride = self.width as usize * 4;
+        for row in y0..y1 {
+            let start = row * stride;
+            for pixel in self.data[start + x0 * 4..start + x1 * 4].chunks_exact_mut(4) {
+                blend(self.blend_mode, pixel, color);
+            }
+        }
+    }
+
+    /// Draw the one pixel wide outline of a rectangle.
+    pub fn stroke_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: [u8; 4]) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let right = (x as i64 + width as i64 - 1).min(i32::MAX as i64) as i32;
+        let bottom = (y as i64 + height as i64 - 1).min(i32::MAX as i64) as i32;
+
+        self.fill_rect(x, y, width, 1, color);
+        if height > 1 {
+            self.fill_rect(x, bottom, width, 1, color);
+            self.fill_rect(x, y.saturating_add(1), 1, height - 2, color);
+            if width > 1 {
+                self.fill_rect(right, y.saturating_add(1), 1, height - 2, color);
+            }
+        }
+    }
+
+    /// Draw a line between two points, both included, with the pixels Bresenham's algorithm
+    /// picks.
+    ///
+    /// The line is clipped to the frame before it is walked, so the cost depends on the visible
+    /// part only.
+    pub fn line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: [u8; 4]) {
+        let (x0, y0, x1, y1) = (x0 as i64, y0 as i64, x1 as i64, y1 as i64);
+        let (w, h) = (self.width as i64, self.height as i64);
+        // Skip lines entirely on one side of the frame
+        if (x0 < 0 && x1 < 0) || (y0 < 0 && y1 < 0) || (x0 >= w && x1 >= w) || (y0 >= h && y1 >= h)
+        {
+            return;
+        }
+
+        // Walk the major axis; the minor coordinate of step `i` is `i * minor / major` rounded
+        // half up, which is the pixel Bresenham's error term selects
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let x_major = dx.abs() >= dy.abs();
+        let (major_start, major_delta, major_len, minor_start, minor_delta) = if x_major {
+            (x0, dx, w, y0, dy)
+        } else {
+            (y0, dy, h, x0, dx)
+        };
+        let steps = major_delta.abs();
+        let major_step = major_delta.signum();
+
+        // Steps whose major coordinate lies inside the frame
+        let (first, last) = if major_step >= 0 {
+            (-major_start, major_len - 1 - major_start)
+        } else {
+            (major_start - (major_len - 1), major_start)
+        };
+        let (first, last) = (first.max(0), last.min(steps));
+
+        for i in first..=last {
+            let minor = if steps == 0 {
+                0
+            } else {
+                let offset = (2 * i as i128 * minor_delta.abs() as i128 + steps as i128)
+                    / (2 * steps as i128);
+                offset as i64 * minor_delta.signum()
+            };
+            let major = major_start + i * major_step;
+            let minor = minor_start + minor;
+            if x_major {
+                self.plot_i64(major, minor, color);
+            } else {
+                self.plot_i64(minor, major, color);
+            }
+        }
+    }
+
+    /// Draw the outline of a circle.
+    pub fn circle(&mut self, cx: i32, cy: i32, radius: u32, color: [u8; 4]) {
+        self.ellipse(cx, cy, radius, radius, color);
+    }
+
+    /// Fill a circle.
+    pub fn fill_circle(&mut self, cx: i32, cy: i32, radius: u32, color: [u8; 4]) {
+        self.fill_ellipse(cx, cy, radius, radius, color);
+    }
+
+    /// Draw the outline of an axis aligned ellipse with radii `rx` and `ry`.
+    pub fn ellipse(&mut self, cx: i32, cy: i32, rx: u32, ry: u32, color: [u8; 4]) {
+        let (cx, cy) = (cx as i64, cy as i64);
+        let (rx, ry) = (rx as i64, ry as i64);
+        if rx == 0 || ry == 0 {
+            self.line_i64(cx - rx, cy - ry, cx + rx, cy + ry, color);
+            return;
+        }
+
+        // Midpoint ellipse algorithm, mirrored into the four quadrants
+        let (rx2, ry2) = ((rx * rx) as f64, (ry * ry) as f64);
+        let (mut x, mut y) = (0, ry);
+        let (mut px, mut py) = (0.0, 2.0 * rx2 * y as f64);
+
+        let mut p = ry2 - rx2 * ry as f64 + rx2 / 4.0;
+        while px < py {
+            self.plot_quadrants(cx, cy, x, y, color);
+            x += 1;
+            px += 2.0 * ry2;
+            if p < 0.0 {
+                p += ry2 + px;
+            } else {
+                y -= 1;
+                py -= 2.0 * rx2;
+                p += ry2 + px - py;
+            }
+        }
+
+        let (xf, yf) = (x as f64 + 0.5, (y - 1) as f64);
+        let mut p = ry2 * xf * xf + rx2 * yf * yf - rx2 * ry2;
+        while y >= 0 {
+            self.plot_quadrants(cx, cy, x, y, color);
+            y -= 1;
+            py -= 2.0 * rx2;
+            if p > 0.0 {
+                p += rx2 - py;
+            } else {
+                x += 1;
+                px += 2.0 * ry2;
+                p += rx2 - py + px;
+            }
+        }
+    }
+
+    /// Fill an axis aligned ellipse with radii `rx` and `ry`.
+    pub fn fill_ellipse(&mut self, cx: i32, cy: i32, rx: u32, ry: u32, color: [u8; 4]) {
+        let (rx, ry) = (rx as f64, ry as i64);
+        // Only the rows inside the frame
+        let first = (-ry).max(-(cy as i64));
+        let last = ry.min(self.height as i64 - 1 - cy as i64);
+        for dy in first..=last {
+            // Measure to the pixel edges so the tips aren't single pixels
+            let t = dy as f64 / (ry as f64 + 0.5);
+            let half = ((rx + 0.5) * (1.0 - t * t).sqrt() - 0.5).round() as i64;
+            self.span(
+                cx as i64 - half,
+                cx as i64 + half + 1,
+                cy as i64 + dy,
+                color,
+            );
+        }
+    }
+
+    /// Fill a polygon using the even-odd rule.
+    ///
+    /// A pixel is filled when its center lies inside the polygon, so polygons sharing an edge
+    /// don't overlap.
+    pub fn fill_polygon(&mut self, points: &[(i32, i32)], color: [u8; 4]) {
+        if points.len() < 3 {
+            return;
+        }
+        let min_y = points.iter().map(|p| p.1).min().unwrap_or(0).max(0);
+        let max_y = points
+            .iter()
+            .map(|p| p.1)
+            .max()
+            .unwrap_or(0)
+            .min(self.height as i32);
+
+        let mut crossings = Vec::with_capacity(points.len());
+        for y in min_y..max_y {
+            let center = y as f64 + 0.5;
+            crossings.clear();
+            for (i, &(x0, y0)) in points.iter().enumerate() {
+                let (x1, y1) = points[(i + 1) % points.len()];
+                let (y0f, y1f) = (y0 as f64, y1 as f64);
+                if (y0f <= center && center < y1f) || (y1f <= center && center < y0f) {
+                    let t = (center - y0f) / (y1f - y0f);
+                    crossings.push(x0 as f64 + t * (x1 - x0) as f64);
+                }
+            }
+            crossings.sort_unstable_by(|a, b| a.total_cmp(b));
+
+            for pair in crossings.chunks_exact(2) {
+                let start = (pair[0] - 0.5).ceil() as i64;
+                let end = (pair[1] - 0.5).ceil() as i64;
+                self.span(start, end, y as i64, color);
+            }
+        }
+    }
+
+    /// Replace the area of same colored pixels connected to `(x, y)` with `color`.
+    ///
+    /// Pixels are connected horizontally and vertically. The color is written as is, regardless
+    /// of the blend mode.
+    pub fn flood_fill(&mut self, x: i32, y: i32, color: [u8; 4]) {
+        let target = match self.pixel(x, y) {
+            Some(target) if target != color => target,
+            _ => return,
+        };
+        let matches = |canvas: &Self, x: i64, y: i64| {
+            let i = ((y * canvas.width as i64 + x) * 4) as usize;
+            canvas.data[i..i + 4] == target
+        };
+
+        // Scanline fill: fill a whole run, then queue the runs above and below it
+        let (w, h) = (self.width as i64, self.height as i64);
+        let mut stack = vec![(x as i64, y as i64)];
+        while let Some((x, y)) = stack.pop() {
+            if !matches(self, x, y) {
+                continue;
+            }
+            let mut start = x;
+            while start > 0 && matches(self, start - 1, y) {
+                start -= 1;
+            }
+            let mut end = x + 1;
+            while end < w && matches(self, end, y) {
+                end += 1;
+            }
+
+            let row = (y * w * 4) as usize;
+            for pixel in
+                self.data[row + start as usize * 4..row + end as usize * 4].chunks_exact_mut(4)
+            {
+                pixel.copy_from_slice(&color);
+            }
+
+            for next_y in [y - 1, y + 1] {
+                if next_y < 0 || next_y >= h {
+                    continue;
+                }
+                let mut in_run = false;
+                for next_x in start..end {
+                    let inside = matches(self, next_x, next_y);
+                    if inside && !in_run {
+                        stack.push((next_x, next_y));
+                    }
+                    in_run = inside;
+                }
+            }
+        }
+    }
+
+    /// Draw a whole image with its top left corner at `(x, y)`.
+    pub fn draw_image(&mut self, image: &Image, x: i32, y: i32) {
+        self.blit(image, x, y, &BlitOptions::default());
+    }
+
+    /// Draw (part of) an image with its top left corner at `(x, y)`.
+    pub fn blit(&mut self, image: &Image, x: i32, y: i32, options: &BlitOptions) {
+        let source = options
+            .source
+            .unwrap_or_else(|| image.rect())
+            .intersect(&image.rect());
+        let (width, height) = options.size.unwrap_or((source.width, source.height));
+        if source.is_empty() || width == 0 || height == 0 {
+            return;
+        }
+
+        let dest = Rect::new(x, y, width, height).intersect(&self.rect());
+        for dy in dest.y..dest.y + dest.height as i32 {
+            let mut v = ((dy as i64 - y as i64) * source.height as i64 / height as i64) as u32;
+            if options.flip_y {
+                v = source.height - 1 - v;
+            }
+            let row = (dy as usize * self.width as usize) * 4;
+
+            for dx in dest.x..dest.x + dest.width as i32 {
+                let mut u = ((dx as i64 - x as i64) * source.width as i64 / width as i64) as u32;
+                if options.flip_x {
+                    u = source.width - 1 - u;
+                }
+
+                let mut color = image.pixel(source.x as u32 + u, source.y as u32 + v);
+                if options.color_key == Some([color[0], color[1], color[2]]) {
+                    continue;
+                }
+                if options.alpha != 0xff {
+                    color[3] = (color[3] as u32 * options.alpha as u32 / 255) as u8;
+                }
+                let i = row + dx as usize * 4;
+                blend(self.blend_mode, &mut self.data[i..i + 4], color);
+            }
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height {
+            return None;
+        }
+        Some((y as usize * self.width as usize + x as usize) * 4)
+    }
+
+    fn plot_i64(&mut self, x: i64, y: i64, color: [u8; 4]) {
+        if x >= i32::MIN as i64
+            && x <= i32::MAX as i64
+            && y >= i32::MIN as i64
+            && y <= i32::MAX as i64
+        {
+            self.plot(x as i32, y as i32, color);
+        }
+    }
+
+    fn line_i64(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, color: [u8; 4]) {
+        let clamp = |v: i64| v.clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+        self.line(clamp(x0), clamp(y0), clamp(x1), clamp(y1), color);
+    }
+
+    /// Plot `(x, y)` mirrored around the center, each distinct point once.
+    fn plot_quadrants(&mut self, cx: i64, cy: i64, x: i64, y: i64, color: [u8; 4]) {
+        self.plot_i64(cx + x, cy + y, color);
+        if x != 0 {
+            self.plot_i64(cx - x, cy + y, color);
+        }
+        if y != 0 {
+            self.plot_i64(cx + x, cy - y, color);
+            if x != 0 {
+                self.plot_i64(cx - x, cy - y, color);
+            }
+        }
+    }
+
+    /// Draw the pixels `start..end` of row `y`.
+    fn span(&mut self, start: i64, end: i64, y: i64, color: [u8; 4]) {
+        if y < 0 || y >= self.height as i64 {
+            return;
+        }
+        let start = start.clamp(0, self.width as i64) as usize;
+        let end = end.clamp(0, self.width as i64) as usize;
+        let row = y as usize * self.width as usize * 4;
+        for pixel in self.data[row + start * 4..row + end.max(start) * 4].chunks_exact_mut(4) {
+            blend(self.blend_mode, pixel, color);
+        }
+    }
+}
+
+/// Combine `color` into `pixel` according to `mode`.
+pub(crate) fn blend(mode: BlendMode, pixel: &mut [u8], color: [u8; 4]) {
+    let alpha = color[3] as u32;
+    if mode == BlendMode::Replace || alpha == 0xff {
+        pixel.copy_from_slice(&color);
+        return;
+    }
+    if alpha == 0 {
+        return;
+    }
+
+    for (dst, src) in pixel[..3].iter_mut().zip(color) {
+        *dst = ((src as u32 * alpha + *dst as u32 * (255 - alpha) + 127) / 255) as u8;
+    }
+    pixel[3] = (alpha + (pixel[3] as u32 * (255 - alpha) + 127) / 255) as u8;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 4] = [0xff, 0, 0, 0xff];
+    const BLACK: [u8; 4] = [0, 0, 0, 0xff];
+
+    /// Draw on a `width * height` black frame and return its rows, `#` for drawn pixels.
+    fn draw(width: u32, height: u32, f: impl FnOnce(&mut Canvas)) -> Vec<String> {
+        let mut data = vec![0; width as usize * height as usize * 4];
+        let mut canvas = Canvas::new(&mut data, width, height);
+        canvas.clear(BLACK);
+        f(&mut canvas);
+        data.chunks_exact(width as usize * 4)
+            .map(|row| {
+                row.chunks_exact(4)
+                    .map(|pixel| if pixel == BLACK { '.' } else { '#' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn lit(rows: &[String]) -> Vec<(i32, i32)> {
+        let mut points = Vec::new();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == '#' {
+                    points.push((x as i32, y as i32));
+                }
+            }
+        }
+        points
+    }
+
+    #[test]
+    fn fill_rect_clips_negative_origin() {
+        let rows = draw(4, 4, |c| c.fill_rect(-2, -1, 4, 3, RED));
+        assert_eq!(rows, ["##..", "##..", "....", "...."]);
+    }
+
+    #[test]
+    fn fill_rect_clips_overflow() {
+        let rows = draw(4, 4, |c| c.fill_rect(2, 3, 10, 10, RED));
+        assert_eq!(rows, ["....", "....", "....", "..##"]);
+
+        let rows = draw(4, 4, |c| c.fill_rect(i32::MAX, 0, u32::MAX, 1, RED));
+        assert_eq!(rows, ["....", "....", "....", "...."]);
+
+        // Spans to i32::MAX - 1, covering the whole frame
+        let rows = draw(4, 4, |c| {
+            c.fill_rect(i32::MIN, i32::MIN, u32::MAX, u32::MAX, RED)
+        });
+        assert_eq!(rows, ["####", "####", "####", "####"]);
+    }
+
+    #[test]
+    fn stroke_rect_clips() {
+        let rows = draw(4, 4, |c| c.stroke_rect(-1, -1, 4, 4, RED));
+        assert_eq!(rows, ["..#.", "..#.", "###.", "...."]);
+
+        let rows = draw(4, 4, |c| c.stroke_rect(2, 2, 10, 10, RED));
+        assert_eq!(rows, ["....", "....", "..##", "..#."]);
+    }
+
+    #[test]
+    fn stroke_rect_draws_each_pixel_once() {
+        let mut data = vec![0; 4 * 4 * 4];
+        let mut canvas = Canvas::new(&mut data, 4, 4);
+        canvas.stroke_rect(0, 0, 4, 4, [0xff, 0xff, 0xff, 0x80]);
+        // A pixel drawn twice would be more opaque than one drawn once
+        assert_eq!(canvas.pixel(0, 0), canvas.pixel(1, 0));
+        assert_eq!(canvas.pixel(0, 0), canvas.pixel(0, 1));
+        assert_eq!(canvas.pixel(3, 3), canvas.pixel(3, 1));
+        assert_eq!(canvas.pixel(1, 1), Some([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn line_shallow() {
+        let rows = draw(7, 3, |c| c.line(0, 0, 6, 2, RED));
+        assert_eq!(rows, ["##.....", "..###..", ".....##"]);
+        // The same pixels in the other direction
+        let reversed = draw(7, 3, |c| c.line(6, 2, 0, 0, RED));
+        assert_eq!(rows, reversed);
+    }
+
+    #[test]
+    fn line_all_octants() {
+        // Offsets of a line from the origin to (3, 1)
+        let base = [(0, 0), (1, 0), (2, 1), (3, 1)];
+        for swap in [false, true] {
+            for sx in [-1, 1] {
+                for sy in [-1, 1] {
+                    let transform = |(a, b): (i32, i32)| {
+                        let (x, y) = if swap { (b, a) } else { (a, b) };
+                        (3 + sx * x, 3 + sy * y)
+                    };
+                    let (x1, y1) = transform((3, 1));
+                    let rows = draw(7, 7, |c| c.line(3, 3, x1, y1, RED));
+
+                    let mut expected: Vec<_> = base.iter().map(|&p| transform(p)).collect();
+                    expected.sort_by_key(|&(x, y)| (y, x));
+                    assert_eq!(lit(&rows), expected, "line to ({}, {})", x1, y1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn line_single_point_and_diagonal() {
+        let rows = draw(3, 3, |c| c.line(1, 1, 1, 1, RED));
+        assert_eq!(rows, ["...", ".#.", "..."]);
+
+        let rows = draw(3, 3, |c| c.line(2, 0, 0, 2, RED));
+        assert_eq!(rows, ["..#", ".#.", "#.."]);
+    }
+
+    #[test]
+    fn line_clips_extreme_endpoints() {
+        let rows = draw(4, 2, |c| c.line(i32::MIN, 0, i32::MAX, 0, RED));
+        assert_eq!(rows, ["####", "...."]);
+
+        let rows = draw(4, 4, |c| {
+            c.line(i32::MIN, i32::MIN, i32::MAX, i32::MAX, RED)
+        });
+        assert_eq!(rows, ["#...", ".#..", "..#.", "...#"]);
+
+        let rows = draw(4, 4, |c| c.line(1, i32::MAX, 1, i32::MIN, RED));
+        assert_eq!(rows, [".#..", ".#..", ".#..", ".#.."]);
+
+        let rows = draw(4, 4, |c| c.line(-10, -10, -1, 20, RED));
+        assert_eq!(rows, ["....", "....", "....", "...."]);
+    }
+
+    #[test]
+    fn circle_outline() {
+        let rows = draw(7, 7, |c| c.circle(3, 3, 3, RED));
+        assert_eq!(
+            rows,
+            ["..###..", ".#...#.", "#.....#", "#.....#", "#.....#", ".#...#.", "..###..",]
+        );
+    }
+
+    #[test]
+    fn ellipse_outline() {
+        let rows = draw(9, 3, |c| c.ellipse(4, 1, 4, 1, RED));
+        assert_eq!(rows, [".#######.", "#.......#", ".#######."]);
+
+        // Zero radius degenerates to a line
+        let rows = draw(5, 3, |c| c.ellipse(2, 1, 2, 0, RED));
+        assert_eq!(rows, [".....", "#####", "....."]);
+    }
+
+    #[test]
+    fn fill_circle_and_ellipse() {
+        let rows = draw(7, 7, |c| c.fill_circle(3, 3, 3, RED));
+        assert_eq!(
+            rows,
+            ["..###..", ".#####.", "#######", "#######", "#######", ".#####.", "..###..",]
+        );
+
+        let rows = draw(9, 3, |c| c.fill_ellipse(4, 1, 4, 1, RED));
+        assert_eq!(rows, [".#######.", "#########", ".#######."]);
+    }
+
+    #[test]
+    fn fill_ellipse_clips_rows() {
+        let rows = draw(4, 2, |c| c.fill_ellipse(1, 0, 1, u32::MAX, RED));
+        assert_eq!(rows, ["###.", "###."]);
+    }
+
+    #[test]
+    fn fill_polygon_triangle() {
+        // Pixels whose centers are inside, so the hypotenuse pixels stay empty
+        let rows = draw(5, 4, |c| c.fill_polygon(&[(0, 0), (4, 0), (0, 4)], RED));
+        assert_eq!(rows, ["###..", "##...", "#....", "....."]);
+    }
+
+    #[test]
+    fn fill_polygon_concave() {
+        // A U shape; the notch between the arms stays empty
+        let points = [
+            (0, 0),
+            (2, 0),
+            (2, 2),
+            (3, 2),
+            (3, 0),
+            (5, 0),
+            (5, 4),
+            (0, 4),
+        ];
+        let rows = draw(5, 4, |c| c.fill_polygon(&points, RED));
+        assert_eq!(rows, ["##.##", "##.##", "#####", "#####"]);
+    }
+
+    #[test]
+    fn fill_polygon_shared_edges_dont_overlap() {
+        let mut data = vec![0; 4 * 2 * 4];
+        let mut canvas = Canvas::new(&mut data, 4, 2);
+        let color = [0xff, 0xff, 0xff, 0x80];
+        canvas.fill_polygon(&[(0, 0), (2, 0), (2, 2), (0, 2)], color);
+        canvas.fill_polygon(&[(2, 0), (4, 0), (4, 2), (2, 2)], color);
+        let first = canvas.pixel(0, 0);
+        assert!((0..4).all(|x| canvas.pixel(x, 1) == first));
+    }
+
+    #[test]
+    fn fill_polygon_degenerate() {
+        let empty = ["....", "....", "...."];
+        assert_eq!(draw(4, 3, |c| c.fill_polygon(&[], RED)), empty);
+        assert_eq!(
+            draw(4, 3, |c| c.fill_polygon(&[(0, 0), (3, 2)], RED)),
+            empty
+        );
+        // Collinear points enclose no area
+        let collinear = [(0, 0), (1, 1), (3, 3)];
+        assert_eq!(draw(4, 3, |c| c.fill_polygon(&collinear, RED)), empty);
+        let flat = [(0, 1), (4, 1), (2, 1)];
+        assert_eq!(draw(4, 3, |c| c.fill_polygon(&flat, RED)), empty);
+    }
+
+    #[test]
+    fn flood_fill_from_boundary() {
+        // Fill the border starting from a corner, leaving the enclosed area alone
+        let rows = draw(5, 5, |c| {
+            c.stroke_rect(1, 1, 3, 3, RED);
+            c.flood_fill(0, 0, RED);
+        });
+        assert_eq!(rows, ["#####", "#####", "##.##", "#####", "#####"]);
+
+        // A region touching the right and bottom edges
+        let rows = draw(4, 4, |c| {
+            c.line(2, 0, 2, 1, RED);
+            c.line(2, 2, 0, 2, RED);
+            c.flood_fill(3, 3, RED);
+        });
+        assert_eq!(rows, ["..##", "..##", "####", "####"]);
+    }
+
+    #[test]
+    fn flood_fill_ignores_same_color_and_outside() {
+        let rows = draw(3, 3, |c| {
+            c.flood_fill(1, 1, BLACK);
+            c.flood_fill(-1, 0, RED);
+            c.flood_fill(0, 3, RED);
+        });
+        assert_eq!(rows, ["...", "...", "..."]);
+    }
+
+    #[test]
+    fn alpha_blending() {
+        let mut pixel = [0, 0, 0xff, 0xff];
+        blend(BlendMode::Alpha, &mut pixel, [0xff, 0, 0, 0x80]);
+        assert_eq!(pixel, [0x80, 0, 0x7f, 0xff]);
+
+        let mut pixel = [0, 0, 0, 0];
+        blend(BlendMode::Alpha, &mut pixel, [0xff, 0, 0, 0x80]);
+        assert_eq!(pixel, [0x80, 0, 0, 0x80]);
+
+        let mut pixel = [10, 20, 30, 40];
+        blend(BlendMode::Alpha, &mut pixel, [0xff, 0xff, 0xff, 0]);
+        assert_eq!(pixel, [10, 20, 30, 40]);
+
+        blend(BlendMode::Replace, &mut pixel, [1, 2, 3, 4]);
+        assert_eq!(pixel, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn canvas_uses_blend_mode() {
+        let mut data = vec![0; 4];
+        let mut canvas = Canvas::new(&mut data, 1, 1);
+        canvas.clear([0, 0, 0, 0xff]);
+        canvas.plot(0, 0, [0xff, 0xff, 0xff, 0x80]);
+        assert_eq!(canvas.pixel(0, 0), Some([0x80, 0x80, 0x80, 0xff]));
+
+        canvas.set_blend_mode(BlendMode::Replace);
+        canvas.plot(0, 0, [0xff, 0xff, 0xff, 0x80]);
+        assert_eq!(canvas.pixel(0, 0), Some([0xff, 0xff, 0xff, 0x80]));
+    }
+}
@@ -0,0 +1,535 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{bail, ensure};
+use log::LevelFilter;
+use pixels::wgpu::Color;
+
+use crate::gesture::GestureConfig;
+use crate::screenshot::ScreenshotTrigger;
+use crate::timing::ControlFlowPolicy;
+use crate::viewport::ScalingMode;
+
+/// Largest buffer width or height accepted by [`AppConfigBuilder::build`], the texture size
+/// wgpu supports with its default limits.
+pub const MAX_BUFFER_SIZE: u32 = 8192;
+
+/// Settings used by [`run_app`](crate::app::run_app) to set up the window, the pixel buffer and
+/// logging.
+///
+/// Create one with [`AppConfig::builder`]; the default describes a 320×240 buffer.
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    width: u32,
+    height: u32,
+    title: String,
+    window_size: Option<(u32, u32)>,
+    min_window_size: Option<(u32, u32)>,
+    vsync: bool,
+    tick_rate: u32,
+    max_catch_up_steps: u32,
+    control_flow: ControlFlowPolicy,
+    clear_color: Color,
+    scaling_mode: ScalingMode,
+    frame_stats_overlay: bool,
+    frame_stats_log_interval: Option<Duration>,
+    resize_debounce: Duration,
+    gesture_config: GestureConfig,
+    log_level: LevelFilter,
+    log_tag: String,
+    assets_dir: PathBuf,
+    state_dir: PathBuf,
+    record_input: Option<PathBuf>,
+    screenshot_dir: PathBuf,
+    screenshot_scale: u32,
+    screenshot_triggers: Vec<ScreenshotTrigger>,
+}
+
+/// Builder for [`AppConfig`].
+#[derive(Debug, Clone)]
+pub struct AppConfigBuilder {
+    config: AppConfig,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            width: 320,
+            height: 240,
+            title: "Hello Pixels".to_string(),
+            window_size: None,
+            min_window_size: None,
+            vsync: true,
+            tick_rate: 60,
+            max_catch_up_steps: 5,
+            control_flow: ControlFlowPolicy::WaitUntilNextFrame,
+            clear_color: Color::BLACK,
+            scaling_mode: ScalingMode::Integer,
+            frame_stats_overlay: false,
+            frame_stats_log_interval: None,
+            resize_debounce: Duration::from_millis(100),
+            gesture_config: GestureConfig::default(),
+            log_level: LevelFilter::Info,
+            log_tag: "pixels-android".to_string(),
+            assets_dir: PathBuf::from("assets"),
+            state_dir: PathBuf::from("saves"),
+            record_input: None,
+            screenshot_dir: PathBuf::from("screenshots"),
+            screenshot_scale: 1,
+            screenshot_triggers: Vec::new(),
+        }
+    }
+}
+
+impl AppConfig {
+    /// Start from the default configuration.
+    pub fn builder() -> AppConfigBuilder {
+        AppConfigBuilder {
+            config: Self::default(),
+        }
+    }
+
+    /// Width of the pixel buffer.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the pixel buffer.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Title of the window.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Initial inner size of the window in logical pixels. Defaults to the buffer size.
+    pub fn window_size(&self) -> (u32, u32) {
+        self.window_size.unwrap_or((self.width, self.height))
+    }
+
+    /// Minimum inner size of the window in logical pixels. Defaults to the buffer size, or to the
+    /// window size where that is smaller.
+    pub fn min_window_size(&self) -> (u32, u32) {
+        let (window_width, window_height) = self.window_size();
+        self.min_window_size
+            .unwrap_or((self.width.min(window_width), self.height.min(window_height)))
+    }
+
+    /// Whether presentation waits for vertical sync.
+    pub fn vsync(&self) -> bool {
+        self.vsync
+    }
+
+    /// Number of [`update`](crate::app::PixelsApp::update) calls per second.
+    pub fn tick_rate(&self) -> u32 {
+        self.tick_rate
+    }
+
+    /// Maximum number of updates run in one frame to catch up after a stall.
+    pub fn max_catch_up_steps(&self) -> u32 {
+        self.max_catch_up_steps
+    }
+
+    /// How the event loop waits between frames.
+    pub fn control_flow(&self) -> ControlFlowPolicy {
+        self.control_flow
+    }
+
+    /// Color of the area around the scaled pixel buffer.
+    pub fn clear_color(&self) -> Color {
+        self.clear_color
+    }
+
+    /// How the pixel buffer is scaled to the window.
+    pub fn scaling_mode(&self) -> ScalingMode {
+        self.scaling_mode
+    }
+
+    /// Whether a frame time graph is drawn over each frame.
+    pub fn frame_stats_overlay(&self) -> bool {
+        self.frame_stats_overlay
+    }
+
+    /// How often frame statistics are logged, if at all.
+    pub fn frame_stats_log_interval(&self) -> Option<Duration> {
+        self.frame_stats_log_interval
+    }
+
+    /// How long the window size has to be stable before the surface is resized.
+    pub fn resize_debounce(&self) -> Duration {
+        self.resize_debounce
+    }
+
+    /// Thresholds of the gesture recognizer.
+    pub fn gesture_config(&self) -> &GestureConfig {
+        &self.gesture_config
+    }
+
+    /// Maximum level of log messages.
+    pub fn log_level(&self) -> LevelFilter {
+        self.log_level
+    }
+
+    /// Tag of log messages on Android.
+    pub fn log_tag(&self) -> &str {
+        &self.log_tag
+    }
+
+    /// Directory read by [`Assets::for_platform`](crate::assets::Assets::for_platform) outside of
+    /// Android.
+    pub fn assets_dir(&self) -> &Path {
+        &self.assets_dir
+    }
+
+    /// Directory the app state is saved in outside of Android, see
+    /// [`PixelsApp::save_state`](crate::app::PixelsApp::save_state).
+    pub fn state_dir(&self) -> &Path {
+        &self.state_dir
+    }
+
+    /// File the input is recorded to, see [`InputRecorder`](crate::replay::InputRecorder).
+    pub fn record_input(&self) -> Option<&Path> {
+        self.record_input.as_deref()
+    }
+
+    /// Directory screenshots and recordings are saved in outside of Android, see
+    /// [`Screenshots`](crate::screenshot::Screenshots).
+    pub fn screenshot_dir(&self) -> &Path {
+        &self.screenshot_dir
+    }
+
+    /// Factor screenshots are scaled up by.
+    pub fn screenshot_scale(&self) -> u32 {
+        self.screenshot_scale
+    }
+
+    /// Inputs that take a screenshot.
+    pub fn screenshot_triggers(&self) -> &[ScreenshotTrigger] {
+        &self.screenshot_triggers
+    }
+
+    /// Check that the settings describe a usable window and buffer.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.width > 0 && self.height > 0,
+            "buffer size must not be zero, got {}x{}",
+            self.width,
+            self.height
+        );
+        ensure!(
+            self.width <= MAX_BUFFER_SIZE && self.height <= MAX_BUFFER_SIZE,
+            "buffer size must be at most {}x{}, got {}x{}",
+            MAX_BUFFER_SIZE,
+            MAX_BUFFER_SIZE,
+            self.width,
+            self.height
+        );
+
+        let (window_width, window_height) = self.window_size();
+        let (min_width, min_height) = self.min_window_size();
+        ensure!(
+            window_width > 0 && window_height > 0,
+            "window size must not be zero, got {}x{}",
+            window_width,
+            window_height
+        );
+        if min_width > window_width || min_height > window_height {
+            bail!(
+                "minimum window size {}x{} exceeds the window size {}x{}",
+                min_width,
+                min_height,
+                window_width,
+                window_height
+            );
+        }
+
+        ensure!(self.tick_rate > 0, "tick rate must not be zero");
+        ensure!(
+            self.max_catch_up_steps > 0,
+            "max catch-up steps must not be zero"
+        );
+
+        ensure!(
+            self.screenshot_scale > 0,
+            "screenshot scale must not be zero"
+        );
+
+        ensure!(!self.log_tag.is_empty(), "log tag must not be empty");
+        ensure!(
+            !self.log_tag.contains('\0'),
+            "log tag must not contain NUL bytes"
+        );
+
+        Ok(())
+    }
+}
+
+impl AppConfigBuilder {
+    /// Size of the pixel buffer, at most [`MAX_BUFFER_SIZE`] in each direction.
+    pub fn buffer_size(mut self, width: u32, height: u32) -> Self {
+        self.config.width = width;
+        self.config.height = height;
+        self
+    }
+
+    /// Title of the window.
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.config.title = title.into();
+        self
+    }
+
+    /// Initial inner size of the window in logical pixels.
+    pub fn window_size(mut self, width: u32, height: u32) -> Self {
+        self.config.window_size = Some((width, height));
+        self
+    }
+
+    /// Minimum inner size of the window in logical pixels.
+    pub fn min_window_size(mut self, width: u32, height: u32) -> Self {
+        self.config.min_window_size = Some((width, height));
+        self
+    }
+
+    /// Whether presentation waits for vertical sync.
+    pub fn vsync(mut self, vsync: bool) -> Self {
+        self.config.vsync = vsync;
+        self
+    }
+
+    /// Number of [`update`](crate::app::PixelsApp::update) calls per second.
+    pub fn tick_rate(mut self, tick_rate: u32) -> Self {
+        self.config.tick_rate = tick_rate;
+        self
+    }
+
+    /// Maximum number of updates run in one frame to catch up after a stall.
+    pub fn max_catch_up_steps(mut self, steps: u32) -> Self {
+        self.config.max_catch_up_steps = steps;
+        self
+    }
+
+    /// How the event loop waits between frames.
+    pub fn control_flow(mut self, control_flow: ControlFlowPolicy) -> Self {
+        self.config.control_flow = control_flow;
+        self
+    }
+
+    /// Color of the area around the scaled pixel buffer.
+    pub fn clear_color(mut self, color: Color) -> Self {
+        self.config.clear_color = color;
+        self
+    }
+
+    /// Color of the letterbox around the scaled pixel buffer as sRGB bytes, like the colors drawn
+    /// to the buffer. Sets the [`clear_color`](Self::clear_color).
+    pub fn letterbox_color(self, color: [u8; 3]) -> Self {
+        self.clear_color(Color {
+            r: srgb_to_linear(color[0]),
+            g: srgb_to_linear(color[1]),
+            b: srgb_to_linear(color[2]),
+            a: 1.0,
+        })
+    }
+
+    /// How the pixel buffer is scaled to the window. Defaults to [`ScalingMode::Integer`].
+    pub fn scaling_mode(mut self, mode: ScalingMode) -> Self {
+        self.config.scaling_mode = mode;
+        self
+    }
+
+    /// Draw a frame time graph over each frame, after the app has drawn.
+    pub fn frame_stats_overlay(mut self, enabled: bool) -> Self {
+        self.config.frame_stats_overlay = enabled;
+        self
+    }
+
+    /// Log frame statistics every `interval`.
+    pub fn log_frame_stats(mut self, interval: Duration) -> Self {
+        self.config.frame_stats_log_interval = Some(interval);
+        self
+    }
+
+    /// How long the window size has to be stable before the surface is resized. Defaults to
+    /// 100 ms; zero resizes on the next frame.
+    pub fn resize_debounce(mut self, delay: Duration) -> Self {
+        self.config.resize_debounce = delay;
+        self
+    }
+
+    /// Thresholds used to recognize gestures. Defaults to [`GestureConfig::default`].
+    pub fn gesture_config(mut self, config: GestureConfig) -> Self {
+        self.config.gesture_config = config;
+        self
+    }
+
+    /// Maximum level of log messages.
+    pub fn log_level(mut self, level: LevelFilter) -> Self {
+        self.config.log_level = level;
+        self
+    }
+
+    /// Tag of log messages on Android.
+    pub fn log_tag(mut self, tag: impl Into<String>) -> Self {
+        self.config.log_tag = tag.into();
+        self
+    }
+
+    /// Directory read by [`Assets::for_platform`](crate::assets::Assets::for_platform) outside of
+    /// Android. Relative paths are resolved against the working directory.
+    pub fn assets_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.assets_dir = dir.into();
+        self
+    }
+
+    /// Directory the app state is saved in outside of Android. Relative paths are resolved
+    /// against the working directory.
+    pub fn state_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.state_dir = dir.into();
+        self
+    }
+
+    /// Record the input to `path` for [`Replay`](crate::replay::Replay). Saved state is not
+    /// restored while recording, so that a replay starts from the same state.
+    pub fn record_input(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.record_input = Some(path.into());
+        self
+    }
+
+    /// Directory screenshots and recordings are saved in outside of Android. Relative paths are
+    /// resolved against the working directory.
+    pub fn screenshot_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.screenshot_dir = dir.into();
+        self
+    }
+
+    /// Scale screenshots up by an integer factor. Defaults to 1, the buffer resolution.
+    pub fn screenshot_scale(mut self, scale: u32) -> Self {
+        self.config.screenshot_scale = scale;
+        self
+    }
+
+    /// Take a screenshot on `trigger`. Can be called several times to add more triggers.
+    pub fn screenshot_trigger(mut self, trigger: ScreenshotTrigger) -> Self {
+        self.config.screenshot_triggers.push(trigger);
+        self
+    }
+
+    /// Validate the settings and create the configuration.
+    pub fn build(self) -> anyhow::Result<AppConfig> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+/// Convert an sRGB channel to the linear value the surface is cleared with.
+fn srgb_to_linear(value: u8) -> f64 {
+    let value = value as f64 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults() {
+        let config = AppConfig::builder().build().unwrap();
+        assert_eq!((config.width(), config.height()), (320, 240));
+        assert_eq!(config.window_size(), (320, 240));
+        assert_eq!(config.min_window_size(), (320, 240));
+        assert_eq!(config.tick_rate(), 60);
+        assert_eq!(config.scaling_mode(), ScalingMode::Integer);
+        assert_eq!(config.record_input(), None);
+    }
+
+    #[test]
+    fn builder_sets_values() {
+        let config = AppConfig::builder()
+            .buffer_size(64, 48)
+            .title("Test")
+            .window_size(640, 480)
+            .min_window_size(128, 96)
+            .tick_rate(30)
+            .max_catch_up_steps(2)
+            .scaling_mode(ScalingMode::Fit)
+            .log_tag("test")
+            .record_input("input.jsonl")
+            .screenshot_scale(3)
+            .build()
+            .unwrap();
+        assert_eq!((config.width(), config.height()), (64, 48));
+        assert_eq!(config.title(), "Test");
+        assert_eq!(config.window_size(), (640, 480));
+        assert_eq!(config.min_window_size(), (128, 96));
+        assert_eq!(config.tick_rate(), 30);
+        assert_eq!(config.max_catch_up_steps(), 2);
+        assert_eq!(config.scaling_mode(), ScalingMode::Fit);
+        assert_eq!(config.log_tag(), "test");
+        assert_eq!(config.record_input(), Some(Path::new("input.jsonl")));
+        assert_eq!(config.screenshot_scale(), 3);
+    }
+
+    #[test]
+    fn min_window_size_defaults_to_smaller_window() {
+        let config = AppConfig::builder().window_size(160, 120).build().unwrap();
+        assert_eq!(config.min_window_size(), (160, 120));
+
+        let config = AppConfig::builder().window_size(160, 480).build().unwrap();
+        assert_eq!(config.min_window_size(), (160, 240));
+    }
+
+    #[test]
+    fn explicit_min_window_size_is_validated() {
+        let error = AppConfig::builder()
+            .window_size(160, 120)
+            .min_window_size(320, 240)
+            .build()
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("minimum window size"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn buffer_size_bounds() {
+        assert!(AppConfig::builder().buffer_size(0, 240).build().is_err());
+        assert!(AppConfig::builder().buffer_size(320, 0).build().is_err());
+        assert!(AppConfig::builder()
+            .buffer_size(MAX_BUFFER_SIZE, MAX_BUFFER_SIZE)
+            .build()
+            .is_ok());
+
+        let error = AppConfig::builder()
+            .buffer_size(MAX_BUFFER_SIZE + 1, 240)
+            .build()
+            .unwrap_err();
+        assert!(error.to_string().contains("at most"), "{}", error);
+        assert!(AppConfig::builder()
+            .buffer_size(320, u32::MAX)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn invalid_settings() {
+        let invalid = [
+            AppConfig::builder().window_size(0, 240),
+            AppConfig::builder().tick_rate(0),
+            AppConfig::builder().max_catch_up_steps(0),
+            AppConfig::builder().screenshot_scale(0),
+            AppConfig::builder().log_tag(""),
+            AppConfig::builder().log_tag("a\0b"),
+        ];
+        for builder in invalid {
+            assert!(builder.clone().build().is_err(), "{:?}", builder);
+        }
+    }
+}
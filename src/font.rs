@@ -0,0 +1,870 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, ensure, Context};
+
+use crate::canvas::Canvas;
+
+/// Horizontal alignment of the lines of a text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// How a text is laid out and drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextStyle {
+    pub color: [u8; 4],
+    /// Size of a font pixel in frame pixels.
+    pub scale: u32,
+    pub align: Align,
+    /// Width at which lines are wrapped, in frame pixels. Lines are aligned within this width
+    /// when set, otherwise within the widest line.
+    pub max_width: Option<u32>,
+    /// Extra space between lines, in font pixels.
+    pub line_spacing: u32,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self::new([0xff, 0xff, 0xff, 0xff])
+    }
+}
+
+impl TextStyle {
+    /// Unscaled, left aligned and unwrapped text in `color`.
+    pub fn new(color: [u8; 4]) -> Self {
+        Self {
+            color,
+            scale: 1,
+            align: Align::Left,
+            max_width: None,
+            line_spacing: 1,
+        }
+    }
+
+    pub fn with_scale(mut self, scale: u32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn with_max_width(mut self, max_width: u32) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    pub fn with_line_spacing(mut self, line_spacing: u32) -> Self {
+        self.line_spacing = line_spacing;
+        self
+    }
+}
+
+/// A laid out line, positioned relative to the top left corner of the text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextLine {
+    pub text: String,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+}
+
+/// Result of [`Font::layout`]. Sizes are in frame pixels.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TextLayout {
+    pub lines: Vec<TextLine>,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone)]
+struct Glyph {
+    advance: u32,
+    /// `cell_width * height` pixels, row by row.
+    bitmap: Vec<bool>,
+}
+
+/// A bitmap font.
+///
+/// All glyphs share a cell of the same size; the horizontal advance can differ per glyph.
+/// Characters missing from the font are drawn as `?`, or skipped if the font has no `?` either.
+/// The default is a built-in 5×7 ASCII font with descenders in a 6×9 cell.
+#[derive(Debug, Clone)]
+pub struct Font {
+    cell_width: u32,
+    height: u32,
+    glyphs: HashMap<char, Glyph>,
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl Font {
+    /// The built-in ASCII font.
+    pub fn builtin() -> Self {
+        let glyphs = (' '..='~')
+            .zip(BUILTIN_GLYPHS.iter())
+            .map(|(c, rows)| {
+                let bitmap = rows
+                    .iter()
+                    .flat_map(|row| (0..5).rev().map(move |bit| row & (1 << bit) != 0))
+                    .collect();
+                (c, Glyph { advance: 6, bitmap })
+            })
+            .collect();
+
+        Self {
+            cell_width: 5,
+            height: 9,
+            glyphs,
+        }
+    }
+
+    /// Parse a PC Screen Font, version 1 or 2.
+    ///
+    /// Glyphs are mapped through the font's Unicode table; fonts without one are assumed to
+    /// follow Latin-1.
+    pub fn from_psf(data: &[u8]) -> anyhow::Result<Self> {
+        let (width, height, count, glyph_size, offset, has_table) = match data {
+            [0x36, 0x04, mode, size, ..] => {
+                let count = if mode & 0x01 != 0 { 512 } else { 256 };
+                (
+                    8,
+                    *size as usize,
+                    count,
+                    *size as usize,
+                    4,
+                    mode & 0x06 != 0,
+                )
+            }
+            [0x72, 0xb5, 0x4a, 0x86, ..] if data.len() >= 32 => {
+                let field = |i: usize| {
+                    u32::from_le_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]) as usize
+                };
+                let (offset, flags, count, size) = (field(8), field(12), field(16), field(20));
+                let (height, width) = (field(24), field(28));
+                ensure!(
+                    width.div_ceil(8).checked_mul(height) == Some(size),
+                    "PSF glyph size {} does not match {}x{}",
+                    size,
+                    width,
+                    height
+                );
+                (width, height, count, size, offset, flags & 0x01 != 0)
+            }
+            _ => bail!("not a PSF font"),
+        };
+        ensure!(width > 0 && height > 0, "PSF font has empty glyphs");
+        // The header fields are untrusted and may overflow a 32-bit usize
+        let end = count
+            .checked_mul(glyph_size)
+            .and_then(|size| size.checked_add(offset))
+            .context("PSF font is too large")?;
+        ensure!(data.len() >= end, "PSF font is truncated");
+        // The Unicode table follows the glyphs
+        let table = has_table.then_some(end);
+
+        let mut chars: Vec<Vec<char>> = (0..count)
+            .map(|i| char::from_u32(i as u32).into_iter().collect())
+            .collect();
+        if let Some(table) = table {
+            let psf1 = data[0] == 0x36;
+            chars = psf_unicode_table(&data[table..], count, psf1)?;
+        }
+
+        let stride = width.div_ceil(8);
+        let mut glyphs = HashMap::new();
+        for (i, chars) in chars.into_iter().enumerate() {
+            let rows = &data[offset + i * glyph_size..offset + (i + 1) * glyph_size];
+            let bitmap: Vec<bool> = (0..height)
+                .flat_map(|y| (0..width).map(move |x| (y, x)))
+                .map(|(y, x)| rows[y * stride + x / 8] & (0x80 >> (x % 8)) != 0)
+                .collect();
+            for c in chars {
+                glyphs.entry(c).or_insert_with(|| Glyph {
+                    advance: width as u32,
+                    bitmap: bitmap.clone(),
+                });
+            }
+        }
+
+        Ok(Self {
+            cell_width: width as u32,
+            height: height as u32,
+            glyphs,
+        })
+    }
+
+    /// Parse a Glyph Bitmap Distribution Format font.
+    ///
+    /// The cell is the font bounding box; glyphs are placed in it by their own bounding box and
+    /// clipped to it.
+    pub fn from_bdf(text: &str) -> anyhow::Result<Self> {
+        let mut bounds = None;
+        let mut glyphs = HashMap::new();
+        let mut lines = text.lines().map(str::trim);
+        while let Some(line) = lines.next() {
+            let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+            match keyword {
+                "FONTBOUNDINGBOX" => {
+                    let v = bdf_numbers::<4>(rest)?;
+                    ensure!(v[0] > 0 && v[1] > 0, "empty BDF font bounding box");
+                    ensure!(
+                        v[0].checked_mul(v[1]).is_some(),
+                        "BDF font bounding box is too large"
+                    );
+                    bounds = Some(v);
+                }
+                "STARTCHAR" => {
+                    let bounds = bounds.context("BDF glyph before FONTBOUNDINGBOX")?;
+                    if let (Some(c), glyph) = bdf_glyph(&mut lines, bounds)? {
+                        glyphs.insert(c, glyph);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let [width, height, ..] = bounds.context("BDF font has no FONTBOUNDINGBOX")?;
+        Ok(Self {
+            cell_width: width as u32,
+            height: height as u32,
+            glyphs,
+        })
+    }
+
+    /// Height of a line in font pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Whether the font has a glyph for `c`.
+    pub fn contains(&self, c: char) -> bool {
+        self.glyphs.contains_key(&c)
+    }
+
+    /// Horizontal advance of `c` in font pixels.
+    pub fn advance(&self, c: char) -> u32 {
+        self.glyph(c).map_or(0, |glyph| glyph.advance)
+    }
+
+    /// Width of a single line of text in font pixels.
+    pub fn text_width(&self, text: &str) -> u32 {
+        text.chars().map(|c| self.advance(c)).sum()
+    }
+
+    /// Break `text` into lines at newlines and, with a maximum width, between words. Words
+    /// wider than a line are broken between characters.
+    pub fn layout(&self, text: &str, style: &TextStyle) -> TextLayout {
+        let scale = style.scale.max(1);
+        let pitch = (self.height + style.line_spacing) * scale;
+
+        let mut lines = Vec::new();
+        for paragraph in text.split('\n') {
+            match style.max_width {
+                Some(max_width) => self.wrap(paragraph, max_width, scale, &mut lines),
+                None => lines.push((paragraph.to_string(), self.text_width(paragraph) * scale)),
+            }
+        }
+
+        let width = style
+            .max_width
+            .unwrap_or_else(|| lines.iter().map(|(_, width)| *width).max().unwrap_or(0));
+        let height = (pitch * lines.len() as u32).saturating_sub(style.line_spacing * scale);
+        let lines = lines
+            .into_iter()
+            .enumerate()
+            .map(|(i, (text, line_width))| {
+                let free = width.saturating_sub(line_width);
+                let x = match style.align {
+                    Align::Left => 0,
+                    Align::Center => free / 2,
+                    Align::Right => free,
+                };
+                TextLine {
+                    text,
+                    x,
+                    y: i as u32 * pitch,
+                    width: line_width,
+                }
+            })
+            .collect();
+
+        TextLayout {
+            lines,
+            width,
+            height,
+        }
+    }
+
+    /// Lay out `text` and draw it with its top left corner at `(x, y)`.
+    pub fn draw(
+        &self,
+        canvas: &mut Canvas,
+        text: &str,
+        x: i32,
+        y: i32,
+        style: &TextStyle,
+    ) -> TextLayout {
+        let layout = self.layout(text, style);
+        self.draw_layout(canvas, &layout, x, y, style);
+        layout
+    }
+
+    /// Draw a text laid out by [`layout`](Self::layout) with its top left corner at `(x, y)`.
+    pub fn draw_layout(
+        &self,
+        canvas: &mut Canvas,
+        layout: &TextLayout,
+        x: i32,
+        y: i32,
+        style: &TextStyle,
+    ) {
+        let scale = style.scale.max(1);
+        for line in &layout.lines {
+            let mut pen_x = x as i64 + line.x as i64;
+            let pen_y = y as i64 + line.y as i64;
+            for c in line.text.chars() {
+                let glyph = match self.glyph(c) {
+                    Some(glyph) => glyph,
+                    None => continue,
+                };
+                for (i, _) in glyph.bitmap.iter().enumerate().filter(|(_, set)| **set) {
+                    let gx = (i as u32 % self.cell_width) as i64 * scale as i64;
+                    let gy = (i as u32 / self.cell_width) as i64 * scale as i64;
+                    let (px, py) = (pen_x + gx, pen_y + gy);
+                    if px.abs() < i32::MAX as i64 && py.abs() < i32::MAX as i64 {
+                        canvas.fill_rect(px as i32, py as i32, scale, scale, style.color);
+                    }
+                }
+                pen_x += (glyph.advance * scale) as i64;
+            }
+        }
+    }
+
+    fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.get(&c).or_else(|| self.glyphs.get(&'?'))
+    }
+
+    fn wrap(&self, paragraph: &str, max_width: u32, scale: u32, lines: &mut Vec<(String, u32)>) {
+        let space = self.advance(' ') * scale;
+        let mut line = String::new();
+        let mut width = 0;
+        let mut started = false;
+
+        for word in paragraph.split(' ') {
+            let word_width = self.text_width(word) * scale;
+            if started {
+                if width + space + word_width <= max_width {
+                    line.push(' ');
+                    line.push_str(word);
+                    width += space + word_width;
+                    continue;
+                }
+                lines.push((std::mem::take(&mut line), width));
+                width = 0;
+            }
+            started = true;
+
+            for c in word.chars() {
+                let advance = self.advance(c) * scale;
+                if width > 0 && width + advance > max_width {
+                    lines.push((std::mem::take(&mut line), width));
+                    width = 0;
+                }
+                line.push(c);
+                width += advance;
+            }
+        }
+        lines.push((line, width));
+    }
+}
+
+/// Parse `N` whitespace separated numbers of a BDF property.
+fn bdf_numbers<const N: usize>(text: &str) -> anyhow::Result<[i32; N]> {
+    let mut values = [0; N];
+    let mut words = text.split_whitespace();
+    for value in values.iter_mut() {
+        let word = words
+            .next()
+            .with_context(|| format!("expected {} BDF numbers: {:?}", N, text))?;
+        *value = word
+            .parse()
+            .with_context(|| format!("invalid BDF number: {:?}", word))?;
+    }
+    Ok(values)
+}
+
+/// Parse the glyph following a `STARTCHAR` line into a cell of the font bounding box `bounds`.
+/// Returns no character for glyphs without a Unicode encoding.
+fn bdf_glyph<'a>(
+    lines: &mut impl Iterator<Item = &'a str>,
+    bounds: [i32; 4],
+) -> anyhow::Result<(Option<char>, Glyph)> {
+    // Offsets come from the file, so positions are computed in i64 to rule out overflows
+    let [width, height, x_offset, y_offset] = bounds.map(i64::from);
+    let ascent = height + y_offset;
+    let mut encoding = None;
+    let mut advance = width;
+    let mut bbx = bounds.map(i64::from);
+    let mut bitmap = vec![false; (width * height) as usize];
+
+    while let Some(line) = lines.next() {
+        let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match keyword {
+            "ENCODING" => encoding = Some(bdf_numbers::<1>(rest)?[0]),
+            "DWIDTH" => advance = bdf_numbers::<1>(rest)?[0].into(),
+            "BBX" => bbx = bdf_numbers::<4>(rest)?.map(i64::from),
+            "BITMAP" => {
+                let [w, h, bx, by] = bbx;
+                let left = bx - x_offset;
+                let top = ascent - (by + h);
+                for gy in 0..h {
+                    let row = lines.next().context("truncated BDF bitmap")?;
+                    // Slicing below assumes one byte per character
+                    ensure!(row.is_ascii(), "invalid BDF bitmap row: {:?}", row);
+                    let bytes = (0..row.len() / 2)
+                        .map(|i| u8::from_str_radix(&row[i * 2..i * 2 + 2], 16))
+                        .collect::<Result<Vec<u8>, _>>()
+                        .with_context(|| format!("invalid BDF bitmap row: {:?}", row))?;
+                    for gx in 0..w {
+                        let byte = bytes.get(gx as usize / 8).copied().unwrap_or(0);
+                        let (x, y) = (left + gx, top + gy);
+                        if byte & (0x80 >> (gx % 8)) != 0
+                            && (0..width).contains(&x)
+                            && (0..height).contains(&y)
+                        {
+                            bitmap[(y * width + x) as usize] = true;
+                        }
+                    }
+                }
+            }
+            "ENDCHAR" => break,
+            _ => (),
+        }
+    }
+
+    let c = encoding
+        .and_then(|code| u32::try_from(code).ok())
+        .and_then(char::from_u32);
+    let glyph = Glyph {
+        advance: advance.clamp(0, u32::MAX as i64) as u32,
+        bitmap,
+    };
+    Ok((c, glyph))
+}
+
+/// Read the characters of each glyph from a PSF Unicode table. Sequences of several code points
+/// are skipped.
+fn psf_unicode_table(table: &[u8], count: usize, psf1: bool) -> anyhow::Result<Vec<Vec<char>>> {
+    let mut chars = vec![Vec::new(); count];
+    let mut rest = table;
+
+    for glyph_chars in chars.iter_mut() {
+        let mut in_sequence = false;
+        loop {
+            if psf1 {
+                let (entry, tail) = match rest {
+                    [a, b, tail @ ..] => (u16::from_le_bytes([*a, *b]), tail),
+                    _ => bail!("PSF Unicode table is truncated"),
+                };
+                rest = tail;
+                match entry {
+                    0xffff => break,
+                    0xfffe => in_sequence = true,
+                    _ if !in_sequence => glyph_chars.extend(char::from_u32(entry as u32)),
+                    _ => (),
+                }
+            } else {
+                match rest.first() {
+                    Some(0xff) => {
+                        rest = &rest[1..];
+                        break;
+                    }
+                    Some(0xfe) => {
+                        rest = &rest[1..];
+                        in_sequence = true;
+                    }
+                    Some(&byte) => {
+                        let len = match byte {
+                            0x00..=0x7f => 1,
+                            0xc0..=0xdf => 2,
+                            0xe0..=0xef => 3,
+                            _ => 4,
+                        };
+                        ensure!(rest.len() >= len, "PSF Unicode table is truncated");
+                        let c = std::str::from_utf8(&rest[..len])
+                            .context("invalid UTF-8 in PSF Unicode table")?
+                            .chars()
+                            .next();
+                        if !in_sequence {
+                            glyph_chars.extend(c);
+                        }
+                        rest = &rest[len..];
+                    }
+                    None => bail!("PSF Unicode table is truncated"),
+                }
+            }
+        }
+    }
+    Ok(chars)
+}
+
+/// Glyphs of the built-in font for `' '..='~'`, one byte per row with the leftmost pixel in
+/// bit 4. Rows 0 to 6 hold capitals, rows 7 and 8 descenders.
+#[rustfmt::skip]
+const BUILTIN_GLYPHS: [[u8; 9]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04, 0x00, 0x00], // '!'
+    [0x0a, 0x0a, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x0a, 0x0a, 0x1f, 0x0a, 0x1f, 0x0a, 0x0a, 0x00, 0x00], // '#'
+    [0x04, 0x0f, 0x14, 0x0e, 0x05, 0x1e, 0x04, 0x00, 0x00], // '$'
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03, 0x00, 0x00], // '%'
+    [0x0c, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0d, 0x00, 0x00], // '&'
+    [0x04, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '\''
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02, 0x00, 0x00], // '('
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08, 0x00, 0x00], // ')'
+    [0x00, 0x04, 0x15, 0x0e, 0x15, 0x04, 0x00, 0x00, 0x00], // '*'
+    [0x00, 0x04, 0x04, 0x1f, 0x04, 0x04, 0x00, 0x00, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x04, 0x08, 0x00], // ','
+    [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c, 0x00, 0x00], // '.'
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00, 0x00, 0x00], // '/'
+    [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e, 0x00, 0x00], // '0'
+    [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e, 0x00, 0x00], // '1'
+    [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f, 0x00, 0x00], // '2'
+    [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e, 0x00, 0x00], // '3'
+    [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02, 0x00, 0x00], // '4'
+    [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e, 0x00, 0x00], // '5'
+    [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e, 0x00, 0x00], // '6'
+    [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08, 0x00, 0x00], // '7'
+    [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e, 0x00, 0x00], // '8'
+    [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c, 0x00, 0x00], // '9'
+    [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00, 0x00, 0x00], // ':'
+    [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x04, 0x08, 0x00, 0x00], // ';'
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02, 0x00, 0x00], // '<'
+    [0x00, 0x00, 0x1f, 0x00, 0x1f, 0x00, 0x00, 0x00, 0x00], // '='
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08, 0x00, 0x00], // '>'
+    [0x0e, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04, 0x00, 0x00], // '?'
+    [0x0e, 0x11, 0x01, 0x0d, 0x15, 0x15, 0x0e, 0x00, 0x00], // '@'
+    [0x0e, 0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x00, 0x00], // 'A'
+    [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e, 0x00, 0x00], // 'B'
+    [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e, 0x00, 0x00], // 'C'
+    [0x1c, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1c, 0x00, 0x00], // 'D'
+    [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f, 0x00, 0x00], // 'E'
+    [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10, 0x00, 0x00], // 'F'
+    [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f, 0x00, 0x00], // 'G'
+    [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11, 0x00, 0x00], // 'H'
+    [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e, 0x00, 0x00], // 'I'
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c, 0x00, 0x00], // 'J'
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11, 0x00, 0x00], // 'K'
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f, 0x00, 0x00], // 'L'
+    [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11, 0x00, 0x00], // 'M'
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11, 0x00, 0x00], // 'N'
+    [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e, 0x00, 0x00], // 'O'
+    [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10, 0x00, 0x00], // 'P'
+    [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d, 0x00, 0x00], // 'Q'
+    [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11, 0x00, 0x00], // 'R'
+    [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e, 0x00, 0x00], // 'S'
+    [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x00], // 'T'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e, 0x00, 0x00], // 'U'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04, 0x00, 0x00], // 'V'
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a, 0x00, 0x00], // 'W'
+    [0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11, 0x00, 0x00], // 'X'
+    [0x11, 0x11, 0x11, 0x0a, 0x04, 0x04, 0x04, 0x00, 0x00], // 'Y'
+    [0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f, 0x00, 0x00], // 'Z'
+    [0x0e, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0e, 0x00, 0x00], // '['
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00, 0x00, 0x00], // '\\'
+    [0x0e, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0e, 0x00, 0x00], // ']'
+    [0x04, 0x0a, 0x11, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1f, 0x00], // '_'
+    [0x08, 0x04, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '`'
+    [0x00, 0x00, 0x0e, 0x01, 0x0f, 0x11, 0x0f, 0x00, 0x00], // 'a'
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1e, 0x00, 0x00], // 'b'
+    [0x00, 0x00, 0x0e, 0x10, 0x10, 0x11, 0x0e, 0x00, 0x00], // 'c'
+    [0x01, 0x01, 0x0d, 0x13, 0x11, 0x11, 0x0f, 0x00, 0x00], // 'd'
+    [0x00, 0x00, 0x0e, 0x11, 0x1f, 0x10, 0x0e, 0x00, 0x00], // 'e'
+    [0x06, 0x09, 0x08, 0x1c, 0x08, 0x08, 0x08, 0x00, 0x00], // 'f'
+    [0x00, 0x00, 0x0f, 0x11, 0x11, 0x0f, 0x01, 0x01, 0x0e], // 'g'
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x11, 0x00, 0x00], // 'h'
+    [0x04, 0x00, 0x0c, 0x04, 0x04, 0x04, 0x0e, 0x00, 0x00], // 'i'
+    [0x02, 0x00, 0x06, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c], // 'j'
+    [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12, 0x00, 0x00], // 'k'
+    [0x0c, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e, 0x00, 0x00], // 'l'
+    [0x00, 0x00, 0x1a, 0x15, 0x15, 0x11, 0x11, 0x00, 0x00], // 'm'
+    [0x00, 0x00, 0x16, 0x19, 0x11, 0x11, 0x11, 0x00, 0x00], // 'n'
+    [0x00, 0x00, 0x0e, 0x11, 0x11, 0x11, 0x0e, 0x00, 0x00], // 'o'
+    [0x00, 0x00, 0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10], // 'p'
+    [0x00, 0x00, 0x0f, 0x11, 0x11, 0x0f, 0x01, 0x01, 0x01], // 'q'
+    [0x00, 0x00, 0x16, 0x19, 0x10, 0x10, 0x10, 0x00, 0x00], // 'r'
+    [0x00, 0x00, 0x0f, 0x10, 0x0e, 0x01, 0x1e, 0x00, 0x00], // 's'
+    [0x08, 0x08, 0x1c, 0x08, 0x08, 0x09, 0x06, 0x00, 0x00], // 't'
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x13, 0x0d, 0x00, 0x00], // 'u'
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x0a, 0x04, 0x00, 0x00], // 'v'
+    [0x00, 0x00, 0x11, 0x11, 0x15, 0x15, 0x0a, 0x00, 0x00], // 'w'
+    [0x00, 0x00, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x00, 0x00], // 'x'
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x0f, 0x01, 0x01, 0x0e], // 'y'
+    [0x00, 0x00, 0x1f, 0x02, 0x04, 0x08, 0x1f, 0x00, 0x00], // 'z'
+    [0x02, 0x04, 0x04, 0x08, 0x04, 0x04, 0x02, 0x00, 0x00], // '{'
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x00], // '|'
+    [0x08, 0x04, 0x04, 0x02, 0x04, 0x04, 0x08, 0x00, 0x00], // '}'
+    [0x00, 0x00, 0x08, 0x15, 0x02, 0x00, 0x00, 0x00, 0x00], // '~'
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rows of a glyph, `#` for set pixels.
+    fn rows(font: &Font, c: char) -> Vec<String> {
+        let glyph = &font.glyphs[&c];
+        glyph
+            .bitmap
+            .chunks(font.cell_width as usize)
+            .map(|row| row.iter().map(|&set| if set { '#' } else { '.' }).collect())
+            .collect()
+    }
+
+    fn psf2_font(width: u32, height: u32, flags: u32, glyphs: &[&[u8]], table: &[u8]) -> Vec<u8> {
+        let size = width.div_ceil(8) * height;
+        let mut data = vec![0x72, 0xb5, 0x4a, 0x86];
+        for field in [0, 32, flags, glyphs.len() as u32, size, height, width] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        for glyph in glyphs {
+            data.extend_from_slice(glyph);
+        }
+        data.extend_from_slice(table);
+        data
+    }
+
+    #[test]
+    fn builtin_font() {
+        let font = Font::builtin();
+        assert_eq!(font.height(), 9);
+        assert!((' '..='~').all(|c| font.contains(c)));
+        assert!(!font.contains('é'));
+        assert_eq!(font.advance('W'), 6);
+        assert_eq!(
+            rows(&font, 'A'),
+            [".###.", "#...#", "#...#", "#...#", "#####", "#...#", "#...#", ".....", "....."]
+        );
+        // Missing characters are measured and drawn as '?'
+        assert_eq!(font.text_width("é"), 6);
+    }
+
+    #[test]
+    fn psf1() {
+        let mut data = vec![0x36, 0x04, 0x00, 2];
+        for i in 0..256 {
+            data.extend_from_slice(if i == b'x' as usize {
+                &[0x81, 0x3c]
+            } else {
+                &[0, 0]
+            });
+        }
+        let font = Font::from_psf(&data).unwrap();
+        assert_eq!(font.height(), 2);
+        assert_eq!(font.advance('x'), 8);
+        // Without a Unicode table glyphs follow Latin-1
+        assert!(font.contains('é'));
+        assert_eq!(rows(&font, 'x'), ["#......#", "..####.."]);
+
+        data.truncate(100);
+        assert!(Font::from_psf(&data).is_err());
+    }
+
+    #[test]
+    fn psf1_unicode_table() {
+        let mut data = vec![0x36, 0x04, 0x02, 1];
+        data.extend((0..=255).map(|i| if i == 1 { 0xff } else { 0 }));
+        for i in 0..256 {
+            if i == 1 {
+                // Two characters, then a sequence that is skipped
+                data.extend_from_slice(&[0xe9, 0x00, 0x45, 0x00, 0xfe, 0xff, 0x41, 0x00]);
+            }
+            data.extend_from_slice(&[0xff, 0xff]);
+        }
+        let font = Font::from_psf(&data).unwrap();
+        assert_eq!(rows(&font, 'é'), ["########"]);
+        assert_eq!(rows(&font, 'E'), ["########"]);
+        assert!(!font.contains('A'));
+    }
+
+    #[test]
+    fn psf2() {
+        let table = b"a\xff\xe2\x82\xac\xfeb\xff";
+        let data = psf2_font(10, 2, 1, &[&[0x80, 0x40, 0, 0], &[0xff, 0xc0, 0, 0]], table);
+        let font = Font::from_psf(&data).unwrap();
+        assert_eq!(font.height(), 2);
+        assert_eq!(font.advance('a'), 10);
+        assert_eq!(rows(&font, 'a'), ["#........#", ".........."]);
+        assert_eq!(rows(&font, '€'), ["##########", ".........."]);
+        assert!(!font.contains('b'));
+    }
+
+    #[test]
+    fn psf2_invalid_headers() {
+        // Glyph size not matching the dimensions
+        let mut data = psf2_font(8, 2, 0, &[&[0, 0]], &[]);
+        data[20] = 3;
+        assert!(Font::from_psf(&data).is_err());
+
+        // Sizes that overflow a 32-bit usize and offsets past the end
+        let mut data = psf2_font(8, 1, 0, &[&[0]], &[]);
+        data[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Font::from_psf(&data).is_err());
+        let mut data = psf2_font(8, 1, 0, &[&[0]], &[]);
+        data[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Font::from_psf(&data).is_err());
+        let mut data = psf2_font(8, 1, 0, &[&[0]], &[]);
+        for i in [20, 24, 28] {
+            data[i..i + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        }
+        assert!(Font::from_psf(&data).is_err());
+
+        // Unicode table missing
+        let data = psf2_font(8, 1, 1, &[&[0]], &[]);
+        assert!(Font::from_psf(&data).is_err());
+
+        assert!(Font::from_psf(&[0x72, 0xb5, 0x4a, 0x86]).is_err());
+        assert!(Font::from_psf(b"not a font").is_err());
+    }
+
+    const BDF: &str = "STARTFONT 2.1
+FONTBOUNDINGBOX 4 4 0 -1
+CHARS 2
+STARTCHAR A
+ENCODING 65
+DWIDTH 5 0
+BBX 3 3 0 0
+BITMAP
+40
+A0
+E0
+ENDCHAR
+STARTCHAR comma
+ENCODING 44
+DWIDTH 2 0
+BBX 1 2 1 -1
+BITMAP
+80
+80
+ENDCHAR
+ENDFONT
+";
+
+    #[test]
+    fn bdf() {
+        let font = Font::from_bdf(BDF).unwrap();
+        assert_eq!(font.height(), 4);
+        assert_eq!(font.advance('A'), 5);
+        assert_eq!(font.advance(','), 2);
+        // Placed by their bounding boxes relative to the baseline above the last row
+        assert_eq!(rows(&font, 'A'), [".#..", "#.#.", "###.", "...."]);
+        assert_eq!(rows(&font, ','), ["....", "....", ".#..", ".#.."]);
+    }
+
+    #[test]
+    fn bdf_invalid() {
+        assert!(Font::from_bdf(&BDF.replace("A0", "é0")).is_err());
+        assert!(Font::from_bdf(&BDF.replace("A0", "Zz")).is_err());
+        assert!(
+            Font::from_bdf(&BDF.replace("FONTBOUNDINGBOX 4 4", "FONTBOUNDINGBOX 0 4")).is_err()
+        );
+        let huge = BDF.replace("FONTBOUNDINGBOX 4 4", "FONTBOUNDINGBOX 65536 65536");
+        assert!(Font::from_bdf(&huge).is_err());
+        assert!(Font::from_bdf("STARTCHAR A\nENDCHAR\n").is_err());
+        assert!(Font::from_bdf(&BDF[..BDF.find("E0").unwrap()]).is_err());
+    }
+
+    fn texts(layout: &TextLayout) -> Vec<&str> {
+        layout.lines.iter().map(|line| line.text.as_str()).collect()
+    }
+
+    #[test]
+    fn wrap_at_max_width() {
+        let font = Font::builtin();
+        // "hello world" is exactly 66 pixels wide
+        let style = TextStyle::default().with_max_width(66);
+        let layout = font.layout("hello world foo\nbar", &style);
+        assert_eq!(texts(&layout), ["hello world", "foo", "bar"]);
+        assert_eq!(layout.lines[0].width, 66);
+        assert_eq!(layout.width, 66);
+        assert_eq!(layout.height, 3 * 10 - 1);
+
+        let layout = font.layout("hello world", &TextStyle::default().with_max_width(65));
+        assert_eq!(texts(&layout), ["hello", "world"]);
+
+        // Words wider than a line are broken between characters
+        let layout = font.layout("abcdefgh ij", &TextStyle::default().with_max_width(30));
+        assert_eq!(texts(&layout), ["abcde", "fgh", "ij"]);
+    }
+
+    #[test]
+    fn alignment() {
+        let font = Font::builtin();
+        let xs = |style: TextStyle| -> Vec<u32> {
+            let layout = font.layout("ab\nabcd", &style);
+            layout.lines.iter().map(|line| line.x).collect()
+        };
+
+        // Within the widest line
+        assert_eq!(xs(TextStyle::default()), [0, 0]);
+        assert_eq!(xs(TextStyle::default().with_align(Align::Center)), [6, 0]);
+        assert_eq!(xs(TextStyle::default().with_align(Align::Right)), [12, 0]);
+
+        // Within the maximum width
+        let style = TextStyle::default().with_max_width(40);
+        assert_eq!(xs(style.with_align(Align::Center)), [14, 8]);
+        assert_eq!(xs(style.with_align(Align::Right)), [28, 16]);
+    }
+
+    #[test]
+    fn scaling() {
+        let font = Font::builtin();
+        let style = TextStyle::default().with_scale(2);
+        let layout = font.layout(".\n.", &style);
+        assert_eq!(layout.width, 12);
+        assert_eq!(layout.lines[1].y, 20);
+        assert_eq!(layout.height, 38);
+
+        let mut data = vec![0; 12 * 18 * 4];
+        let mut canvas = Canvas::new(&mut data, 12, 18);
+        font.draw(&mut canvas, ".", 0, 0, &style);
+        // The 2×2 dot of '.' at rows 5 and 6 becomes 4×4
+        for y in 0..18 {
+            for x in 0..12 {
+                let set = canvas.pixel(x, y).unwrap()[3] != 0;
+                let expected = (2..6).contains(&x) && (10..14).contains(&y);
+                assert_eq!(set, expected, "pixel ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn draw_clips_to_canvas() {
+        let font = Font::builtin();
+        let mut data = vec![0; 4 * 4 * 4];
+        let mut canvas = Canvas::new(&mut data, 4, 4);
+        let style = TextStyle::default().with_scale(4);
+        font.draw(&mut canvas, "A", i32::MIN, i32::MAX, &style);
+        font.draw(&mut canvas, "A", i32::MAX, i32::MIN, &style);
+        assert!(data.iter().all(|&byte| byte == 0));
+
+        let mut canvas = Canvas::new(&mut data, 4, 4);
+        font.draw(&mut canvas, "A", -3, -1, &TextStyle::default());
+        // The right edge of the second row of 'A'
+        assert_eq!(canvas.pixel(0, 0), Some([0; 4]));
+        assert_eq!(canvas.pixel(1, 0), Some([0xff; 4]));
+    }
+}
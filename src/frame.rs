@@ -0,0 +1,151 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::image::Image;
+
+/// A copy of the frame buffer after a draw.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// Width of the frame in pixels.
+    pub width: u32,
+    /// Height of the frame in pixels.
+    pub height: u32,
+    /// RGBA pixel data, `width * height * 4` bytes.
+    pub data: Vec<u8>,
+}
+
+impl Frame {
+    /// Color of the pixel at `(x, y)`.
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        let mut rgba = [0; 4];
+        rgba.copy_from_slice(&self.data[i..i + 4]);
+        rgba
+    }
+
+    /// A copy scaled up by an integer `factor`, each pixel becoming a `factor * factor` block.
+    pub fn scaled(&self, factor: u32) -> Self {
+        assert!(factor > 0, "scale factor must be positive");
+        if factor == 1 {
+            return self.clone();
+        }
+
+        let factor = factor as usize;
+        let row_len = self.width as usize * 4;
+        let mut data = Vec::with_capacity(self.data.len() * factor * factor);
+        for row in self.data.chunks_exact(row_len) {
+            let start = data.len();
+            for pixel in row.chunks_exact(4) {
+                for _ in 0..factor {
+                    data.extend_from_slice(pixel);
+                }
+            }
+            for _ in 1..factor {
+                data.extend_from_within(start..start + row_len * factor);
+            }
+        }
+
+        Self {
+            width: self.width * factor as u32,
+            height: self.height * factor as u32,
+            data,
+        }
+    }
+
+    /// Encode the frame as an RGBA PNG.
+    pub fn write_png(&self, writer: impl Write) -> anyhow::Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.data)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Load a PNG file, see [`Image::decode_png`].
+    pub fn load_png(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let bytes =
+            std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        let image = Image::decode_png(&bytes)
+            .with_context(|| format!("failed to decode {}", path.display()))?;
+        Ok(image.into())
+    }
+
+    /// Save the frame as a PNG file, creating parent directories as needed.
+    pub fn save_png(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file =
+            File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+        self.write_png(BufWriter::new(file))
+            .with_context(|| format!("failed to encode {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scaled() {
+        // 2x2 pixels, each identified by its red channel
+        let frame = Frame {
+            width: 2,
+            height: 2,
+            data: [
+                [1, 0, 0, 0xff],
+                [2, 0, 0, 0xff],
+                [3, 0, 0, 0xff],
+                [4, 0, 0, 0xff],
+            ]
+            .concat(),
+        };
+        let scaled = frame.scaled(2);
+        assert_eq!((scaled.width, scaled.height), (4, 4));
+        let rows: Vec<Vec<u8>> = (0..4)
+            .map(|y| (0..4).map(|x| scaled.pixel(x, y)[0]).collect())
+            .collect();
+        assert_eq!(
+            rows,
+            [[1, 1, 2, 2], [1, 1, 2, 2], [3, 3, 4, 4], [3, 3, 4, 4]]
+        );
+
+        assert_eq!(frame.scaled(1), frame);
+    }
+
+    #[test]
+    fn scaled_non_square() {
+        let frame = Frame {
+            width: 3,
+            height: 1,
+            data: [[1, 2, 3, 4], [5, 6, 7, 8], [9, 10, 11, 12]].concat(),
+        };
+        let scaled = frame.scaled(3);
+        assert_eq!((scaled.width, scaled.height), (9, 3));
+        assert_eq!(scaled.data.len(), 9 * 3 * 4);
+        for y in 0..3 {
+            assert_eq!(scaled.pixel(0, y), [1, 2, 3, 4]);
+            assert_eq!(scaled.pixel(2, y), [1, 2, 3, 4]);
+            assert_eq!(scaled.pixel(3, y), [5, 6, 7, 8]);
+            assert_eq!(scaled.pixel(8, y), [9, 10, 11, 12]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "scale factor must be positive")]
+    fn scaled_by_zero() {
+        Frame {
+            width: 1,
+            height: 1,
+            data: vec![0; 4],
+        }
+        .scaled(0);
+    }
+}
@@ -0,0 +1,644 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use winit::event::{Touch, TouchPhase, WindowEvent};
+
+use crate::viewport::Viewport;
+
+/// Gesture recognized by [`GestureRecognizer`]. Positions are in buffer coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gesture {
+    /// A finger went down and up without moving.
+    Tap { position: (f64, f64) },
+    /// A second tap close to the previous one. The first tap is reported as [`Gesture::Tap`].
+    DoubleTap { position: (f64, f64) },
+    /// A finger stayed down without moving.
+    LongPress { position: (f64, f64) },
+    /// A finger moved quickly and went up.
+    Swipe {
+        start: (f64, f64),
+        end: (f64, f64),
+        direction: SwipeDirection,
+        /// Average velocity in buffer pixels per second.
+        velocity: (f64, f64),
+    },
+    /// Two fingers moved apart or together. Reported on every move once started.
+    Pinch {
+        center: (f64, f64),
+        /// Distance between the fingers relative to their initial distance.
+        scale: f64,
+    },
+    /// Two fingers rotated around each other. Reported on every move once started.
+    Rotate {
+        center: (f64, f64),
+        /// Clockwise rotation since the second finger went down, in radians.
+        angle: f64,
+    },
+}
+
+/// Dominant direction of a [`Gesture::Swipe`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwipeDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// Thresholds of the [`GestureRecognizer`]. Distances are in buffer pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GestureConfig {
+    /// Distance a finger may move before it no longer counts as a tap or long press.
+    pub slop: f64,
+    /// Time a finger has to stay down for a long press.
+    pub long_press: Duration,
+    /// Maximum time between the two taps of a double tap.
+    pub double_tap_interval: Duration,
+    /// Maximum distance between the two taps of a double tap.
+    pub double_tap_slop: f64,
+    /// Minimum distance of a swipe.
+    pub swipe_min_distance: f64,
+    /// Maximum duration of a swipe.
+    pub swipe_max_duration: Duration,
+    /// Change in finger distance, relative to the initial distance, that starts a pinch.
+    pub pinch_threshold: f64,
+    /// Rotation in radians that starts a rotate gesture.
+    pub rotate_threshold: f64,
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        Self {
+            slop: 4.0,
+            long_press: Duration::from_millis(500),
+            double_tap_interval: Duration::from_millis(300),
+            double_tap_slop: 12.0,
+            swipe_min_distance: 24.0,
+            swipe_max_duration: Duration::from_millis(500),
+            pinch_threshold: 0.1,
+            rotate_threshold: 0.15,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Finger {
+    id: u64,
+    start: (f64, f64),
+    position: (f64, f64),
+    start_time: Instant,
+}
+
+/// Single finger gesture in progress.
+#[derive(Debug, Clone, Copy)]
+struct Press {
+    moved: bool,
+    long_pressed: bool,
+}
+
+/// Two finger gesture in progress.
+#[derive(Debug, Clone, Copy)]
+struct Transform {
+    distance: f64,
+    angle: f64,
+    pinching: bool,
+    rotating: bool,
+}
+
+/// Recognizes gestures from touch events.
+///
+/// Feed it every touch event and call [`update`](Self::update) once per frame so long presses
+/// are detected while the finger doesn't move. Time is passed in explicitly, so synthetic event
+/// sequences produce deterministic results. Only the first two fingers are considered; a
+/// cancelled touch aborts the gesture in progress.
+#[derive(Debug)]
+pub struct GestureRecognizer {
+    config: GestureConfig,
+    fingers: Vec<Finger>,
+    press: Option<Press>,
+    transform: Option<Transform>,
+    /// Set once a second finger went down, until all fingers are up.
+    multi_touch: bool,
+    last_tap: Option<((f64, f64), Instant)>,
+    events: VecDeque<Gesture>,
+}
+
+impl Default for GestureRecognizer {
+    fn default() -> Self {
+        Self::new(GestureConfig::default())
+    }
+}
+
+impl GestureRecognizer {
+    /// Create a recognizer with the given thresholds.
+    pub fn new(config: GestureConfig) -> Self {
+        Self {
+            config,
+            fingers: Vec::new(),
+            press: None,
+            transform: None,
+            multi_touch: false,
+            last_tap: None,
+            events: VecDeque::new(),
+        }
+    }
+
+    /// The thresholds in use.
+    pub fn config(&self) -> &GestureConfig {
+        &self.config
+    }
+
+    /// Take the recognized gestures, oldest first.
+    pub fn drain(&mut self) -> impl Iterator<Item = Gesture> + '_ {
+        self.events.drain(..)
+    }
+
+    /// Feed a window event. Events other than touches are ignored.
+    pub fn handle_window_event(&mut self, event: &WindowEvent, viewport: &Viewport, now: Instant) {
+        if let WindowEvent::Touch(Touch {
+            id,
+            phase,
+            location,
+            ..
+        }) = event
+        {
+            let position = viewport.to_buffer_unclamped(*location);
+            self.handle_touch(*id, *phase, position, now);
+        }
+    }
+
+    /// Feed a touch at a position in buffer coordinates.
+    pub fn handle_touch(&mut self, id: u64, phase: TouchPhase, position: (f64, f64), now: Instant) {
+        let index = self.fingers.iter().position(|finger| finger.id == id);
+
+        match (phase, index) {
+            (TouchPhase::Started, None) => self.finger_down(id, position, now),
+            (TouchPhase::Moved, Some(index)) => {
+                self.fingers[index].position = position;
+                self.finger_moved(index);
+            }
+            (TouchPhase::Ended, Some(index)) => {
+                self.fingers[index].position = position;
+                self.finger_up(index, now);
+            }
+            (TouchPhase::Cancelled, Some(_)) => self.reset(),
+            _ => (),
+        }
+    }
+
+    /// Advance the clock, detecting long presses.
+    pub fn update(&mut self, now: Instant) {
+        let (finger, press) = match (self.fingers.first(), self.press.as_mut()) {
+            (Some(finger), Some(press)) => (finger, press),
+            _ => return,
+        };
+        if press.moved || press.long_pressed {
+            return;
+        }
+
+        if now.saturating_duration_since(finger.start_time) >= self.config.long_press {
+            press.long_pressed = true;
+            self.last_tap = None;
+            self.events.push_back(Gesture::LongPress {
+                position: finger.start,
+            });
+        }
+    }
+
+    /// Abort all gestures in progress.
+    pub fn reset(&mut self) {
+        self.fingers.clear();
+        self.press = None;
+        self.transform = None;
+        self.multi_touch = false;
+    }
+
+    fn finger_down(&mut self, id: u64, position: (f64, f64), now: Instant) {
+        if self.fingers.len() >= 2 {
+            return;
+        }
+        self.fingers.push(Finger {
+            id,
+            start: position,
+            position,
+            start_time: now,
+        });
+
+        if self.fingers.len() == 1 && !self.multi_touch {
+            self.press = Some(Press {
+                moved: false,
+                long_pressed: false,
+            });
+        } else {
+            self.multi_touch = true;
+            self.press = None;
+            self.last_tap = None;
+            // A finger going down while another one is left over from a pinch starts nothing
+            if let [a, b] = self.fingers.as_slice() {
+                self.transform = Some(Transform {
+                    distance: distance(a.position, b.position),
+                    angle: angle(a.position, b.position),
+                    pinching: false,
+                    rotating: false,
+                });
+            }
+        }
+    }
+
+    fn finger_moved(&mut self, index: usize) {
+        if let Some(press) = self.press.as_mut() {
+            let finger = &self.fingers[index];
+            if distance(finger.start, finger.position) > self.config.slop {
+                press.moved = true;
+            }
+        }
+
+        if let (Some(transform), [a, b]) = (self.transform.as_mut(), self.fingers.as_slice()) {
+            let (a, b) = (a.position, b.position);
+            let center = ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+
+            if transform.distance > 0.0 {
+                let scale = distance(a, b) / transform.distance;
+                if (scale - 1.0).abs() > self.config.pinch_threshold {
+                    transform.pinching = true;
+                }
+                if transform.pinching {
+                    self.events.push_back(Gesture::Pinch { center, scale });
+                }
+            }
+
+            let rotation = normalize_angle(angle(a, b) - transform.angle);
+            if rotation.abs() > self.config.rotate_threshold {
+                transform.rotating = true;
+            }
+            if transform.rotating {
+                self.events.push_back(Gesture::Rotate {
+                    center,
+                    angle: rotation,
+                });
+            }
+        }
+    }
+
+    fn finger_up(&mut self, index: usize, now: Instant) {
+        let finger = self.fingers.remove(index);
+        self.transform = None;
+
+        if let Some(press) = self.press.take() {
+            if !press.moved && !press.long_pressed {
+                self.tap(finger.start, now);
+            } else if press.moved {
+                self.swipe(&finger, now);
+            }
+        }
+
+        if self.fingers.is_empty() {
+            self.multi_touch = false;
+        }
+    }
+
+    fn tap(&mut self, position: (f64, f64), now: Instant) {
+        let double_tap = match self.last_tap {
+            Some((last_position, last_time)) => {
+                now.saturating_duration_since(last_time) <= self.config.double_tap_interval
+                    && distance(last_position, position) <= self.config.double_tap_slop
+            }
+            None => false,
+        };
+
+        if double_tap {
+            self.last_tap = None;
+            self.events.push_back(Gesture::DoubleTap { position });
+        } else {
+            self.last_tap = Some((position, now));
+            self.events.push_back(Gesture::Tap { position });
+        }
+    }
+
+    fn swipe(&mut self, finger: &Finger, now: Instant) {
+        let duration = now.saturating_duration_since(finger.start_time);
+        let (dx, dy) = (
+            finger.position.0 - finger.start.0,
+            finger.position.1 - finger.start.1,
+        );
+        if duration > self.config.swipe_max_duration
+            || dx.hypot(dy) < self.config.swipe_min_distance
+        {
+            return;
+        }
+
+        let direction = if dx.abs() >= dy.abs() {
+            if dx < 0.0 {
+                SwipeDirection::Left
+            } else {
+                SwipeDirection::Right
+            }
+        } else if dy < 0.0 {
+            SwipeDirection::Up
+        } else {
+            SwipeDirection::Down
+        };
+        let seconds = duration.as_secs_f64().max(f64::EPSILON);
+
+        self.events.push_back(Gesture::Swipe {
+            start: finger.start,
+            end: finger.position,
+            direction,
+            velocity: (dx / seconds, dy / seconds),
+        });
+    }
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (b.0 - a.0).hypot(b.1 - a.1)
+}
+
+/// Angle of the line from `a` to `b`. Clockwise, since the y axis points down.
+fn angle(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (b.1 - a.1).atan2(b.0 - a.0)
+}
+
+/// Wrap an angle into `-π..=π`.
+fn normalize_angle(angle: f64) -> f64 {
+    use std::f64::consts::{PI, TAU};
+
+    let angle = angle.rem_euclid(TAU);
+    if angle > PI {
+        angle - TAU
+    } else {
+        angle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_2;
+
+    use super::*;
+
+    /// Feeds touches at millisecond offsets from a fixed start.
+    struct Harness {
+        recognizer: GestureRecognizer,
+        start: Instant,
+    }
+
+    impl Harness {
+        fn new() -> Self {
+            Self {
+                recognizer: GestureRecognizer::default(),
+                start: Instant::now(),
+            }
+        }
+
+        fn touch(&mut self, ms: u64, id: u64, phase: TouchPhase, position: (f64, f64)) {
+            let now = self.start + Duration::from_millis(ms);
+            self.recognizer.handle_touch(id, phase, position, now);
+        }
+
+        fn tap(&mut self, ms: u64, position: (f64, f64)) {
+            self.touch(ms, 0, TouchPhase::Started, position);
+            self.touch(ms + 50, 0, TouchPhase::Ended, position);
+        }
+
+        fn update(&mut self, ms: u64) {
+            self.recognizer
+                .update(self.start + Duration::from_millis(ms));
+        }
+
+        fn gestures(&mut self) -> Vec<Gesture> {
+            self.recognizer.drain().collect()
+        }
+    }
+
+    #[test]
+    fn tap() {
+        let mut h = Harness::new();
+        h.tap(0, (10.0, 20.0));
+        assert_eq!(
+            h.gestures(),
+            [Gesture::Tap {
+                position: (10.0, 20.0)
+            }]
+        );
+    }
+
+    #[test]
+    fn double_tap() {
+        let mut h = Harness::new();
+        h.tap(0, (10.0, 10.0));
+        // The second tap ends 300 ms after the first, the longest interval allowed
+        h.tap(300, (15.0, 10.0));
+        assert_eq!(
+            h.gestures(),
+            [
+                Gesture::Tap {
+                    position: (10.0, 10.0)
+                },
+                Gesture::DoubleTap {
+                    position: (15.0, 10.0)
+                },
+            ]
+        );
+
+        // A third tap starts a new sequence
+        h.tap(400, (15.0, 10.0));
+        assert_eq!(
+            h.gestures(),
+            [Gesture::Tap {
+                position: (15.0, 10.0)
+            }]
+        );
+    }
+
+    #[test]
+    fn double_tap_interval_just_missed() {
+        let mut h = Harness::new();
+        h.tap(0, (10.0, 10.0));
+        h.tap(301, (10.0, 10.0));
+        assert_eq!(
+            h.gestures(),
+            [
+                Gesture::Tap {
+                    position: (10.0, 10.0)
+                },
+                Gesture::Tap {
+                    position: (10.0, 10.0)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn double_tap_too_far_apart() {
+        let mut h = Harness::new();
+        h.tap(0, (10.0, 10.0));
+        h.tap(100, (10.0, 22.5));
+        assert!(matches!(
+            h.gestures().as_slice(),
+            [Gesture::Tap { .. }, Gesture::Tap { .. }]
+        ));
+    }
+
+    #[test]
+    fn long_press() {
+        let mut h = Harness::new();
+        h.touch(0, 0, TouchPhase::Started, (5.0, 5.0));
+        // Moving within the slop doesn't prevent a long press
+        h.touch(100, 0, TouchPhase::Moved, (8.0, 5.0));
+        h.update(499);
+        assert_eq!(h.gestures(), []);
+
+        h.update(500);
+        h.update(600);
+        assert_eq!(
+            h.gestures(),
+            [Gesture::LongPress {
+                position: (5.0, 5.0)
+            }]
+        );
+
+        // Lifting the finger afterwards is not a tap
+        h.touch(700, 0, TouchPhase::Ended, (8.0, 5.0));
+        assert_eq!(h.gestures(), []);
+    }
+
+    #[test]
+    fn slop() {
+        let mut h = Harness::new();
+        h.touch(0, 0, TouchPhase::Started, (0.0, 0.0));
+        h.touch(20, 0, TouchPhase::Moved, (4.0, 0.0));
+        h.touch(40, 0, TouchPhase::Ended, (4.0, 0.0));
+        assert_eq!(
+            h.gestures(),
+            [Gesture::Tap {
+                position: (0.0, 0.0)
+            }]
+        );
+
+        // Beyond the slop it is neither a tap, a long press nor, being short, a swipe
+        h.touch(1000, 0, TouchPhase::Started, (0.0, 0.0));
+        h.touch(1020, 0, TouchPhase::Moved, (3.0, 3.0));
+        h.update(1600);
+        h.touch(1700, 0, TouchPhase::Ended, (3.0, 3.0));
+        assert_eq!(h.gestures(), []);
+    }
+
+    #[test]
+    fn swipe() {
+        let mut h = Harness::new();
+        h.touch(0, 0, TouchPhase::Started, (0.0, 0.0));
+        h.touch(50, 0, TouchPhase::Moved, (15.0, 2.0));
+        h.touch(100, 0, TouchPhase::Ended, (30.0, 5.0));
+        assert_eq!(
+            h.gestures(),
+            [Gesture::Swipe {
+                start: (0.0, 0.0),
+                end: (30.0, 5.0),
+                direction: SwipeDirection::Right,
+                velocity: (300.0, 50.0),
+            }]
+        );
+    }
+
+    #[test]
+    fn swipe_directions() {
+        let cases = [
+            ((-30.0, 10.0), SwipeDirection::Left),
+            ((0.0, -25.0), SwipeDirection::Up),
+            ((-10.0, 30.0), SwipeDirection::Down),
+        ];
+        for (end, expected) in cases {
+            let mut h = Harness::new();
+            h.touch(0, 0, TouchPhase::Started, (0.0, 0.0));
+            h.touch(100, 0, TouchPhase::Moved, end);
+            h.touch(100, 0, TouchPhase::Ended, end);
+            match h.gestures().as_slice() {
+                [Gesture::Swipe { direction, .. }] => assert_eq!(*direction, expected),
+                gestures => panic!("expected a swipe to {:?}, got {:?}", end, gestures),
+            }
+        }
+    }
+
+    #[test]
+    fn swipe_too_slow() {
+        let mut h = Harness::new();
+        h.touch(0, 0, TouchPhase::Started, (0.0, 0.0));
+        h.touch(100, 0, TouchPhase::Moved, (40.0, 0.0));
+        h.touch(501, 0, TouchPhase::Ended, (40.0, 0.0));
+        assert_eq!(h.gestures(), []);
+    }
+
+    #[test]
+    fn pinch() {
+        let mut h = Harness::new();
+        h.touch(0, 0, TouchPhase::Started, (0.0, 0.0));
+        h.touch(10, 1, TouchPhase::Started, (10.0, 0.0));
+        // Within the threshold
+        h.touch(20, 1, TouchPhase::Moved, (10.5, 0.0));
+        assert_eq!(h.gestures(), []);
+
+        h.touch(30, 1, TouchPhase::Moved, (20.0, 0.0));
+        h.touch(40, 1, TouchPhase::Moved, (10.5, 0.0));
+        assert_eq!(
+            h.gestures(),
+            [
+                Gesture::Pinch {
+                    center: (10.0, 0.0),
+                    scale: 2.0
+                },
+                // Once started, every move is reported
+                Gesture::Pinch {
+                    center: (5.25, 0.0),
+                    scale: 1.05
+                },
+            ]
+        );
+
+        // Two fingers going up don't tap
+        h.touch(50, 1, TouchPhase::Ended, (10.5, 0.0));
+        h.touch(60, 0, TouchPhase::Ended, (0.0, 0.0));
+        assert_eq!(h.gestures(), []);
+    }
+
+    #[test]
+    fn rotate() {
+        let mut h = Harness::new();
+        h.touch(0, 0, TouchPhase::Started, (0.0, 0.0));
+        h.touch(10, 1, TouchPhase::Started, (10.0, 0.0));
+        h.touch(20, 1, TouchPhase::Moved, (0.0, 10.0));
+        assert_eq!(
+            h.gestures(),
+            [Gesture::Rotate {
+                center: (0.0, 5.0),
+                angle: FRAC_PI_2
+            }]
+        );
+    }
+
+    #[test]
+    fn cancel_aborts_gesture() {
+        let mut h = Harness::new();
+        h.touch(0, 0, TouchPhase::Started, (0.0, 0.0));
+        h.touch(100, 0, TouchPhase::Cancelled, (0.0, 0.0));
+        h.update(1000);
+        h.touch(1100, 0, TouchPhase::Ended, (0.0, 0.0));
+        assert_eq!(h.gestures(), []);
+    }
+
+    #[test]
+    fn custom_config() {
+        let mut h = Harness::new();
+        h.recognizer = GestureRecognizer::new(GestureConfig {
+            long_press: Duration::from_millis(100),
+            ..GestureConfig::default()
+        });
+        h.touch(0, 0, TouchPhase::Started, (1.0, 1.0));
+        h.update(100);
+        assert_eq!(
+            h.gestures(),
+            [Gesture::LongPress {
+                position: (1.0, 1.0)
+            }]
+        );
+    }
+}
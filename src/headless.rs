@@ -0,0 +1,108 @@
+use crate::app::PixelsApp;
+use crate::config::AppConfig;
+use crate::frame::Frame;
+
+/// Drives a [`PixelsApp`] without a window or GPU.
+///
+/// Instead of a `Pixels` surface the runner owns a plain RGBA frame buffer. Each
+/// [`step`](Self::step) performs the same update/draw cycle as the
+/// `MainEventsCleared`/`RedrawRequested` path of [`run_app`](crate::app::run_app), running
+/// exactly one tick per frame and drawing with an interpolation alpha of `0.0`;
+/// [`update`](Self::update) and [`draw`](Self::draw) drive the two halves separately. Like
+/// `Pixels`, the buffer is not cleared between frames.
+pub struct Headless<A> {
+    app: A,
+    width: u32,
+    height: u32,
+    frame: Vec<u8>,
+    frame_count: u64,
+}
+
+impl<A: PixelsApp> Headless<A> {
+    /// Create the app from `config` and resume it with a zeroed frame buffer.
+    pub fn new(config: &AppConfig) -> Self {
+        let mut headless = Self::suspended(config);
+        headless.app.on_resume();
+        headless
+    }
+
+    /// Create the app from `config` without resuming it, e.g. to replay input that starts with
+    /// the resume.
+    pub fn suspended(config: &AppConfig) -> Self {
+        Self {
+            app: A::init(config),
+            width: config.width(),
+            height: config.height(),
+            frame: vec![0; config.width() as usize * config.height() as usize * 4],
+            frame_count: 0,
+        }
+    }
+
+    /// The app being driven.
+    pub fn app(&self) -> &A {
+        &self.app
+    }
+
+    /// Mutable access to the app, e.g. to feed it events between steps.
+    pub fn app_mut(&mut self) -> &mut A {
+        &mut self.app
+    }
+
+    /// Number of frames drawn so far.
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// Contents of the frame buffer after the last step.
+    pub fn frame(&self) -> Frame {
+        Frame {
+            width: self.width,
+            height: self.height,
+            data: self.frame.clone(),
+        }
+    }
+
+    /// Update the app once and draw it into the frame buffer.
+    pub fn step(&mut self) -> &[u8] {
+        self.update();
+        self.draw(0.0)
+    }
+
+    /// Update the app once without drawing.
+    pub fn update(&mut self) {
+        self.app.update();
+    }
+
+    /// Draw the app into the frame buffer with the interpolation `alpha`.
+    pub fn draw(&mut self, alpha: f64) -> &[u8] {
+        self.app.draw(&mut self.frame, alpha);
+        self.frame_count += 1;
+        &self.frame
+    }
+
+    /// Run `frames` steps and return a copy of every drawn frame.
+    pub fn run(&mut self, frames: usize) -> Vec<Frame> {
+        (0..frames)
+            .map(|_| {
+                self.step();
+                self.frame()
+            })
+            .collect()
+    }
+
+    /// Run until `frame_count` frames have been drawn and return the last one.
+    ///
+    /// Returns the current frame if at least `frame_count` frames were drawn already.
+    pub fn render_frame(&mut self, frame_count: u64) -> Frame {
+        while self.frame_count < frame_count {
+            self.step();
+        }
+        self.frame()
+    }
+
+    /// Suspend the app and return it.
+    pub fn into_app(mut self) -> A {
+        self.app.on_suspend();
+        self.app
+    }
+}
@@ -0,0 +1,595 @@
+use std::path::Path;
+
+use anyhow::{bail, ensure, Context};
+
+use crate::canvas::{Canvas, Rect};
+use crate::frame::Frame;
+
+/// An owned RGBA image, e.g. a sprite to [`blit`](Canvas::blit) into the frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl Image {
+    /// An image filled with `color`.
+    pub fn new(width: u32, height: u32, color: [u8; 4]) -> Self {
+        let data = color
+            .iter()
+            .copied()
+            .cycle()
+            .take(width as usize * height as usize * 4)
+            .collect();
+
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// Wrap RGBA pixel data.
+    pub fn from_rgba(width: u32, height: u32, data: Vec<u8>) -> anyhow::Result<Self> {
+        ensure!(
+            Some(data.len()) == data_len(width, height),
+            "{} bytes of pixel data do not match {}x{}",
+            data.len(),
+            width,
+            height
+        );
+
+        Ok(Self {
+            width,
+            height,
+            data,
+        })
+    }
+
+    /// Decode a PNG, QOI or BMP image, detected by its signature.
+    pub fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.starts_with(b"\x89PNG") {
+            Self::decode_png(bytes)
+        } else if bytes.starts_with(b"qoif") {
+            Self::decode_qoi(bytes)
+        } else if bytes.starts_with(b"BM") {
+            Self::decode_bmp(bytes)
+        } else {
+            bail!("unknown image format")
+        }
+    }
+
+    /// Load an image file, see [`decode`](Self::decode).
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let bytes =
+            std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        Self::decode(&bytes).with_context(|| format!("failed to decode {}", path.display()))
+    }
+
+    /// Decode a PNG of any color type and bit depth.
+    pub fn decode_png(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info()?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf)?;
+        buf.truncate(info.buffer_size());
+
+        let data = match info.color_type {
+            png::ColorType::Rgba => buf,
+            png::ColorType::Rgb => buf
+                .chunks_exact(3)
+                .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 0xff])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => buf
+                .chunks_exact(2)
+                .flat_map(|ga| [ga[0], ga[0], ga[0], ga[1]])
+                .collect(),
+            png::ColorType::Grayscale => buf.iter().flat_map(|&g| [g, g, g, 0xff]).collect(),
+            color_type => bail!("unsupported PNG color type: {:?}", color_type),
+        };
+        Self::from_rgba(info.width, info.height, data)
+    }
+
+    /// Decode a QOI image.
+    pub fn decode_qoi(bytes: &[u8]) -> anyhow::Result<Self> {
+        ensure!(
+            bytes.len() >= 14 && bytes.starts_with(b"qoif"),
+            "not a QOI image"
+        );
+        let width = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        let height = u32::from_be_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
+        let len = data_len(width, height).context("QOI image is too large")?;
+        ensure!(len / 4 <= (bytes.len() - 14) * 62, "QOI image is truncated");
+
+        let mut data = Vec::with_capacity(len);
+        let mut index = [[0u8; 4]; 64];
+        let mut px = [0, 0, 0, 0xff];
+        let mut run = 0;
+        let mut input = bytes[14..].iter().copied();
+        let mut next = || input.next().context("QOI image is truncated");
+
+        while data.len() < len {
+            if run > 0 {
+                run -= 1;
+            } else {
+                let op = next()?;
+                match op {
+                    0xfe => px = [next()?, next()?, next()?, px[3]],
+                    0xff => px = [next()?, next()?, next()?, next()?],
+                    _ => match op >> 6 {
+                        0 => px = index[op as usize],
+                        1 => {
+                            px[0] = px[0].wrapping_add((op >> 4) & 0x03).wrapping_sub(2);
+                            px[1] = px[1].wrapping_add((op >> 2) & 0x03).wrapping_sub(2);
+                            px[2] = px[2].wrapping_add(op & 0x03).wrapping_sub(2);
+                        }
+                        2 => {
+                            let dg = (op & 0x3f).wrapping_sub(32);
+                            let rb = next()?;
+                            px[0] = px[0].wrapping_add(dg.wrapping_add(rb >> 4).wrapping_sub(8));
+                            px[1] = px[1].wrapping_add(dg);
+                            px[2] = px[2].wrapping_add(dg.wrapping_add(rb & 0x0f).wrapping_sub(8));
+                        }
+                        _ => run = op & 0x3f,
+                    },
+                }
+                let [r, g, b, a] = px.map(|c| c as usize);
+                index[(r * 3 + g * 5 + b * 7 + a * 11) % 64] = px;
+            }
+            data.extend_from_slice(&px);
+        }
+
+        Self::from_rgba(width, height, data)
+    }
+
+    /// Decode an uncompressed 24 or 32 bit BMP.
+    pub fn decode_bmp(bytes: &[u8]) -> anyhow::Result<Self> {
+        let u16_at = |i: usize| {
+            bytes
+                .get(i..i + 2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        };
+        let u32_at = |i: usize| {
+            bytes
+                .get(i..i + 4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        };
+        ensure!(bytes.starts_with(b"BM"), "not a BMP image");
+        let header = || -> Option<_> {
+            Some((
+                u32_at(10)? as usize,
+                u32_at(14)?,
+                u32_at(18)? as i32,
+                u32_at(22)? as i32,
+                u16_at(28)?,
+                u32_at(30)?,
+            ))
+        };
+        let (offset, header_size, width, height, bits, compression) =
+            header().context("BMP header is truncated")?;
+
+        // Channel masks of 32 bit images; the alpha mask is only present in newer headers
+        let masks = match (bits, compression) {
+            (24, 0) => None,
+            (32, 0) => Some([0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0]),
+            (32, 3) | (32, 6) => {
+                let alpha = if header_size >= 56 {
+                    u32_at(66)
+                } else {
+                    Some(0)
+                };
+                let masks = (|| Some([u32_at(54)?, u32_at(58)?, u32_at(62)?, alpha?]))();
+                Some(masks.context("BMP header is truncated")?)
+            }
+            _ => bail!(
+                "unsupported BMP format: {} bits, compression {}",
+                bits,
+                compression
+            ),
+        };
+
+        ensure!(
+            width > 0 && height != 0,
+            "invalid BMP size {}x{}",
+            width,
+            height
+        );
+        let (width, top_down) = (width as u32, height < 0);
+        let height = height.unsigned_abs();
+        // Rows are padded to four bytes
+        let layout = || -> Option<_> {
+            let stride = (width as usize)
+                .checked_mul(bits as usize / 8)?
+                .checked_next_multiple_of(4)?;
+            let end = stride.checked_mul(height as usize)?.checked_add(offset)?;
+            Some((stride, end))
+        };
+        let (stride, end) = layout().context("BMP image is too large")?;
+        ensure!(bytes.len() >= end, "BMP image is truncated");
+
+        let len = data_len(width, height).context("BMP image is too large")?;
+        let mut data = Vec::with_capacity(len);
+        for y in 0..height as usize {
+            let row = if top_down { y } else { height as usize - 1 - y };
+            let row = &bytes[offset + row * stride..];
+            for x in 0..width as usize {
+                match masks {
+                    None => {
+                        let bgr = &row[x * 3..x * 3 + 3];
+                        data.extend_from_slice(&[bgr[2], bgr[1], bgr[0], 0xff]);
+                    }
+                    Some(masks) => {
+                        let i = x * 4;
+                        let value =
+                            u32::from_le_bytes([row[i], row[i + 1], row[i + 2], row[i + 3]]);
+                        let [r, g, b, a] = masks.map(|mask| channel(value, mask));
+                        data.extend_from_slice(&[r, g, b, if masks[3] == 0 { 0xff } else { a }]);
+                    }
+                }
+            }
+        }
+
+        Self::from_rgba(width, height, data)
+    }
+
+    /// Width in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The image bounds, at the origin.
+    pub fn rect(&self) -> Rect {
+        Rect::new(0, 0, self.width, self.height)
+    }
+
+    /// RGBA pixel data, `width * height * 4` bytes.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Mutable RGBA pixel data.
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// Color of the pixel at `(x, y)`.
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        let mut rgba = [0; 4];
+        rgba.copy_from_slice(&self.data[i..i + 4]);
+        rgba
+    }
+
+    /// A canvas to draw into the image.
+    pub fn canvas(&mut self) -> Canvas<'_> {
+        Canvas::new(&mut self.data, self.width, self.height)
+    }
+}
+
+impl From<Frame> for Image {
+    fn from(frame: Frame) -> Self {
+        Self {
+            width: frame.width,
+            height: frame.height,
+            data: frame.data,
+        }
+    }
+}
+
+impl From<Image> for Frame {
+    fn from(image: Image) -> Self {
+        Self {
+            width: image.width,
+            height: image.height,
+            data: image.data,
+        }
+    }
+}
+
+/// Size of the RGBA data of a `width` × `height` image, `None` if it overflows.
+fn data_len(width: u32, height: u32) -> Option<usize> {
+    (width as usize)
+        .checked_mul(height as usize)?
+        .checked_mul(4)
+}
+
+/// Extract the channel selected by `mask` from `value`, scaled to 8 bits.
+fn channel(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let max = mask >> mask.trailing_zeros();
+    let value = (value & mask) >> mask.trailing_zeros();
+    (value as u64 * 255 / max as u64) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 3x2 image with distinct pixels.
+    fn sample() -> Image {
+        let pixels = [
+            [0xff, 0x00, 0x00, 0xff],
+            [0x00, 0xff, 0x00, 0x80],
+            [0x00, 0x00, 0xff, 0x00],
+            [0xff, 0xff, 0xff, 0xff],
+            [0x00, 0x00, 0x00, 0xff],
+            [0x01, 0x02, 0x03, 0x04],
+        ];
+        Image::from_rgba(3, 2, pixels.concat()).unwrap()
+    }
+
+    fn encode_png(
+        width: u32,
+        height: u32,
+        color_type: png::ColorType,
+        depth: png::BitDepth,
+        data: &[u8],
+    ) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(color_type);
+        encoder.set_depth(depth);
+        encoder
+            .write_header()
+            .unwrap()
+            .write_image_data(data)
+            .unwrap();
+        bytes
+    }
+
+    /// QOI with every pixel stored as a full RGBA chunk.
+    fn encode_qoi(image: &Image) -> Vec<u8> {
+        let mut bytes = b"qoif".to_vec();
+        bytes.extend_from_slice(&image.width().to_be_bytes());
+        bytes.extend_from_slice(&image.height().to_be_bytes());
+        bytes.extend_from_slice(&[4, 0]);
+        for rgba in image.data().chunks_exact(4) {
+            bytes.push(0xff);
+            bytes.extend_from_slice(rgba);
+        }
+        bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+        bytes
+    }
+
+    /// A BMP with an info header followed by `masks`, and the given rows.
+    fn encode_bmp(
+        width: i32,
+        height: i32,
+        bits: u16,
+        compression: u32,
+        masks: &[u32],
+        rows: &[u8],
+    ) -> Vec<u8> {
+        let header_size = 40 + masks.len() as u32 * 4;
+        let offset = 14 + header_size;
+        let mut bytes = b"BM".to_vec();
+        bytes.extend_from_slice(&(offset + rows.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&offset.to_le_bytes());
+        bytes.extend_from_slice(&header_size.to_le_bytes());
+        bytes.extend_from_slice(&width.to_le_bytes());
+        bytes.extend_from_slice(&height.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&bits.to_le_bytes());
+        bytes.extend_from_slice(&compression.to_le_bytes());
+        bytes.extend_from_slice(&[0; 20]);
+        for mask in masks {
+            bytes.extend_from_slice(&mask.to_le_bytes());
+        }
+        bytes.extend_from_slice(rows);
+        bytes
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let image = sample();
+        let rgba = encode_png(
+            3,
+            2,
+            png::ColorType::Rgba,
+            png::BitDepth::Eight,
+            image.data(),
+        );
+        assert_eq!(Image::decode(&rgba).unwrap(), image);
+
+        let frame = Frame::from(image.clone());
+        let path =
+            std::env::temp_dir().join(format!("pixels-android-image-{}.png", std::process::id()));
+        frame.save_png(&path).unwrap();
+        assert_eq!(Image::load(&path).unwrap(), image);
+    }
+
+    #[test]
+    fn png_color_types() {
+        let rgb = encode_png(
+            2,
+            1,
+            png::ColorType::Rgb,
+            png::BitDepth::Eight,
+            &[1, 2, 3, 4, 5, 6],
+        );
+        assert_eq!(
+            Image::decode_png(&rgb).unwrap().data(),
+            [1, 2, 3, 0xff, 4, 5, 6, 0xff]
+        );
+
+        let gray = encode_png(
+            2,
+            1,
+            png::ColorType::Grayscale,
+            png::BitDepth::Eight,
+            &[7, 8],
+        );
+        assert_eq!(
+            Image::decode_png(&gray).unwrap().data(),
+            [7, 7, 7, 0xff, 8, 8, 8, 0xff]
+        );
+
+        let gray_alpha = encode_png(
+            1,
+            1,
+            png::ColorType::GrayscaleAlpha,
+            png::BitDepth::Eight,
+            &[9, 10],
+        );
+        assert_eq!(
+            Image::decode_png(&gray_alpha).unwrap().data(),
+            [9, 9, 9, 10]
+        );
+
+        // 16 bit channels keep their high byte
+        let deep = encode_png(
+            1,
+            1,
+            png::ColorType::Rgba,
+            png::BitDepth::Sixteen,
+            &[1, 2, 3, 4, 5, 6, 7, 8],
+        );
+        assert_eq!(Image::decode_png(&deep).unwrap().data(), [1, 3, 5, 7]);
+    }
+
+    #[test]
+    fn qoi_round_trip() {
+        let image = sample();
+        assert_eq!(Image::decode(&encode_qoi(&image)).unwrap(), image);
+    }
+
+    #[test]
+    fn qoi_chunks() {
+        let mut bytes = b"qoif".to_vec();
+        bytes.extend_from_slice(&7u32.to_be_bytes());
+        bytes.extend_from_slice(&1u32.to_be_bytes());
+        bytes.extend_from_slice(&[4, 0]);
+        bytes.extend_from_slice(&[
+            0xfe, 10, 20, 30,   // RGB
+            0x76, // DIFF by (1, -1, 0)
+            0xa5, 0xa5, // LUMA by (7, 5, 2)
+            0xff, 1, 2, 3, 4,    // RGBA
+            0x09, // INDEX of the first pixel
+            0xc1, // RUN of two
+        ]);
+        bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+
+        let image = Image::decode_qoi(&bytes).unwrap();
+        let pixels: Vec<_> = (0..7).map(|x| image.pixel(x, 0)).collect();
+        assert_eq!(
+            pixels,
+            [
+                [10, 20, 30, 0xff],
+                [11, 19, 30, 0xff],
+                [18, 24, 32, 0xff],
+                [1, 2, 3, 4],
+                [10, 20, 30, 0xff],
+                [10, 20, 30, 0xff],
+                [10, 20, 30, 0xff],
+            ]
+        );
+    }
+
+    #[test]
+    fn qoi_bad_headers() {
+        let bytes = encode_qoi(&sample());
+        assert!(Image::decode_qoi(&bytes[..10]).is_err());
+        assert!(Image::decode_qoi(&bytes[..20]).is_err());
+
+        let mut huge = bytes.clone();
+        huge[4..12].copy_from_slice(&[0xff; 8]);
+        assert!(Image::decode_qoi(&huge).is_err());
+        let mut large = bytes;
+        large[4..12].copy_from_slice(&[0, 1, 0, 0, 0, 1, 0, 0]);
+        assert!(Image::decode_qoi(&large).is_err());
+    }
+
+    #[test]
+    fn bmp_24_bit_bottom_up() {
+        // Rows are stored bottom first and padded to 12 bytes
+        let rows = [
+            [
+                0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x03, 0x02, 0x01, 0, 0, 0,
+            ],
+            [
+                0x00, 0x00, 0xff, 0x00, 0xff, 0x00, 0xff, 0x00, 0x00, 0, 0, 0,
+            ],
+        ]
+        .concat();
+        let image = Image::decode(&encode_bmp(3, 2, 24, 0, &[], &rows)).unwrap();
+
+        let expected = [
+            [0xff, 0x00, 0x00, 0xff],
+            [0x00, 0xff, 0x00, 0xff],
+            [0x00, 0x00, 0xff, 0xff],
+            [0xff, 0xff, 0xff, 0xff],
+            [0x00, 0x00, 0x00, 0xff],
+            [0x01, 0x02, 0x03, 0xff],
+        ];
+        let expected = Image::from_rgba(3, 2, expected.concat()).unwrap();
+        assert_eq!(image, expected);
+    }
+
+    #[test]
+    fn bmp_32_bit_bitfields() {
+        // Top-down with an alpha mask
+        let masks = [0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0xff00_0000];
+        let bmp = encode_bmp(2, -1, 32, 3, &masks, &[30, 20, 10, 40, 3, 2, 1, 0]);
+        let image = Image::decode(&bmp).unwrap();
+        assert_eq!(image.data(), [10, 20, 30, 40, 1, 2, 3, 0]);
+
+        // 5 bit channels without alpha
+        let masks = [0x7c00, 0x03e0, 0x001f];
+        let bmp = encode_bmp(2, 1, 32, 3, &masks, &[0xff, 0x7f, 0, 0, 0x10, 0x42, 0, 0]);
+        let image = Image::decode(&bmp).unwrap();
+        assert_eq!(image.data(), [0xff, 0xff, 0xff, 0xff, 131, 131, 131, 0xff]);
+    }
+
+    #[test]
+    fn bmp_bad_headers() {
+        let rows = [0; 8];
+        let bmp = encode_bmp(2, 1, 32, 0, &[], &rows);
+        assert!(Image::decode_bmp(&bmp).is_ok());
+        assert!(Image::decode_bmp(&bmp[..bmp.len() - 1]).is_err());
+        assert!(Image::decode_bmp(&bmp[..20]).is_err());
+
+        assert!(Image::decode_bmp(&encode_bmp(0, 1, 32, 0, &[], &rows)).is_err());
+        assert!(Image::decode_bmp(&encode_bmp(2, 0, 32, 0, &[], &rows)).is_err());
+        assert!(Image::decode_bmp(&encode_bmp(2, 1, 8, 0, &[], &rows)).is_err());
+        assert!(Image::decode_bmp(&encode_bmp(2, 1, 32, 1, &[], &rows)).is_err());
+        // Sizes whose data size overflows
+        assert!(Image::decode_bmp(&encode_bmp(i32::MAX, i32::MIN, 32, 0, &[], &rows)).is_err());
+        assert!(Image::decode_bmp(&encode_bmp(i32::MAX, i32::MAX, 24, 0, &[], &rows)).is_err());
+
+        let mut far_offset = bmp;
+        far_offset[10..14].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Image::decode_bmp(&far_offset).is_err());
+    }
+
+    #[test]
+    fn truncated_png() {
+        let png = encode_png(
+            3,
+            2,
+            png::ColorType::Rgba,
+            png::BitDepth::Eight,
+            sample().data(),
+        );
+        assert!(Image::decode(&png[..png.len() / 2]).is_err());
+        assert!(Image::decode(&png[..8]).is_err());
+    }
+
+    #[test]
+    fn from_rgba_checks_the_size() {
+        assert!(Image::from_rgba(2, 2, vec![0; 16]).is_ok());
+        assert!(Image::from_rgba(2, 2, vec![0; 15]).is_err());
+        assert!(Image::from_rgba(u32::MAX, u32::MAX, Vec::new()).is_err());
+        assert!(Image::decode(b"GIF89a").is_err());
+    }
+}
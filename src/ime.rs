@@ -0,0 +1,368 @@
+/// Kind of on-screen keyboard to request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyboardType {
+    /// Plain text.
+    #[default]
+    Text,
+    /// Digits only.
+    Number,
+    /// Digits with sign and decimal separator.
+    Decimal,
+    /// Phone number.
+    Phone,
+    /// Email address.
+    Email,
+    /// URI.
+    Uri,
+    /// Hidden text.
+    Password,
+    /// Hidden digits, e.g. a PIN.
+    NumberPassword,
+}
+
+/// Automatic capitalization of text keyboards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Capitalization {
+    #[default]
+    None,
+    Characters,
+    Words,
+    Sentences,
+}
+
+/// Action shown on the enter key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImeAction {
+    /// Let the IME decide.
+    #[default]
+    Unspecified,
+    /// No action, the key inserts a new line if allowed.
+    None,
+    Go,
+    Search,
+    Send,
+    Next,
+    Previous,
+    Done,
+}
+
+/// Options applied when requesting the soft keyboard.
+///
+/// # Android
+///
+/// A stock `NativeActivity` offers no way to change the `EditorInfo` of its view, so these
+/// options are only applied if the app's activity subclass provides
+/// `setImeOptions(int inputType, int imeOptions)` and returns the values from
+/// `onCreateInputConnection`:
+///
+/// ```java
+/// public class MainActivity extends NativeActivity {
+///     private int inputType = InputType.TYPE_CLASS_TEXT;
+///     private int imeOptions = EditorInfo.IME_ACTION_UNSPECIFIED;
+///
+///     public void setImeOptions(int inputType, int imeOptions) {
+///         this.inputType = inputType;
+///         this.imeOptions = imeOptions;
+///     }
+///
+///     // Install this view as the content view in onCreate
+///     class InputView extends View {
+///         InputView(Context context) {
+///             super(context);
+///             setFocusable(true);
+///             setFocusableInTouchMode(true);
+///         }
+///
+///         @Override
+///         public InputConnection onCreateInputConnection(EditorInfo outAttrs) {
+///             outAttrs.inputType = inputType;
+///             outAttrs.imeOptions = imeOptions;
+///             return new BaseInputConnection(this, false);
+///         }
+///     }
+/// }
+/// ```
+///
+/// Without the method the options are ignored and a warning is logged once; the keyboard still
+/// shows, with the IME's defaults. The values passed are [`input_type`](Self::input_type) and
+/// [`ime_options`](Self::ime_options).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImeOptions {
+    pub keyboard: KeyboardType,
+    pub capitalization: Capitalization,
+    pub action: ImeAction,
+    /// Allow entering multiple lines. Only applies to text keyboards.
+    pub multi_line: bool,
+    /// Let the IME correct typos. Ignored for passwords.
+    pub auto_correct: bool,
+    /// Show word suggestions. Ignored for passwords.
+    pub suggestions: bool,
+    /// Allow the IME to take over the whole screen in landscape.
+    pub fullscreen: bool,
+}
+
+impl Default for ImeOptions {
+    fn default() -> Self {
+        Self {
+            keyboard: KeyboardType::Text,
+            capitalization: Capitalization::None,
+            action: ImeAction::Unspecified,
+            multi_line: false,
+            auto_correct: false,
+            suggestions: true,
+            fullscreen: false,
+        }
+    }
+}
+
+/// Constants of `android.text.InputType` and `android.view.inputmethod.EditorInfo`.
+pub mod android {
+    pub const TYPE_CLASS_TEXT: i32 = 0x1;
+    pub const TYPE_CLASS_NUMBER: i32 = 0x2;
+    pub const TYPE_CLASS_PHONE: i32 = 0x3;
+
+    pub const TYPE_TEXT_VARIATION_URI: i32 = 0x10;
+    pub const TYPE_TEXT_VARIATION_EMAIL_ADDRESS: i32 = 0x20;
+    pub const TYPE_TEXT_VARIATION_PASSWORD: i32 = 0x80;
+    pub const TYPE_NUMBER_VARIATION_PASSWORD: i32 = 0x10;
+
+    pub const TYPE_TEXT_FLAG_CAP_CHARACTERS: i32 = 0x1000;
+    pub const TYPE_TEXT_FLAG_CAP_WORDS: i32 = 0x2000;
+    pub const TYPE_TEXT_FLAG_CAP_SENTENCES: i32 = 0x4000;
+    pub const TYPE_TEXT_FLAG_AUTO_CORRECT: i32 = 0x8000;
+    pub const TYPE_TEXT_FLAG_MULTI_LINE: i32 = 0x20000;
+    pub const TYPE_TEXT_FLAG_NO_SUGGESTIONS: i32 = 0x80000;
+
+    pub const TYPE_NUMBER_FLAG_SIGNED: i32 = 0x1000;
+    pub const TYPE_NUMBER_FLAG_DECIMAL: i32 = 0x2000;
+
+    pub const IME_ACTION_UNSPECIFIED: i32 = 0x0;
+    pub const IME_ACTION_NONE: i32 = 0x1;
+    pub const IME_ACTION_GO: i32 = 0x2;
+    pub const IME_ACTION_SEARCH: i32 = 0x3;
+    pub const IME_ACTION_SEND: i32 = 0x4;
+    pub const IME_ACTION_NEXT: i32 = 0x5;
+    pub const IME_ACTION_DONE: i32 = 0x6;
+    pub const IME_ACTION_PREVIOUS: i32 = 0x7;
+
+    pub const IME_FLAG_NO_FULLSCREEN: i32 = 0x2000000;
+    pub const IME_FLAG_NO_EXTRACT_UI: i32 = 0x10000000;
+    pub const IME_FLAG_NO_ENTER_ACTION: i32 = 0x40000000;
+}
+
+impl ImeOptions {
+    /// Default options for `keyboard`.
+    pub fn new(keyboard: KeyboardType) -> Self {
+        Self {
+            keyboard,
+            ..Self::default()
+        }
+    }
+
+    /// Set the enter key action.
+    pub fn with_action(mut self, action: ImeAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Allow entering multiple lines.
+    pub fn with_multi_line(mut self, multi_line: bool) -> Self {
+        self.multi_line = multi_line;
+        self
+    }
+
+    /// Value for `EditorInfo.inputType`.
+    pub fn input_type(&self) -> i32 {
+        use android::*;
+
+        let is_password = matches!(
+            self.keyboard,
+            KeyboardType::Password | KeyboardType::NumberPassword
+        );
+        let text = |variation: i32| {
+            let mut input_type = TYPE_CLASS_TEXT | variation;
+            input_type |= match self.capitalization {
+                Capitalization::None => 0,
+                Capitalization::Characters => TYPE_TEXT_FLAG_CAP_CHARACTERS,
+                Capitalization::Words => TYPE_TEXT_FLAG_CAP_WORDS,
+                Capitalization::Sentences => TYPE_TEXT_FLAG_CAP_SENTENCES,
+            };
+            if self.multi_line && self.keyboard == KeyboardType::Text {
+                input_type |= TYPE_TEXT_FLAG_MULTI_LINE;
+            }
+            if self.auto_correct && !is_password {
+                input_type |= TYPE_TEXT_FLAG_AUTO_CORRECT;
+            }
+            if !self.suggestions || is_password {
+                input_type |= TYPE_TEXT_FLAG_NO_SUGGESTIONS;
+            }
+            input_type
+        };
+
+        match self.keyboard {
+            KeyboardType::Text => text(0),
+            KeyboardType::Email => text(TYPE_TEXT_VARIATION_EMAIL_ADDRESS),
+            KeyboardType::Uri => text(TYPE_TEXT_VARIATION_URI),
+            KeyboardType::Password => text(TYPE_TEXT_VARIATION_PASSWORD),
+            KeyboardType::Number => TYPE_CLASS_NUMBER,
+            KeyboardType::Decimal => {
+                TYPE_CLASS_NUMBER | TYPE_NUMBER_FLAG_SIGNED | TYPE_NUMBER_FLAG_DECIMAL
+            }
+            KeyboardType::NumberPassword => TYPE_CLASS_NUMBER | TYPE_NUMBER_VARIATION_PASSWORD,
+            KeyboardType::Phone => TYPE_CLASS_PHONE,
+        }
+    }
+
+    /// Value for `EditorInfo.imeOptions`.
+    pub fn ime_options(&self) -> i32 {
+        use android::*;
+
+        let mut ime_options = match self.action {
+            ImeAction::Unspecified => IME_ACTION_UNSPECIFIED,
+            ImeAction::None => IME_ACTION_NONE,
+            ImeAction::Go => IME_ACTION_GO,
+            ImeAction::Search => IME_ACTION_SEARCH,
+            ImeAction::Send => IME_ACTION_SEND,
+            ImeAction::Next => IME_ACTION_NEXT,
+            ImeAction::Previous => IME_ACTION_PREVIOUS,
+            ImeAction::Done => IME_ACTION_DONE,
+        };
+        // A multi-line field needs the enter key to insert new lines
+        if self.multi_line && self.keyboard == KeyboardType::Text {
+            ime_options |= IME_FLAG_NO_ENTER_ACTION;
+        }
+        if !self.fullscreen {
+            ime_options |= IME_FLAG_NO_FULLSCREEN | IME_FLAG_NO_EXTRACT_UI;
+        }
+        ime_options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::android::*;
+    use super::*;
+
+    /// Flags set by the default options besides the keyboard class.
+    const NO_FULLSCREEN: i32 = IME_FLAG_NO_FULLSCREEN | IME_FLAG_NO_EXTRACT_UI;
+
+    #[test]
+    fn android_constants() {
+        // Values from the Android SDK documentation
+        assert_eq!(TYPE_CLASS_TEXT, 0x0000_0001);
+        assert_eq!(TYPE_CLASS_NUMBER, 0x0000_0002);
+        assert_eq!(TYPE_CLASS_PHONE, 0x0000_0003);
+        assert_eq!(TYPE_TEXT_VARIATION_EMAIL_ADDRESS, 0x0000_0020);
+        assert_eq!(TYPE_TEXT_VARIATION_PASSWORD, 0x0000_0080);
+        assert_eq!(TYPE_TEXT_FLAG_MULTI_LINE, 0x0002_0000);
+        assert_eq!(IME_ACTION_SEARCH, 0x0000_0003);
+        assert_eq!(IME_ACTION_NEXT, 0x0000_0005);
+        assert_eq!(IME_ACTION_DONE, 0x0000_0006);
+        assert_eq!(IME_FLAG_NO_ENTER_ACTION, 0x4000_0000);
+    }
+
+    #[test]
+    fn input_type_per_keyboard() {
+        let cases = [
+            (KeyboardType::Text, TYPE_CLASS_TEXT),
+            (
+                KeyboardType::Email,
+                TYPE_CLASS_TEXT | TYPE_TEXT_VARIATION_EMAIL_ADDRESS,
+            ),
+            (KeyboardType::Uri, TYPE_CLASS_TEXT | TYPE_TEXT_VARIATION_URI),
+            (
+                KeyboardType::Password,
+                TYPE_CLASS_TEXT | TYPE_TEXT_VARIATION_PASSWORD | TYPE_TEXT_FLAG_NO_SUGGESTIONS,
+            ),
+            (KeyboardType::Number, TYPE_CLASS_NUMBER),
+            (
+                KeyboardType::Decimal,
+                TYPE_CLASS_NUMBER | TYPE_NUMBER_FLAG_SIGNED | TYPE_NUMBER_FLAG_DECIMAL,
+            ),
+            (
+                KeyboardType::NumberPassword,
+                TYPE_CLASS_NUMBER | TYPE_NUMBER_VARIATION_PASSWORD,
+            ),
+            (KeyboardType::Phone, TYPE_CLASS_PHONE),
+        ];
+        for (keyboard, expected) in cases {
+            assert_eq!(
+                ImeOptions::new(keyboard).input_type(),
+                expected,
+                "{:?}",
+                keyboard
+            );
+        }
+    }
+
+    #[test]
+    fn input_type_flags() {
+        let options = ImeOptions {
+            capitalization: Capitalization::Sentences,
+            auto_correct: true,
+            suggestions: false,
+            ..ImeOptions::new(KeyboardType::Text).with_multi_line(true)
+        };
+        assert_eq!(
+            options.input_type(),
+            TYPE_CLASS_TEXT
+                | TYPE_TEXT_FLAG_CAP_SENTENCES
+                | TYPE_TEXT_FLAG_MULTI_LINE
+                | TYPE_TEXT_FLAG_AUTO_CORRECT
+                | TYPE_TEXT_FLAG_NO_SUGGESTIONS
+        );
+
+        // Passwords never auto-correct
+        let options = ImeOptions {
+            auto_correct: true,
+            ..ImeOptions::new(KeyboardType::Password)
+        };
+        assert_eq!(options.input_type() & TYPE_TEXT_FLAG_AUTO_CORRECT, 0);
+
+        // Only plain text keyboards are multi-line
+        for keyboard in [KeyboardType::Email, KeyboardType::Number] {
+            let options = ImeOptions::new(keyboard).with_multi_line(true);
+            assert_eq!(options.input_type() & TYPE_TEXT_FLAG_MULTI_LINE, 0);
+        }
+    }
+
+    #[test]
+    fn ime_options_per_action() {
+        let cases = [
+            (ImeAction::Unspecified, IME_ACTION_UNSPECIFIED),
+            (ImeAction::None, IME_ACTION_NONE),
+            (ImeAction::Go, IME_ACTION_GO),
+            (ImeAction::Search, IME_ACTION_SEARCH),
+            (ImeAction::Send, IME_ACTION_SEND),
+            (ImeAction::Next, IME_ACTION_NEXT),
+            (ImeAction::Previous, IME_ACTION_PREVIOUS),
+            (ImeAction::Done, IME_ACTION_DONE),
+        ];
+        for (action, expected) in cases {
+            let options = ImeOptions::default().with_action(action);
+            assert_eq!(
+                options.ime_options(),
+                expected | NO_FULLSCREEN,
+                "{:?}",
+                action
+            );
+        }
+    }
+
+    #[test]
+    fn ime_options_multi_line_and_fullscreen() {
+        let options = ImeOptions::new(KeyboardType::Text)
+            .with_action(ImeAction::Done)
+            .with_multi_line(true);
+        assert_eq!(
+            options.ime_options(),
+            IME_ACTION_DONE | IME_FLAG_NO_ENTER_ACTION | NO_FULLSCREEN
+        );
+
+        let options = ImeOptions {
+            fullscreen: true,
+            ..ImeOptions::default().with_action(ImeAction::Next)
+        };
+        assert_eq!(options.ime_options(), IME_ACTION_NEXT);
+    }
+}
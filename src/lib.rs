@@ -1,199 +1,136 @@
 #![deny(clippy::all)]
 
-use log::error;
-use pixels::{Pixels, SurfaceTexture};
-use winit::dpi::LogicalSize;
-use winit::event::{Event, TouchPhase, WindowEvent};
-use winit::event_loop::{ControlFlow, EventLoop};
-use winit::window::WindowBuilder;
-
-const WIDTH: u32 = 320;
-const HEIGHT: u32 = 240;
-const BOX_SIZE: i16 = 64;
+pub mod app;
+pub mod assets;
+pub mod canvas;
+pub mod config;
+pub mod font;
+pub mod frame;
+pub mod gesture;
+pub mod headless;
+pub mod image;
+pub mod ime;
+pub mod recording;
+mod renderer;
+pub mod replay;
+pub mod resize;
+pub mod save_state;
+pub mod screenshot;
+pub mod snapshot;
+pub mod soft_input;
+pub mod sprite;
+pub mod stats;
+pub mod text_input;
+pub mod timing;
+pub mod touch;
+pub mod viewport;
+
+pub use app::{run_app, PixelsApp};
+pub use assets::{AssetError, AssetReader, Assets};
+pub use canvas::{BlendMode, BlitOptions, Canvas, Rect};
+pub use config::{AppConfig, AppConfigBuilder};
+pub use font::{Align, Font, TextStyle};
+pub use frame::Frame;
+pub use headless::Headless;
+pub use image::Image;
+
+use serde::{Deserialize, Serialize};
+use text_input::TextInput;
 
 /// Representation of the application state. In this example, a box will bounce around the screen.
-struct World {
+pub struct World {
+    width: i16,
+    height: i16,
+    box_size: i16,
     box_x: i16,
     box_y: i16,
     velocity_x: i16,
     velocity_y: i16,
+    font: Font,
+    text: String,
 }
 
-#[cfg_attr(
-    target_os = "android",
-    ndk_glue::main(backtrace = "on", logger(tag = "pixels-android", level = "info"))
-)]
-fn main() {
-    run().unwrap();
-}
-
-fn show_soft_input(show: bool) -> bool {
-    let ctx = ndk_glue::native_activity();
-
-    let vm = unsafe { jni::JavaVM::from_raw(ctx.vm().cast()) }.unwrap();
-    let env = vm.attach_current_thread().unwrap();
-
-    let class_ctxt = env.find_class("android/content/Context").unwrap();
-    let ime = env
-        .get_static_field(class_ctxt, "INPUT_METHOD_SERVICE", "Ljava/lang/String;")
-        .unwrap();
-    let ime_manager = env
-        .call_method(
-            ctx.activity(),
-            "getSystemService",
-            "(Ljava/lang/String;)Ljava/lang/Object;",
-            &[ime],
-        )
-        .unwrap()
-        .l()
-        .unwrap();
-
-    let jni_window = env
-        .call_method(ctx.activity(), "getWindow", "()Landroid/view/Window;", &[])
-        .unwrap()
-        .l()
-        .unwrap();
-    let view = env
-        .call_method(jni_window, "getDecorView", "()Landroid/view/View;", &[])
-        .unwrap()
-        .l()
-        .unwrap();
-
-    if show {
-        let result = env
-            .call_method(
-                ime_manager,
-                "showSoftInput",
-                "(Landroid/view/View;I)Z",
-                &[view.into(), 0i32.into()],
-            )
-            .unwrap()
-            .z()
-            .unwrap();
-        log::info!("show input: {}", result);
-        result
-    } else {
-        let window_token = env
-            .call_method(view, "getWindowToken", "()Landroid/os/IBinder;", &[])
-            .unwrap()
-            .l()
-            .unwrap();
-        let result = env
-            .call_method(
-                ime_manager,
-                "hideSoftInputFromWindow",
-                "(Landroid/os/IBinder;I)Z",
-                &[window_token.into(), 0i32.into()],
-            )
-            .unwrap()
-            .z()
-            .unwrap();
-        log::info!("hide input: {}", result);
-        result
-    }
+/// The part of `World` kept while the app is suspended.
+#[derive(Serialize, Deserialize)]
+struct WorldState {
+    box_x: i16,
+    box_y: i16,
+    velocity_x: i16,
+    velocity_y: i16,
+    text: String,
 }
 
-fn run() -> anyhow::Result<()> {
-    let event_loop = EventLoop::new();
-    let window = {
-        let size = LogicalSize::new(WIDTH as f64, HEIGHT as f64);
-        WindowBuilder::new()
-            .with_title("Hello Pixels")
-            .with_inner_size(size)
-            .with_min_inner_size(size)
-            .build(&event_loop)
-            .unwrap()
-    };
-
-    let mut pixels: Option<Pixels> = None;
-    let mut world = World::new();
-
-    let mut soft_keyboard = false;
-
-    event_loop.run(move |event, _, control_flow| {
-        control_flow.set_poll();
-
-        if let Event::Resumed = event {
-            log::info!("resumed");
-
-            pixels = Some({
-                let window_size = window.inner_size();
-                let surface_texture =
-                    SurfaceTexture::new(window_size.width, window_size.height, &window);
-                Pixels::new(WIDTH, HEIGHT, surface_texture).unwrap()
-            });
-        }
-
-        if let Event::Suspended = event {
-            pixels = None;
-        }
-
-        if let Some(pixels) = pixels.as_mut() {
-            // Draw the current frame
-            match event {
-                Event::RedrawRequested(_) => {
-                    world.draw(pixels.get_frame());
-                    if pixels
-                        .render()
-                        .map_err(|e| error!("pixels.render() failed: {}", e))
-                        .is_err()
-                    {
-                        *control_flow = ControlFlow::Exit;
-                        return;
-                    }
-                }
-                Event::MainEventsCleared => {
-                    // Update internal state and request a redraw
-                    world.update();
-                    window.request_redraw();
-                }
-                Event::WindowEvent {
-                    event: WindowEvent::CloseRequested,
-                    ..
-                } => {
-                    *control_flow = ControlFlow::Exit;
-                }
-
-                Event::WindowEvent {
-                    event: WindowEvent::Touch(touch),
-                    ..
-                } => {
-                    if touch.phase == TouchPhase::Started {
-                        // toggle software keyboard
-                        soft_keyboard = !soft_keyboard;
-                        show_soft_input(soft_keyboard);
-                    }
-                }
-                Event::WindowEvent {
-                    event: WindowEvent::KeyboardInput { input, .. },
-                    ..
-                } => {
-                    log::info!("input: {:?}", input);
-                }
-
-                _ => (),
-            }
-        }
-    });
+#[cfg_attr(target_os = "android", ndk_glue::main(backtrace = "on"))]
+#[cfg_attr(not(target_os = "android"), allow(dead_code))]
+fn main() {
+    run_app::<World>(AppConfig::default()).unwrap();
 }
 
-impl World {
+impl PixelsApp for World {
     /// Create a new `World` instance that can draw a moving box.
-    fn new() -> Self {
+    fn init(config: &AppConfig) -> Self {
+        // The buffer is at most `MAX_BUFFER_SIZE` pixels wide and high, so it fits an `i16`
+        let width = config.width() as i16;
+        let height = config.height() as i16;
         Self {
+            width,
+            height,
+            box_size: 64.min(width).min(height),
             box_x: 24,
             box_y: 16,
             velocity_x: 1,
             velocity_y: 1,
+            font: Font::builtin(),
+            text: String::new(),
+        }
+    }
+
+    /// Keep the text typed on the soft keyboard.
+    fn text_input(&mut self, input: TextInput) {
+        log::info!("input: {:?}", input);
+        match input {
+            TextInput::Commit(text) => self.text.push_str(&text),
+            TextInput::Backspace => {
+                self.text.pop();
+            }
+            _ => (),
         }
     }
 
+    /// Keep the box position and the typed text.
+    fn save_state(&self) -> Option<Vec<u8>> {
+        let state = WorldState {
+            box_x: self.box_x,
+            box_y: self.box_y,
+            velocity_x: self.velocity_x,
+            velocity_y: self.velocity_y,
+            text: self.text.clone(),
+        };
+        serde_json::to_vec(&state).ok()
+    }
+
+    fn state_version(&self) -> u32 {
+        1
+    }
+
+    fn restore_state(&mut self, version: u32, data: &[u8]) -> anyhow::Result<()> {
+        anyhow::ensure!(version == 1, "unknown state version {}", version);
+        let state: WorldState = serde_json::from_slice(data)?;
+        // The buffer may have shrunk since the state was saved
+        self.box_x = state.box_x.clamp(0, self.width - self.box_size);
+        self.box_y = state.box_y.clamp(0, self.height - self.box_size);
+        self.velocity_x = state.velocity_x;
+        self.velocity_y = state.velocity_y;
+        self.text = state.text;
+        Ok(())
+    }
+
     /// Update the `World` internal state; bounce the box around the screen.
     fn update(&mut self) {
-        if self.box_x <= 0 || self.box_x + BOX_SIZE > WIDTH as i16 {
+        if self.box_x <= 0 || self.box_x + self.box_size > self.width {
             self.velocity_x *= -1;
         }
-        if self.box_y <= 0 || self.box_y + BOX_SIZE > HEIGHT as i16 {
+        if self.box_y <= 0 || self.box_y + self.box_size > self.height {
             self.velocity_y *= -1;
         }
 
@@ -204,23 +141,97 @@ impl World {
     /// Draw the `World` state to the frame buffer.
     ///
     /// Assumes the default texture format: `wgpu::TextureFormat::Rgba8UnormSrgb`
-    fn draw(&self, frame: &mut [u8]) {
-        for (i, pixel) in frame.chunks_exact_mut(4).enumerate() {
-            let x = (i % WIDTH as usize) as i16;
-            let y = (i / WIDTH as usize) as i16;
-
-            let inside_the_box = x >= self.box_x
-                && x < self.box_x + BOX_SIZE
-                && y >= self.box_y
-                && y < self.box_y + BOX_SIZE;
-
-            let rgba = if inside_the_box {
-                [0x5e, 0x48, 0xe8, 0xff]
-            } else {
-                [0x48, 0xb2, 0xe8, 0xff]
-            };
-
-            pixel.copy_from_slice(&rgba);
+    fn draw(&self, frame: &mut [u8], _alpha: f64) {
+        let mut canvas = Canvas::new(frame, self.width as u32, self.height as u32);
+        canvas.clear([0x48, 0xb2, 0xe8, 0xff]);
+        canvas.fill_rect(
+            self.box_x as i32,
+            self.box_y as i32,
+            self.box_size as u32,
+            self.box_size as u32,
+            [0x5e, 0x48, 0xe8, 0xff],
+        );
+
+        let style = TextStyle::new([0xff, 0xff, 0xff, 0xff])
+            .with_max_width((self.width as u32).saturating_sub(8));
+        self.font.draw(&mut canvas, &self.text, 4, 4, &style);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::Headless;
+    use crate::snapshot::Snapshot;
+
+    const BACKGROUND: [u8; 4] = [0x48, 0xb2, 0xe8, 0xff];
+    const BOX: [u8; 4] = [0x5e, 0x48, 0xe8, 0xff];
+
+    #[test]
+    fn world_runs_headless() {
+        let config = AppConfig::builder().buffer_size(160, 120).build().unwrap();
+        let mut headless = Headless::<World>::new(&config);
+        let frames = headless.run(10);
+
+        assert_eq!(frames.len(), 10);
+        assert_eq!(headless.frame_count(), 10);
+        for frame in &frames {
+            assert_eq!((frame.width, frame.height), (160, 120));
+            assert_eq!(frame.data.len(), 160 * 120 * 4);
         }
+
+        // The box starts at (24, 16) and moves one pixel down and right per update
+        for (i, frame) in frames.iter().enumerate() {
+            let (x, y) = (25 + i as u32, 17 + i as u32);
+            assert_eq!(frame.pixel(x, y), BOX, "frame {}", i);
+            assert_eq!(frame.pixel(x + 63, y + 63), BOX, "frame {}", i);
+            assert_eq!(frame.pixel(x - 1, y), BACKGROUND, "frame {}", i);
+            assert_eq!(frame.pixel(x + 64, y + 63), BACKGROUND, "frame {}", i);
+        }
+    }
+
+    #[test]
+    fn world_state_round_trip() {
+        let config = AppConfig::default();
+        let mut world = World::init(&config);
+        world.update();
+        world.update();
+        world.text_input(TextInput::Commit("hi".to_string()));
+        let data = world.save_state().unwrap();
+
+        let mut restored = World::init(&config);
+        restored
+            .restore_state(world.state_version(), &data)
+            .unwrap();
+        assert_eq!((restored.box_x, restored.box_y), (26, 18));
+        assert_eq!((restored.velocity_x, restored.velocity_y), (1, 1));
+        assert_eq!(restored.text, "hi");
+
+        // State from an unknown version is rejected and leaves the world alone
+        let mut fresh = World::init(&config);
+        assert!(fresh.restore_state(2, &data).is_err());
+        assert_eq!((fresh.box_x, fresh.box_y), (24, 16));
+        assert!(fresh.restore_state(1, b"not json").is_err());
+    }
+
+    #[test]
+    fn world_state_is_clamped_to_a_smaller_buffer() {
+        let mut world = World::init(&AppConfig::default());
+        world.box_x = 250;
+        world.box_y = 170;
+        let data = world.save_state().unwrap();
+
+        let config = AppConfig::builder().buffer_size(100, 80).build().unwrap();
+        let mut restored = World::init(&config);
+        restored.restore_state(1, &data).unwrap();
+        assert_eq!((restored.box_x, restored.box_y), (36, 16));
+    }
+
+    #[test]
+    fn world_snapshot() {
+        Snapshot::new(concat!(env!("CARGO_MANIFEST_DIR"), "/snapshots"))
+            .diff_dir(std::env::temp_dir().join("pixels-android-snapshots"))
+            .check_app::<World>("world", &AppConfig::default(), 60)
+            .unwrap();
     }
 }
@@ -0,0 +1,524 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, ensure, Context};
+
+use crate::frame::Frame;
+
+/// File format of a [`FrameRecorder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecordingFormat {
+    /// Animated GIF with a palette of up to 256 colors quantized per frame.
+    #[default]
+    Gif,
+    /// Animated PNG, lossless but larger.
+    Apng,
+}
+
+impl RecordingFormat {
+    /// File extension without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Gif => "gif",
+            Self::Apng => "png",
+        }
+    }
+}
+
+/// Settings of a [`FrameRecorder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordingOptions {
+    pub format: RecordingFormat,
+    /// How long to record for.
+    pub duration: Duration,
+    /// Frames captured per second. Frames drawn in between are skipped.
+    pub fps: u32,
+    /// Integer factor the frames are scaled up by.
+    pub scale: u32,
+}
+
+impl RecordingOptions {
+    /// Record `duration` at 30 frames per second in the buffer resolution.
+    pub fn new(format: RecordingFormat, duration: Duration) -> Self {
+        Self {
+            format,
+            duration,
+            fps: 30,
+            scale: 1,
+        }
+    }
+
+    pub fn with_fps(mut self, fps: u32) -> Self {
+        self.fps = fps;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: u32) -> Self {
+        self.scale = scale;
+        self
+    }
+}
+
+/// Frames queued for the worker thread before further captures are dropped.
+const QUEUE_LEN: usize = 8;
+
+/// Largest width or height of recorded frames after scaling. Fits a GIF and keeps a scaled frame
+/// at 1 GiB at most.
+pub const MAX_SCALED_SIZE: u32 = 16384;
+
+/// Records frames to an animated GIF or APNG.
+///
+/// Frames are copied on [`capture`](Self::capture) and sent to a worker thread, which does the
+/// scaling, color quantization and compression, so recording costs the render thread little more
+/// than a copy of the frame buffer. If the worker falls behind, frames are dropped instead of
+/// queued, see [`dropped_frames`](Self::dropped_frames). The delay of each frame is taken from the
+/// capture times, so the animation plays at the speed it was recorded at even if frames were
+/// dropped.
+#[derive(Debug)]
+pub struct FrameRecorder {
+    width: u32,
+    height: u32,
+    options: RecordingOptions,
+    sender: Option<SyncSender<(Vec<u8>, Instant)>>,
+    worker: Option<JoinHandle<anyhow::Result<PathBuf>>>,
+    start: Option<Instant>,
+    next_capture: Option<Instant>,
+    dropped_frames: u64,
+}
+
+impl FrameRecorder {
+    /// Start recording frames of `width * height` pixels to `path`, replacing the file.
+    ///
+    /// The file is created right away, so a bad path is reported here rather than when the
+    /// recording finishes.
+    pub fn start(
+        path: impl Into<PathBuf>,
+        width: u32,
+        height: u32,
+        options: RecordingOptions,
+    ) -> anyhow::Result<Self> {
+        ensure!(options.fps > 0, "recording frame rate must not be zero");
+        let scaled_size = scaled_size(width, height, options.scale)?;
+
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Readable as well, to patch the APNG frame count in the end
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .with_context(|| format!("failed to create {}", path.display()))?;
+
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_LEN);
+        let worker = std::thread::Builder::new()
+            .name("frame-recorder".to_string())
+            .spawn(move || {
+                let encoded = match options.format {
+                    RecordingFormat::Gif => {
+                        let writer = BufWriter::new(file);
+                        encode_gif(writer, receiver, width, height, scaled_size, options)
+                    }
+                    RecordingFormat::Apng => {
+                        encode_apng(file, receiver, width, height, scaled_size, options)
+                    }
+                };
+                encoded
+                    .with_context(|| format!("failed to encode {}", path.display()))
+                    .map(|()| path)
+            })?;
+
+        Ok(Self {
+            width,
+            height,
+            options,
+            sender: Some(sender),
+            worker: Some(worker),
+            start: None,
+            next_capture: None,
+            dropped_frames: 0,
+        })
+    }
+
+    pub fn options(&self) -> &RecordingOptions {
+        &self.options
+    }
+
+    /// Whether frames are still being captured.
+    pub fn is_capturing(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    /// Number of frames that were due but dropped because the worker was busy.
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped_frames
+    }
+
+    /// Capture `frame`, drawn at `now`, if the next frame is due. The first captured frame starts
+    /// the recording; capturing stops once the duration has passed.
+    pub fn capture(&mut self, frame: &[u8], now: Instant) {
+        if self.sender.is_none() {
+            return;
+        }
+        assert_eq!(
+            frame.len(),
+            self.width as usize * self.height as usize * 4,
+            "frame size does not match the recording"
+        );
+
+        self.start.get_or_insert(now);
+        self.update(now);
+        if self.sender.is_none() {
+            return;
+        }
+        if matches!(self.next_capture, Some(next) if now < next) {
+            return;
+        }
+
+        let interval = Duration::from_secs(1) / self.options.fps;
+        let mut next = self.next_capture.unwrap_or(now) + interval;
+        // Skip the captures missed during a stall instead of catching up with a burst
+        if next <= now {
+            next = now + interval;
+        }
+        self.next_capture = Some(next);
+
+        let sent = self
+            .sender
+            .as_ref()
+            .map(|sender| sender.try_send((frame.to_vec(), now)));
+        match sent {
+            Some(Err(TrySendError::Full(_))) => self.dropped_frames += 1,
+            // The worker failed; the error is reported by `poll` or `finish`
+            Some(Err(TrySendError::Disconnected(_))) => self.stop(),
+            _ => (),
+        }
+    }
+
+    /// Stop capturing once the duration has passed since the first captured frame. Called by
+    /// the runner every frame, so a recording also ends on time while nothing is drawn.
+    pub fn update(&mut self, now: Instant) {
+        let elapsed = self.start.map(|start| now.saturating_duration_since(start));
+        if matches!(elapsed, Some(elapsed) if elapsed >= self.options.duration) {
+            self.stop();
+        }
+    }
+
+    /// Stop capturing. The worker finishes encoding the frames captured so far.
+    pub fn stop(&mut self) {
+        if self.sender.take().is_some() && self.dropped_frames > 0 {
+            log::warn!(
+                "recording dropped {} frames while the encoder was busy",
+                self.dropped_frames
+            );
+        }
+    }
+
+    /// The result of the recording once capturing stopped and the file is written, `None` before
+    /// and after.
+    pub fn poll(&mut self) -> Option<anyhow::Result<PathBuf>> {
+        if self.sender.is_some() || !self.worker.as_ref()?.is_finished() {
+            return None;
+        }
+        self.worker.take().map(join)
+    }
+
+    /// Stop capturing and wait until the file is written.
+    pub fn finish(mut self) -> anyhow::Result<PathBuf> {
+        self.stop();
+        let worker = self
+            .worker
+            .take()
+            .ok_or_else(|| anyhow!("recording already finished"))?;
+        join(worker)
+    }
+}
+
+fn join(worker: JoinHandle<anyhow::Result<PathBuf>>) -> anyhow::Result<PathBuf> {
+    worker
+        .join()
+        .map_err(|_| anyhow!("frame recorder thread panicked"))?
+}
+
+/// Receive frames until the sender is dropped and pass each one on with its delay, the time until
+/// the next frame was captured. The last frame is shown for one frame interval.
+fn for_each_frame(
+    receiver: Receiver<(Vec<u8>, Instant)>,
+    width: u32,
+    height: u32,
+    options: RecordingOptions,
+    mut f: impl FnMut(Frame, Duration) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let to_frame = |data: Vec<u8>| {
+        let mut frame = Frame {
+            width,
+            height,
+            data,
+        }
+        .scaled(options.scale);
+        // The frame buffer is shown opaque, whatever the app wrote to the alpha channel
+        for pixel in frame.data.chunks_exact_mut(4) {
+            pixel[3] = 0xff;
+        }
+        frame
+    };
+
+    let mut pending: Option<(Vec<u8>, Instant)> = None;
+    for (data, time) in receiver {
+        if let Some((previous, previous_time)) = pending.replace((data, time)) {
+            f(to_frame(previous), time - previous_time)?;
+        }
+    }
+    if let Some((last, _)) = pending {
+        f(to_frame(last), Duration::from_secs(1) / options.fps)?;
+    }
+    Ok(())
+}
+
+/// Size of `width * height` frames scaled up by `scale`, checked against [`MAX_SCALED_SIZE`].
+fn scaled_size(width: u32, height: u32, scale: u32) -> anyhow::Result<(u32, u32)> {
+    ensure!(scale > 0, "recording scale must not be zero");
+    let scaled = width.checked_mul(scale).zip(height.checked_mul(scale));
+    match scaled {
+        Some((scaled_width, scaled_height))
+            if scaled_width <= MAX_SCALED_SIZE && scaled_height <= MAX_SCALED_SIZE =>
+        {
+            Ok((scaled_width, scaled_height))
+        }
+        _ => bail!(
+            "{}x{} frames scaled by {} exceed the {}x{} recording limit",
+            width,
+            height,
+            scale,
+            MAX_SCALED_SIZE,
+            MAX_SCALED_SIZE
+        ),
+    }
+}
+
+fn encode_gif(
+    writer: impl Write,
+    receiver: Receiver<(Vec<u8>, Instant)>,
+    width: u32,
+    height: u32,
+    (scaled_width, scaled_height): (u32, u32),
+    options: RecordingOptions,
+) -> anyhow::Result<()> {
+    // Within the GIF limits, see `MAX_SCALED_SIZE`
+    let (scaled_width, scaled_height) = (scaled_width as u16, scaled_height as u16);
+    let mut encoder = gif::Encoder::new(writer, scaled_width, scaled_height, &[])?;
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+
+    // GIF delays are in hundredths of a second; carry the rounding error to the next frame
+    let mut error = 0.0;
+    let mut frames = 0;
+    for_each_frame(receiver, width, height, options, |mut frame, delay| {
+        let exact = delay.as_secs_f64() * 100.0 + error;
+        let centis = exact.round().clamp(1.0, u16::MAX as f64);
+        error = exact - centis;
+
+        let mut gif_frame =
+            gif::Frame::from_rgba_speed(scaled_width, scaled_height, &mut frame.data, 10);
+        gif_frame.delay = centis as u16;
+        encoder.write_frame(&gif_frame)?;
+        frames += 1;
+        Ok(())
+    })?;
+    ensure!(frames > 0, "no frames were captured");
+    Ok(())
+}
+
+fn encode_apng(
+    file: File,
+    receiver: Receiver<(Vec<u8>, Instant)>,
+    width: u32,
+    height: u32,
+    (scaled_width, scaled_height): (u32, u32),
+    options: RecordingOptions,
+) -> anyhow::Result<()> {
+    // The frame count goes in front of the frames. Declare the most frames the recording can
+    // have and patch in the actual count once the frames are written, so no frame is kept around.
+    let max_frames = (options.duration.as_secs_f64() * options.fps as f64).ceil() + 1.0;
+    let max_frames = max_frames.min(u32::MAX as f64) as u32;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(&file), scaled_width, scaled_height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(max_frames, 0)?;
+    let mut writer = encoder.write_header()?;
+
+    let mut frames = 0;
+    for_each_frame(receiver, width, height, options, |frame, delay| {
+        if frames == max_frames {
+            return Ok(());
+        }
+        let millis = delay.as_millis().clamp(1, u16::MAX as u128) as u16;
+        writer.set_frame_delay(millis, 1000)?;
+        writer.write_image_data(&frame.data)?;
+        frames += 1;
+        Ok(())
+    })?;
+    ensure!(frames > 0, "no frames were captured");
+    writer.finish()?;
+
+    patch_frame_count(&file, frames)
+}
+
+/// Offset of the `acTL` chunk, which follows the PNG signature and the `IHDR` chunk.
+const ACTL_OFFSET: u64 = 8 + 25;
+
+/// Overwrite the frame count in the `acTL` chunk of an APNG file.
+fn patch_frame_count(mut file: &File, frames: u32) -> anyhow::Result<()> {
+    // Length, type, frame count, play count and CRC
+    let mut chunk = [0; 20];
+    file.seek(SeekFrom::Start(ACTL_OFFSET))?;
+    file.read_exact(&mut chunk)?;
+    ensure!(
+        &chunk[4..8] == b"acTL",
+        "APNG has no animation control chunk"
+    );
+
+    chunk[8..12].copy_from_slice(&frames.to_be_bytes());
+    let crc = crc32fast::hash(&chunk[4..16]);
+    chunk[16..20].copy_from_slice(&crc.to_be_bytes());
+    file.seek(SeekFrom::Start(ACTL_OFFSET))?;
+    file.write_all(&chunk)?;
+    Ok(())
+}
+
+/// Name of a new recording in `dir`, e.g. `recording-1700000000123.gif`.
+pub(crate) fn recording_path(dir: &Path, format: RecordingFormat) -> PathBuf {
+    crate::screenshot::timestamped_path(dir, "recording", format.extension())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "pixels-android-recording-{}-{}",
+            std::process::id(),
+            name
+        ))
+    }
+
+    /// Capture three frames of 2×1 pixels, 50 ms apart, colored by their index.
+    fn record(path: &Path, options: RecordingOptions) -> Instant {
+        let mut recorder = FrameRecorder::start(path, 2, 1, options).unwrap();
+        let start = Instant::now();
+        for i in 0..3u8 {
+            let now = start + Duration::from_millis(50 * i as u64);
+            recorder.capture(&[i * 10, 0, 0, 0, 0, i * 10, 0, 0x80], now);
+        }
+        assert_eq!(recorder.finish().unwrap(), path);
+        start
+    }
+
+    #[test]
+    fn apng_frames() {
+        let path = temp_path("frames.png");
+        let options = RecordingOptions::new(RecordingFormat::Apng, Duration::from_secs(10))
+            .with_fps(20)
+            .with_scale(2);
+        record(&path, options);
+
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        assert_eq!((info.width, info.height), (4, 2));
+        // Patched from the 201 frames declared up front
+        assert_eq!(info.animation_control.unwrap().num_frames, 3);
+
+        let mut buf = vec![0; reader.output_buffer_size()];
+        for i in 0..3u8 {
+            reader.next_frame(&mut buf).unwrap();
+            let control = reader.info().frame_control.unwrap();
+            assert_eq!((control.delay_num, control.delay_den), (50, 1000));
+            // Scaled up, with opaque alpha
+            let (red, green) = ([i * 10, 0, 0, 0xff], [0, i * 10, 0, 0xff]);
+            assert_eq!(
+                buf,
+                [red, red, green, green, red, red, green, green].concat()
+            );
+        }
+    }
+
+    #[test]
+    fn gif_frames() {
+        let path = temp_path("frames.gif");
+        let options = RecordingOptions::new(RecordingFormat::Gif, Duration::from_secs(10));
+        record(&path, options);
+
+        let mut decoder = gif::DecodeOptions::new()
+            .read_info(File::open(&path).unwrap())
+            .unwrap();
+        let mut delays = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            delays.push(frame.delay);
+        }
+        // The last frame is shown for one frame interval at 30 fps
+        assert_eq!(delays, [5, 5, 3]);
+    }
+
+    #[test]
+    fn stops_after_duration() {
+        let path = temp_path("duration.png");
+        let options = RecordingOptions::new(RecordingFormat::Apng, Duration::from_millis(100));
+        let mut recorder = FrameRecorder::start(&path, 1, 1, options).unwrap();
+        let start = Instant::now();
+
+        // Nothing is recorded before the first capture
+        recorder.update(start + Duration::from_secs(1));
+        assert!(recorder.is_capturing());
+
+        recorder.capture(&[0; 4], start);
+        recorder.update(start + Duration::from_millis(99));
+        assert!(recorder.is_capturing());
+        recorder.update(start + Duration::from_millis(100));
+        assert!(!recorder.is_capturing());
+        assert_eq!(recorder.dropped_frames(), 0);
+
+        // Captures after the end are ignored
+        recorder.capture(&[0xff; 4], start + Duration::from_millis(150));
+        recorder.finish().unwrap();
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let reader = decoder.read_info().unwrap();
+        assert_eq!(reader.info().animation_control.unwrap().num_frames, 1);
+    }
+
+    #[test]
+    fn empty_recording_fails() {
+        for format in [RecordingFormat::Gif, RecordingFormat::Apng] {
+            let path = temp_path(&format!("empty.{}", format.extension()));
+            let options = RecordingOptions::new(format, Duration::from_secs(1));
+            let recorder = FrameRecorder::start(&path, 1, 1, options).unwrap();
+            let error = recorder.finish().unwrap_err();
+            assert!(format!("{:#}", error).contains("no frames"), "{:#}", error);
+        }
+    }
+
+    #[test]
+    fn oversized_frames_are_rejected() {
+        let options = RecordingOptions::new(RecordingFormat::Apng, Duration::from_secs(1));
+        let start = |width, height, scale| {
+            let path = temp_path("oversized.png");
+            FrameRecorder::start(path, width, height, options.with_scale(scale))
+        };
+
+        assert!(start(MAX_SCALED_SIZE / 4, 1, 4).is_ok());
+        assert!(start(MAX_SCALED_SIZE / 4 + 1, 1, 4).is_err());
+        assert!(start(1, MAX_SCALED_SIZE + 1, 1).is_err());
+        // Would wrap around in a plain multiplication
+        assert!(start(1 << 16, 1 << 16, 1 << 16).is_err());
+        assert!(start(u32::MAX, 1, u32::MAX).is_err());
+        assert!(start(1, 1, 0).is_err());
+    }
+}
@@ -0,0 +1,188 @@
+use pixels::wgpu;
+use pixels::wgpu::util::DeviceExt;
+
+use crate::viewport::Viewport;
+
+/// Draws the pixel buffer texture to an arbitrary rectangle of the surface.
+///
+/// The scaling renderer of `Pixels` only supports integer scaling; this one is used for the
+/// other [`ScalingMode`](crate::viewport::ScalingMode)s. The quad is placed in clip space from
+/// the [`Viewport`], so the drawn area always matches the one used to map input.
+#[derive(Debug)]
+pub(crate) struct ViewportRenderer {
+    vertex_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    clear_color: wgpu::Color,
+}
+
+impl ViewportRenderer {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        texture: &wgpu::Texture,
+        render_texture_format: wgpu::TextureFormat,
+        clear_color: wgpu::Color,
+        viewport: &Viewport,
+    ) -> Self {
+        let module = device.create_shader_module(&wgpu::include_wgsl!("../shaders/viewport.wgsl"));
+
+        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("viewport_renderer_sampler"),
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("viewport_renderer_vertex_buffer"),
+            contents: &vertices(viewport),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+        let vertex_buffer_layout = wgpu::VertexBufferLayout {
+            array_stride: 4 * 4,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2],
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("viewport_renderer_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("viewport_renderer_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("viewport_renderer_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("viewport_renderer_pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: &[vertex_buffer_layout],
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &[wgpu::ColorTargetState {
+                    format: render_texture_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                }],
+            }),
+            multiview: None,
+        });
+
+        Self {
+            vertex_buffer,
+            bind_group,
+            pipeline,
+            clear_color,
+        }
+    }
+
+    /// Move the quad to a new viewport, e.g. after the surface was resized. The quad is kept for
+    /// an empty window, e.g. while minimized.
+    pub(crate) fn set_viewport(&self, queue: &wgpu::Queue, viewport: &Viewport) {
+        if is_empty(viewport) {
+            return;
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, &vertices(viewport));
+    }
+
+    /// Clear `render_target` to the letterbox color and draw the buffer to the viewport.
+    pub(crate) fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        render_target: &wgpu::TextureView,
+    ) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("viewport_renderer_render_pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: render_target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.clear_color),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.draw(0..4, 0..1);
+    }
+}
+
+/// Corners of the viewport as a triangle strip of clip space positions and texture
+/// coordinates, in native endian bytes. The quad collapses to a point, drawing nothing, if the
+/// window is empty.
+fn vertices(viewport: &Viewport) -> Vec<u8> {
+    if is_empty(viewport) {
+        return vec![0; 4 * 4 * 4];
+    }
+    let (window_width, window_height) = (
+        viewport.window_size.width as f64,
+        viewport.window_size.height as f64,
+    );
+    let left = viewport.x / window_width * 2.0 - 1.0;
+    let right = (viewport.x + viewport.width) / window_width * 2.0 - 1.0;
+    let top = 1.0 - viewport.y / window_height * 2.0;
+    let bottom = 1.0 - (viewport.y + viewport.height) / window_height * 2.0;
+
+    let corners = [
+        [left, top, 0.0, 0.0],
+        [right, top, 1.0, 0.0],
+        [left, bottom, 0.0, 1.0],
+        [right, bottom, 1.0, 1.0],
+    ];
+    corners
+        .iter()
+        .flatten()
+        .flat_map(|&value| (value as f32).to_ne_bytes())
+        .collect()
+}
+
+fn is_empty(viewport: &Viewport) -> bool {
+    viewport.window_size.width == 0 || viewport.window_size.height == 0
+}
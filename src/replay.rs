@@ -0,0 +1,573 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, ensure, Context};
+use serde::{Deserialize, Serialize};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::{
+    DeviceId, ElementState, KeyboardInput, ModifiersState, TouchPhase, VirtualKeyCode, WindowEvent,
+};
+
+use crate::app::PixelsApp;
+use crate::config::AppConfig;
+use crate::frame::Frame;
+use crate::gesture::GestureRecognizer;
+use crate::headless::Headless;
+use crate::resize::ResizeEvent;
+use crate::soft_input::KeyboardEvent;
+use crate::text_input::TextInput;
+use crate::touch::TouchTracker;
+use crate::viewport::{ScalingMode, Viewport};
+
+/// Version of the recording format.
+const FORMAT_VERSION: u32 = 2;
+
+/// First line of a recording: the settings a replay has to match to draw the same frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    pub version: u32,
+    /// Size of the pixel buffer the app ran with.
+    pub buffer_size: (u32, u32),
+    pub tick_rate: u32,
+    pub scaling_mode: ScalingMode,
+}
+
+impl RecordingHeader {
+    /// The header of a recording of an app running with `config`.
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            buffer_size: (config.width(), config.height()),
+            tick_rate: config.tick_rate(),
+            scaling_mode: config.scaling_mode(),
+        }
+    }
+}
+
+/// Phase of a recorded touch, mirroring winit's `TouchPhase`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Phase {
+    Started,
+    Moved,
+    Ended,
+    Cancelled,
+}
+
+impl From<TouchPhase> for Phase {
+    fn from(phase: TouchPhase) -> Self {
+        match phase {
+            TouchPhase::Started => Self::Started,
+            TouchPhase::Moved => Self::Moved,
+            TouchPhase::Ended => Self::Ended,
+            TouchPhase::Cancelled => Self::Cancelled,
+        }
+    }
+}
+
+impl From<Phase> for TouchPhase {
+    fn from(phase: Phase) -> Self {
+        match phase {
+            Phase::Started => Self::Started,
+            Phase::Moved => Self::Moved,
+            Phase::Ended => Self::Ended,
+            Phase::Cancelled => Self::Cancelled,
+        }
+    }
+}
+
+/// Input as seen by [`run_app`](crate::app::run_app), in the order it was handed to the app.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RecordedEvent {
+    /// The surface was created for a window of this physical size.
+    Resumed {
+        window_size: (u32, u32),
+    },
+    Suspended,
+    /// The surface was resized to a new window size.
+    Resized {
+        window_size: (u32, u32),
+        scale_factor: f64,
+    },
+    /// A touch at a physical window position.
+    Touch {
+        id: u64,
+        phase: Phase,
+        location: (f64, f64),
+    },
+    /// A hardware key, handed to the app as a [`WindowEvent::KeyboardInput`].
+    Key {
+        virtual_keycode: Option<VirtualKeyCode>,
+        scancode: u32,
+        state: ElementState,
+    },
+    TextInput(TextInput),
+    Keyboard(KeyboardEvent),
+    /// End of the event batch of a frame, followed by `steps` updates.
+    Frame {
+        steps: u32,
+    },
+    /// The app drew into the frame buffer.
+    Draw {
+        alpha: f64,
+    },
+}
+
+/// An event with the time since the recording started.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimedEvent {
+    pub time: Duration,
+    pub event: RecordedEvent,
+}
+
+/// Writes the input of a running app to a file, one JSON object per line.
+///
+/// Lines are buffered; the file is flushed on [`flush`](Self::flush), when the app is suspended
+/// and when the recorder is dropped.
+#[derive(Debug)]
+pub struct InputRecorder {
+    writer: BufWriter<File>,
+    start: Instant,
+}
+
+impl InputRecorder {
+    /// Start a recording of an app running with `config`, replacing the file at `path`.
+    pub fn create(path: impl AsRef<Path>, config: &AppConfig) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file =
+            File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+
+        let mut recorder = Self {
+            writer: BufWriter::new(file),
+            start: Instant::now(),
+        };
+        recorder.write_line(&RecordingHeader::new(config))?;
+        Ok(recorder)
+    }
+
+    /// Append an event that happened at `now`.
+    pub fn record(&mut self, now: Instant, event: RecordedEvent) -> anyhow::Result<()> {
+        let event = TimedEvent {
+            time: now.saturating_duration_since(self.start),
+            event,
+        };
+        self.write_line(&event)
+    }
+
+    /// Write buffered events to the file.
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn write_line(&mut self, value: &impl Serialize) -> anyhow::Result<()> {
+        serde_json::to_writer(&mut self.writer, value)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+/// A recording made by [`InputRecorder`].
+#[derive(Debug, Clone)]
+pub struct Replay {
+    header: RecordingHeader,
+    events: Vec<TimedEvent>,
+}
+
+impl Replay {
+    /// Read a recording file. A truncated last line, e.g. from a crash, is ignored.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        let mut lines = BufReader::new(file).lines();
+
+        let header = lines.next().context("recording is empty")??;
+        let header: RecordingHeader =
+            serde_json::from_str(&header).context("invalid recording header")?;
+        ensure!(
+            header.version == FORMAT_VERSION,
+            "unsupported recording version {}",
+            header.version
+        );
+
+        let lines = lines.collect::<Result<Vec<_>, _>>()?;
+        let mut events = Vec::with_capacity(lines.len());
+        for (i, line) in lines.iter().enumerate() {
+            match serde_json::from_str(line) {
+                Ok(event) => events.push(event),
+                Err(_) if i + 1 == lines.len() => log::warn!("ignoring truncated last event"),
+                Err(e) => {
+                    return Err(e).with_context(|| format!("invalid event on line {}", i + 2))
+                }
+            }
+        }
+
+        Ok(Self { header, events })
+    }
+
+    pub fn header(&self) -> &RecordingHeader {
+        &self.header
+    }
+
+    pub fn events(&self) -> &[TimedEvent] {
+        &self.events
+    }
+
+    /// Run a fresh app through the recorded input without a window and return the frames it
+    /// drew, in order.
+    ///
+    /// Events are handed to the app through the same touch tracker and gesture recognizer as in
+    /// [`run_app`](crate::app::run_app), with the recorded timestamps, and updates and draws
+    /// happen exactly where they did while recording, driven by [`Headless`]. An app that only
+    /// depends on this input therefore draws the same frames. Hardware keys reach
+    /// [`handle_event`](PixelsApp::handle_event) again, other raw window events are not recorded,
+    /// and saved state is not restored.
+    ///
+    /// Fails if `config` differs from the recording in a setting that changes the frames, see
+    /// [`RecordingHeader`].
+    pub fn run<A: PixelsApp>(&self, config: &AppConfig) -> anyhow::Result<Vec<Frame>> {
+        self.check_config(config)?;
+
+        let buffer_size = PhysicalSize::new(config.width(), config.height());
+        let mut headless = Headless::<A>::suspended(config);
+        let mut touches = TouchTracker::default();
+        let mut gestures = GestureRecognizer::new(*config.gesture_config());
+        let mut viewport = Viewport::new(config.scaling_mode(), buffer_size, buffer_size);
+        let mut frames = Vec::new();
+
+        let start = Instant::now();
+        for TimedEvent { time, event } in &self.events {
+            let now = start + *time;
+            let app = headless.app_mut();
+            match event {
+                RecordedEvent::Resumed { window_size } => {
+                    let window_size = PhysicalSize::new(window_size.0, window_size.1);
+                    viewport = Viewport::new(config.scaling_mode(), buffer_size, window_size);
+                    app.viewport_changed(viewport);
+                    app.on_resume();
+                }
+                RecordedEvent::Resized {
+                    window_size,
+                    scale_factor,
+                } => {
+                    let window_size = PhysicalSize::new(window_size.0, window_size.1);
+                    viewport = Viewport::new(config.scaling_mode(), buffer_size, window_size);
+                    app.viewport_changed(viewport);
+                    app.resized(ResizeEvent {
+                        window_size,
+                        buffer_size,
+                        scale_factor: *scale_factor,
+                    });
+                }
+                RecordedEvent::Suspended => {
+                    touches.clear();
+                    gestures.reset();
+                    app.on_suspend();
+                }
+                RecordedEvent::Touch {
+                    id,
+                    phase,
+                    location,
+                } => {
+                    let location = PhysicalPosition::new(location.0, location.1);
+                    touches.handle_touch(*id, (*phase).into(), location, &viewport, now);
+                    let position = viewport.to_buffer_unclamped(location);
+                    gestures.handle_touch(*id, (*phase).into(), position, now);
+                }
+                RecordedEvent::Key {
+                    virtual_keycode,
+                    scancode,
+                    state,
+                } => {
+                    #[allow(deprecated)]
+                    let event = WindowEvent::KeyboardInput {
+                        // SAFETY: the id is only compared, never passed to the platform
+                        device_id: unsafe { DeviceId::dummy() },
+                        input: KeyboardInput {
+                            scancode: *scancode,
+                            state: *state,
+                            virtual_keycode: *virtual_keycode,
+                            modifiers: ModifiersState::empty(),
+                        },
+                        is_synthetic: false,
+                    };
+                    app.handle_event(&event);
+                }
+                RecordedEvent::TextInput(input) => app.text_input(input.clone()),
+                RecordedEvent::Keyboard(event) => app.keyboard_event(*event),
+                RecordedEvent::Frame { steps } => {
+                    app.touches(&touches);
+                    touches.end_frame();
+                    gestures.update(now);
+                    for gesture in gestures.drain() {
+                        app.gesture(gesture);
+                    }
+                    for _ in 0..*steps {
+                        headless.update();
+                    }
+                }
+                RecordedEvent::Draw { alpha } => {
+                    headless.draw(*alpha);
+                    frames.push(headless.frame());
+                }
+            }
+        }
+
+        Ok(frames)
+    }
+
+    fn check_config(&self, config: &AppConfig) -> anyhow::Result<()> {
+        let expected = &self.header;
+        let actual = RecordingHeader::new(config);
+        if actual.buffer_size != expected.buffer_size {
+            bail!(
+                "recording was made with a {}x{} buffer, not {}x{}",
+                expected.buffer_size.0,
+                expected.buffer_size.1,
+                actual.buffer_size.0,
+                actual.buffer_size.1
+            );
+        }
+        if actual.tick_rate != expected.tick_rate {
+            bail!(
+                "recording was made at {} ticks per second, not {}",
+                expected.tick_rate,
+                actual.tick_rate
+            );
+        }
+        if actual.scaling_mode != expected.scaling_mode {
+            bail!(
+                "recording was made with {:?} scaling, not {:?}",
+                expected.scaling_mode,
+                actual.scaling_mode
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Marks pointers in the buffer and keeps counters of ticks and presses of the space key in
+    /// the first pixel.
+    struct Marker {
+        width: u32,
+        ticks: u8,
+        spaces: u8,
+        pointers: Vec<(u32, u32)>,
+    }
+
+    impl PixelsApp for Marker {
+        fn init(config: &AppConfig) -> Self {
+            Self {
+                width: config.width(),
+                ticks: 0,
+                spaces: 0,
+                pointers: Vec::new(),
+            }
+        }
+
+        fn update(&mut self) {
+            self.ticks = self.ticks.wrapping_add(1);
+        }
+
+        fn draw(&self, frame: &mut [u8], _alpha: f64) {
+            frame.fill(0);
+            frame[0] = self.ticks;
+            frame[2] = self.spaces;
+            for &(x, y) in &self.pointers {
+                let i = ((y * self.width + x) * 4) as usize;
+                frame[i + 1] = 0xff;
+            }
+        }
+
+        fn handle_event(&mut self, event: &WindowEvent) {
+            if let WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::Space),
+                        ..
+                    },
+                ..
+            } = event
+            {
+                self.spaces += 1;
+            }
+        }
+
+        fn touches(&mut self, touches: &TouchTracker) {
+            self.pointers = touches
+                .pointers()
+                .iter()
+                .map(|pointer| (pointer.position.0 as u32, pointer.position.1 as u32))
+                .collect();
+        }
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "pixels-android-replay-{}-{}.jsonl",
+            name,
+            std::process::id()
+        ))
+    }
+
+    fn config() -> AppConfig {
+        AppConfig::builder()
+            .buffer_size(8, 8)
+            .window_size(16, 16)
+            .tick_rate(30)
+            .build()
+            .unwrap()
+    }
+
+    fn record(name: &str, config: &AppConfig) -> (std::path::PathBuf, Vec<RecordedEvent>) {
+        let events = vec![
+            RecordedEvent::Resumed {
+                window_size: (16, 16),
+            },
+            RecordedEvent::Frame { steps: 1 },
+            RecordedEvent::Draw { alpha: 0.0 },
+            RecordedEvent::Touch {
+                id: 3,
+                phase: Phase::Started,
+                location: (5.0, 9.0),
+            },
+            RecordedEvent::Key {
+                virtual_keycode: Some(VirtualKeyCode::Space),
+                scancode: 57,
+                state: ElementState::Pressed,
+            },
+            RecordedEvent::Key {
+                virtual_keycode: Some(VirtualKeyCode::Space),
+                scancode: 57,
+                state: ElementState::Released,
+            },
+            RecordedEvent::Key {
+                virtual_keycode: None,
+                scancode: 200,
+                state: ElementState::Pressed,
+            },
+            RecordedEvent::Frame { steps: 2 },
+            RecordedEvent::Draw { alpha: 0.5 },
+            RecordedEvent::Touch {
+                id: 3,
+                phase: Phase::Ended,
+                location: (5.0, 9.0),
+            },
+            RecordedEvent::Frame { steps: 0 },
+            RecordedEvent::Draw { alpha: 0.0 },
+        ];
+
+        let path = temp_path(name);
+        let mut recorder = InputRecorder::create(&path, config).unwrap();
+        let start = Instant::now();
+        for (i, event) in events.iter().enumerate() {
+            let now = start + Duration::from_millis(i as u64 * 10);
+            recorder.record(now, event.clone()).unwrap();
+        }
+        recorder.flush().unwrap();
+        (path, events)
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let config = config();
+        let (path, events) = record("round-trip", &config);
+
+        let replay = Replay::load(&path).unwrap();
+        assert_eq!(*replay.header(), RecordingHeader::new(&config));
+        let loaded: Vec<_> = replay.events().iter().map(|e| e.event.clone()).collect();
+        assert_eq!(loaded, events);
+
+        let frames = replay.run::<Marker>(&config).unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].pixel(0, 0)[0], 1);
+        assert_eq!(frames[0].pixel(0, 0)[2], 0);
+        // Only the press of the space key is counted
+        assert_eq!(frames[1].pixel(0, 0)[2], 1);
+        // The touch at (5, 9) in the 2x window is at (2, 4) in the buffer
+        assert_eq!(frames[1].pixel(0, 0)[0], 3);
+        assert_eq!(frames[1].pixel(2, 4)[1], 0xff);
+        assert_eq!(frames[2].pixel(2, 4)[1], 0);
+
+        // Replaying again draws the same frames
+        assert_eq!(replay.run::<Marker>(&config).unwrap(), frames);
+    }
+
+    #[test]
+    fn truncated_last_line_is_ignored() {
+        let config = config();
+        let (path, events) = record("truncated", &config);
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"{\"time\":{\"secs\":1,").unwrap();
+
+        let replay = Replay::load(&path).unwrap();
+        assert_eq!(replay.events().len(), events.len());
+    }
+
+    #[test]
+    fn mismatched_config_is_rejected() {
+        let (path, _) = record("mismatch", &config());
+        let replay = Replay::load(&path).unwrap();
+
+        let mismatches = [
+            (
+                AppConfig::builder()
+                    .buffer_size(16, 8)
+                    .window_size(16, 16)
+                    .tick_rate(30),
+                "buffer",
+            ),
+            (
+                AppConfig::builder()
+                    .buffer_size(8, 8)
+                    .window_size(16, 16)
+                    .tick_rate(60),
+                "ticks per second",
+            ),
+            (
+                AppConfig::builder()
+                    .buffer_size(8, 8)
+                    .window_size(16, 16)
+                    .tick_rate(30)
+                    .scaling_mode(ScalingMode::Stretch),
+                "scaling",
+            ),
+        ];
+        for (builder, message) in mismatches {
+            let error = replay
+                .run::<Marker>(&builder.build().unwrap())
+                .unwrap_err()
+                .to_string();
+            assert!(error.contains(message), "{}", error);
+        }
+    }
+
+    #[test]
+    fn window_size_may_differ() {
+        let (path, _) = record("window", &config());
+        let replay = Replay::load(&path).unwrap();
+
+        // The window size comes from the recorded events, not the configuration
+        let config = AppConfig::builder()
+            .buffer_size(8, 8)
+            .window_size(24, 16)
+            .tick_rate(30)
+            .build()
+            .unwrap();
+        let frames = replay.run::<Marker>(&config).unwrap();
+        assert_eq!(frames[1].pixel(2, 4)[1], 0xff);
+    }
+}
@@ -0,0 +1,174 @@
+use std::time::{Duration, Instant};
+
+use winit::dpi::PhysicalSize;
+use winit::event::WindowEvent;
+
+/// The surface was resized, e.g. after the device was rotated or the window was dragged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResizeEvent {
+    /// New inner size of the window in physical pixels.
+    pub window_size: PhysicalSize<u32>,
+    /// Size of the pixel buffer, which stays the same.
+    pub buffer_size: PhysicalSize<u32>,
+    /// DPI scale factor of the window.
+    pub scale_factor: f64,
+}
+
+/// Coalesces bursts of resize events into a single surface resize.
+///
+/// Dragging a window edge reports a new size on every mouse move, and rotating an Android device
+/// can report an intermediate size before the final one. Reconfiguring the surface for each of
+/// them is wasted work, so a size is only applied once it has been stable for the debounce delay.
+#[derive(Debug, Clone)]
+pub struct ResizeDebouncer {
+    delay: Duration,
+    pending: Option<(PhysicalSize<u32>, Instant)>,
+}
+
+impl Default for ResizeDebouncer {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(100))
+    }
+}
+
+impl ResizeDebouncer {
+    /// Apply sizes after they were stable for `delay`. A zero delay applies them on the next
+    /// [`update`](Self::update).
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            pending: None,
+        }
+    }
+
+    /// Feed a window event. Events other than resizes and scale factor changes are ignored.
+    pub fn handle_window_event(&mut self, event: &WindowEvent, now: Instant) {
+        match event {
+            WindowEvent::Resized(size) => self.handle_resize(*size, now),
+            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                self.handle_resize(**new_inner_size, now)
+            }
+            _ => (),
+        }
+    }
+
+    /// Feed a new window size.
+    pub fn handle_resize(&mut self, size: PhysicalSize<u32>, now: Instant) {
+        self.pending = Some((size, now));
+    }
+
+    /// When the pending size is due, if there is one.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.map(|(_, time)| time + self.delay)
+    }
+
+    /// The size to resize the surface to, once it has been stable for the debounce delay.
+    ///
+    /// An empty size, reported while the window is minimized, is dropped so the surface is kept
+    /// until the window is restored.
+    pub fn update(&mut self, now: Instant) -> Option<PhysicalSize<u32>> {
+        match self.deadline() {
+            Some(deadline) if now >= deadline => {
+                let (size, _) = self.pending.take()?;
+                if size.width == 0 || size.height == 0 {
+                    log::debug!("ignoring resize to {}x{}", size.width, size.height);
+                    return None;
+                }
+                Some(size)
+            }
+            _ => None,
+        }
+    }
+
+    /// Drop the pending size, e.g. when the surface is recreated anyway.
+    pub fn reset(&mut self) {
+        self.pending = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn burst_collapses_to_the_last_size() {
+        let start = Instant::now();
+        let mut debouncer = ResizeDebouncer::new(ms(100));
+        assert_eq!(debouncer.deadline(), None);
+
+        debouncer.handle_resize(PhysicalSize::new(100, 200), start);
+        assert_eq!(debouncer.deadline(), Some(start + ms(100)));
+        debouncer.handle_resize(PhysicalSize::new(150, 200), start + ms(50));
+        debouncer.handle_resize(PhysicalSize::new(200, 100), start + ms(80));
+        // The deadline moves with every new size
+        assert_eq!(debouncer.deadline(), Some(start + ms(180)));
+
+        assert_eq!(debouncer.update(start + ms(120)), None);
+        assert_eq!(debouncer.update(start + ms(179)), None);
+        assert_eq!(
+            debouncer.update(start + ms(180)),
+            Some(PhysicalSize::new(200, 100))
+        );
+        // Applied only once
+        assert_eq!(debouncer.update(start + ms(500)), None);
+        assert_eq!(debouncer.deadline(), None);
+    }
+
+    #[test]
+    fn zero_delay() {
+        let now = Instant::now();
+        let mut debouncer = ResizeDebouncer::new(Duration::ZERO);
+        debouncer.handle_resize(PhysicalSize::new(320, 240), now);
+        assert_eq!(debouncer.update(now), Some(PhysicalSize::new(320, 240)));
+    }
+
+    #[test]
+    fn window_events() {
+        let now = Instant::now();
+        let mut debouncer = ResizeDebouncer::new(Duration::ZERO);
+
+        debouncer.handle_window_event(&WindowEvent::Resized(PhysicalSize::new(640, 480)), now);
+        assert_eq!(debouncer.update(now), Some(PhysicalSize::new(640, 480)));
+
+        let mut new_inner_size = PhysicalSize::new(1280, 960);
+        let event = WindowEvent::ScaleFactorChanged {
+            scale_factor: 2.0,
+            new_inner_size: &mut new_inner_size,
+        };
+        debouncer.handle_window_event(&event, now);
+        assert_eq!(debouncer.update(now), Some(PhysicalSize::new(1280, 960)));
+
+        debouncer.handle_window_event(&WindowEvent::Focused(true), now);
+        assert_eq!(debouncer.deadline(), None);
+    }
+
+    #[test]
+    fn reset_drops_the_pending_size() {
+        let now = Instant::now();
+        let mut debouncer = ResizeDebouncer::new(ms(10));
+        debouncer.handle_resize(PhysicalSize::new(320, 240), now);
+        debouncer.reset();
+        assert_eq!(debouncer.deadline(), None);
+        assert_eq!(debouncer.update(now + ms(10)), None);
+    }
+
+    #[test]
+    fn minimized_window_is_ignored() {
+        let now = Instant::now();
+        let mut debouncer = ResizeDebouncer::new(Duration::ZERO);
+        debouncer.handle_resize(PhysicalSize::new(0, 0), now);
+        assert_eq!(debouncer.update(now), None);
+        assert_eq!(debouncer.deadline(), None);
+
+        debouncer.handle_resize(PhysicalSize::new(640, 0), now);
+        assert_eq!(debouncer.update(now), None);
+
+        // Restoring the window reports the size again
+        debouncer.handle_resize(PhysicalSize::new(640, 480), now);
+        assert_eq!(debouncer.update(now), Some(PhysicalSize::new(640, 480)));
+    }
+}
@@ -0,0 +1,225 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::{ensure, Context};
+
+/// Marks a file written by [`StateStore`].
+const MAGIC: &[u8; 4] = b"PXST";
+/// Size of the magic, version, length and checksum in front of the data.
+const HEADER_LEN: usize = 4 + 4 + 8 + 4;
+
+/// App state read back by [`StateStore::load`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedState {
+    /// Version passed to [`StateStore::save`], to detect state written by older releases.
+    pub version: u32,
+    pub data: Vec<u8>,
+}
+
+/// Persists an opaque blob of app state in a single file.
+///
+/// On Android the file lives in the app's internal storage, which survives the process being
+/// killed while suspended. Writes go to a temporary file first, so a crash while saving leaves
+/// the previous state intact; a checksum catches files that were cut short anyway.
+#[derive(Debug, Clone)]
+pub struct StateStore {
+    path: PathBuf,
+}
+
+impl StateStore {
+    /// File name of the state in its directory.
+    const FILE_NAME: &'static str = "state.bin";
+
+    /// The internal storage of the app on Android, `dir` elsewhere.
+    #[cfg(target_os = "android")]
+    pub fn for_platform(_dir: impl Into<PathBuf>) -> Self {
+        Self::in_dir(ndk_glue::native_activity().internal_data_path())
+    }
+
+    /// The internal storage of the app on Android, `dir` elsewhere.
+    #[cfg(not(target_os = "android"))]
+    pub fn for_platform(dir: impl Into<PathBuf>) -> Self {
+        Self::in_dir(dir)
+    }
+
+    /// Store the state in `dir`, on every platform.
+    pub fn in_dir(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            path: dir.into().join(Self::FILE_NAME),
+        }
+    }
+
+    /// Path of the state file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Replace the saved state, creating the directory as needed.
+    pub fn save(&self, version: u32, data: &[u8]) -> anyhow::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+
+        let mut file = Vec::with_capacity(HEADER_LEN + data.len());
+        file.extend_from_slice(MAGIC);
+        file.extend_from_slice(&version.to_le_bytes());
+        file.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        file.extend_from_slice(&checksum(data).to_le_bytes());
+        file.extend_from_slice(data);
+
+        let temp = self.path.with_extension("tmp");
+        fs::write(&temp, &file).with_context(|| format!("failed to write {}", temp.display()))?;
+        fs::rename(&temp, &self.path)
+            .with_context(|| format!("failed to replace {}", self.path.display()))
+    }
+
+    /// Read the saved state. Returns `None` if nothing was saved yet.
+    pub fn load(&self) -> anyhow::Result<Option<SavedState>> {
+        let file = match fs::read(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to read {}", self.path.display()))
+            }
+        };
+
+        ensure!(
+            file.len() >= HEADER_LEN && file.starts_with(MAGIC),
+            "{} is not a saved state",
+            self.path.display()
+        );
+        let version = u32::from_le_bytes(file[4..8].try_into()?);
+        let len = u64::from_le_bytes(file[8..16].try_into()?);
+        let sum = u32::from_le_bytes(file[16..20].try_into()?);
+        let data = &file[HEADER_LEN..];
+        ensure!(
+            data.len() as u64 == len && checksum(data) == sum,
+            "saved state in {} is corrupt",
+            self.path.display()
+        );
+
+        Ok(Some(SavedState {
+            version,
+            data: data.to_vec(),
+        }))
+    }
+
+    /// Delete the saved state, if any.
+    pub fn clear(&self) -> anyhow::Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("failed to delete {}", self.path.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 32-bit FNV-1a hash of `data`.
+fn checksum(data: &[u8]) -> u32 {
+    data.iter().fold(0x811c_9dc5, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A store in a directory that does not exist yet.
+    fn store(name: &str) -> StateStore {
+        let dir = std::env::temp_dir().join(format!(
+            "pixels-android-state-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        StateStore::in_dir(dir.join("saves"))
+    }
+
+    #[test]
+    fn round_trip() {
+        let store = store("round-trip");
+        assert_eq!(store.load().unwrap(), None);
+
+        store.save(3, b"hello").unwrap();
+        assert_eq!(
+            store.load().unwrap(),
+            Some(SavedState {
+                version: 3,
+                data: b"hello".to_vec(),
+            })
+        );
+
+        // Saving again replaces the state and leaves no temporary file behind
+        store.save(4, b"").unwrap();
+        assert_eq!(
+            store.load().unwrap(),
+            Some(SavedState {
+                version: 4,
+                data: Vec::new(),
+            })
+        );
+        assert!(!store.path().with_extension("tmp").exists());
+    }
+
+    #[test]
+    fn replaces_a_stale_temporary_file() {
+        let store = store("stale-temp");
+        store.save(1, b"old").unwrap();
+        // Left over from a crash while saving
+        fs::write(store.path().with_extension("tmp"), b"partial").unwrap();
+
+        assert_eq!(store.load().unwrap().unwrap().data, b"old");
+        store.save(2, b"new").unwrap();
+        assert_eq!(store.load().unwrap().unwrap().data, b"new");
+        assert!(!store.path().with_extension("tmp").exists());
+    }
+
+    #[test]
+    fn rejects_damaged_files() {
+        let store = store("damaged");
+        store.save(1, b"some state").unwrap();
+        let file = fs::read(store.path()).unwrap();
+
+        let truncated = &file[..file.len() - 1];
+        let mut bad_magic = file.clone();
+        bad_magic[0] = b'X';
+        let mut flipped = file.clone();
+        *flipped.last_mut().unwrap() ^= 0x01;
+        let mut wrong_len = file.clone();
+        wrong_len[8] += 1;
+
+        for damaged in [
+            truncated,
+            &file[..HEADER_LEN - 1],
+            &bad_magic,
+            &flipped,
+            &wrong_len,
+        ] {
+            fs::write(store.path(), damaged).unwrap();
+            assert!(store.load().is_err());
+        }
+    }
+
+    #[test]
+    fn clear() {
+        let store = store("clear");
+        // Nothing to delete yet
+        store.clear().unwrap();
+
+        store.save(1, b"state").unwrap();
+        store.clear().unwrap();
+        assert!(!store.path().exists());
+        assert_eq!(store.load().unwrap(), None);
+    }
+
+    #[test]
+    fn checksum_is_fnv1a() {
+        assert_eq!(checksum(b""), 0x811c_9dc5);
+        assert_eq!(checksum(b"a"), 0xe40c_292c);
+        assert_eq!(checksum(b"foobar"), 0xbf9c_f968);
+    }
+}
@@ -0,0 +1,255 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+use crate::frame::Frame;
+use crate::gesture::{Gesture, SwipeDirection};
+
+/// Input that makes [`run_app`](crate::app::run_app) take a screenshot, see
+/// [`AppConfigBuilder::screenshot_trigger`](crate::config::AppConfigBuilder::screenshot_trigger).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenshotTrigger {
+    /// A key was pressed.
+    Key(VirtualKeyCode),
+    /// A [`Gesture::DoubleTap`].
+    DoubleTap,
+    /// A [`Gesture::LongPress`].
+    LongPress,
+    /// A [`Gesture::Swipe`] in the given direction.
+    Swipe(SwipeDirection),
+}
+
+impl ScreenshotTrigger {
+    /// Whether `event` is a press of the trigger key.
+    pub fn matches_event(&self, event: &WindowEvent) -> bool {
+        match (self, event) {
+            (
+                Self::Key(key),
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(pressed),
+                            ..
+                        },
+                    ..
+                },
+            ) => key == pressed,
+            _ => false,
+        }
+    }
+
+    /// Whether `gesture` is the trigger gesture.
+    pub fn matches_gesture(&self, gesture: &Gesture) -> bool {
+        match (self, gesture) {
+            (Self::DoubleTap, Gesture::DoubleTap { .. })
+            | (Self::LongPress, Gesture::LongPress { .. }) => true,
+            (Self::Swipe(expected), Gesture::Swipe { direction, .. }) => expected == direction,
+            _ => false,
+        }
+    }
+}
+
+/// Saves frames as PNG files named after the time they were taken.
+///
+/// On Android the files go to the app's external storage directory
+/// (`Android/data/<package>/files`), which can be read over USB without root. Elsewhere they go
+/// to a directory, `screenshots` in the working directory by default.
+#[derive(Debug, Clone)]
+pub struct Screenshots {
+    dir: PathBuf,
+    scale: u32,
+}
+
+impl Screenshots {
+    /// The external storage of the app on Android, `dir` elsewhere.
+    #[cfg(target_os = "android")]
+    pub fn for_platform(_dir: impl Into<PathBuf>) -> Self {
+        Self::in_dir(ndk_glue::native_activity().external_data_path())
+    }
+
+    /// The external storage of the app on Android, `dir` elsewhere.
+    #[cfg(not(target_os = "android"))]
+    pub fn for_platform(dir: impl Into<PathBuf>) -> Self {
+        Self::in_dir(dir)
+    }
+
+    /// Save screenshots in `dir`, on every platform.
+    pub fn in_dir(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            scale: 1,
+        }
+    }
+
+    /// Scale screenshots up by an integer factor. Defaults to 1, the buffer resolution.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `scale` is 0.
+    pub fn with_scale(mut self, scale: u32) -> Self {
+        assert!(scale > 0, "screenshot scale must be positive");
+        self.scale = scale;
+        self
+    }
+
+    /// Directory the screenshots are saved in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Factor screenshots are scaled up by.
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// Save `frame` scaled up by [`scale`](Self::scale) to a new file named after the current
+    /// time, e.g. `screenshot-1700000000123.png`. Returns the path of the file.
+    pub fn save(&self, frame: &Frame) -> anyhow::Result<PathBuf> {
+        let path = timestamped_path(&self.dir, "screenshot", "png");
+        frame.scaled(self.scale).save_png(&path)?;
+        Ok(path)
+    }
+}
+
+/// A path in `dir` that does not exist yet, named `<prefix>-<unix time in ms>.<extension>`.
+pub(crate) fn timestamped_path(dir: &Path, prefix: &str, extension: &str) -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis())
+        .unwrap_or(0);
+    unique_path(dir, &format!("{}-{}", prefix, millis), extension)
+}
+
+/// `<dir>/<stem>.<extension>`, or `<stem>-1`, `<stem>-2`, ... if that exists already.
+fn unique_path(dir: &Path, stem: &str, extension: &str) -> PathBuf {
+    let mut path = dir.join(format!("{}.{}", stem, extension));
+    let mut n = 1;
+    while path.exists() {
+        path = dir.join(format!("{}-{}.{}", stem, n, extension));
+        n += 1;
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::event::{DeviceId, ModifiersState};
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "pixels-android-screenshot-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn key(state: ElementState, key: VirtualKeyCode) -> WindowEvent<'static> {
+        #[allow(deprecated)]
+        WindowEvent::KeyboardInput {
+            // SAFETY: the id is only compared, never passed to the platform
+            device_id: unsafe { DeviceId::dummy() },
+            input: KeyboardInput {
+                scancode: 0,
+                state,
+                virtual_keycode: Some(key),
+                modifiers: ModifiersState::empty(),
+            },
+            is_synthetic: false,
+        }
+    }
+
+    fn swipe(direction: SwipeDirection) -> Gesture {
+        Gesture::Swipe {
+            start: (0.0, 0.0),
+            end: (10.0, 0.0),
+            direction,
+            velocity: (100.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn key_trigger() {
+        let trigger = ScreenshotTrigger::Key(VirtualKeyCode::F12);
+        assert!(trigger.matches_event(&key(ElementState::Pressed, VirtualKeyCode::F12)));
+        assert!(!trigger.matches_event(&key(ElementState::Released, VirtualKeyCode::F12)));
+        assert!(!trigger.matches_event(&key(ElementState::Pressed, VirtualKeyCode::F11)));
+        assert!(!trigger.matches_event(&WindowEvent::Focused(true)));
+        assert!(!trigger.matches_gesture(&Gesture::DoubleTap {
+            position: (0.0, 0.0)
+        }));
+    }
+
+    #[test]
+    fn gesture_triggers() {
+        let trigger = ScreenshotTrigger::Swipe(SwipeDirection::Down);
+        assert!(trigger.matches_gesture(&swipe(SwipeDirection::Down)));
+        assert!(!trigger.matches_gesture(&swipe(SwipeDirection::Up)));
+        assert!(!trigger.matches_event(&key(ElementState::Pressed, VirtualKeyCode::Down)));
+
+        let double_tap = Gesture::DoubleTap {
+            position: (1.0, 2.0),
+        };
+        let long_press = Gesture::LongPress {
+            position: (1.0, 2.0),
+        };
+        assert!(ScreenshotTrigger::DoubleTap.matches_gesture(&double_tap));
+        assert!(!ScreenshotTrigger::DoubleTap.matches_gesture(&long_press));
+        assert!(ScreenshotTrigger::LongPress.matches_gesture(&long_press));
+        assert!(
+            !ScreenshotTrigger::LongPress.matches_gesture(&Gesture::Tap {
+                position: (1.0, 2.0)
+            })
+        );
+    }
+
+    #[test]
+    fn unique_paths() {
+        let dir = temp_dir("unique");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let first = unique_path(&dir, "shot-5", "png");
+        assert_eq!(first, dir.join("shot-5.png"));
+        std::fs::write(&first, b"").unwrap();
+        let second = unique_path(&dir, "shot-5", "png");
+        assert_eq!(second, dir.join("shot-5-1.png"));
+        std::fs::write(&second, b"").unwrap();
+        assert_eq!(unique_path(&dir, "shot-5", "png"), dir.join("shot-5-2.png"));
+
+        let path = timestamped_path(&dir, "screenshot", "png");
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert!(
+            name.starts_with("screenshot-") && name.ends_with(".png"),
+            "{}",
+            name
+        );
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn save_scaled() {
+        let dir = temp_dir("save").join("nested");
+        let frame = Frame {
+            width: 2,
+            height: 1,
+            data: vec![1, 2, 3, 4, 5, 6, 7, 8],
+        };
+        let screenshots = Screenshots::in_dir(&dir).with_scale(3);
+
+        let path = screenshots.save(&frame).unwrap();
+        assert_eq!(path.parent(), Some(dir.as_path()));
+        let saved = Frame::load_png(&path).unwrap();
+        assert_eq!(saved, frame.scaled(3));
+        assert_eq!((saved.width, saved.height), (6, 3));
+
+        // A second screenshot doesn't overwrite the first
+        let other = screenshots.save(&frame).unwrap();
+        assert_ne!(other, path);
+        assert!(path.exists() && other.exists());
+    }
+}
@@ -0,0 +1,321 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::bail;
+
+use crate::app::PixelsApp;
+use crate::config::AppConfig;
+use crate::frame::Frame;
+use crate::headless::Headless;
+
+/// Environment variable that makes [`Snapshot::check`] (re)write golden files instead of failing.
+pub const UPDATE_ENV: &str = "PIXELS_UPDATE_SNAPSHOTS";
+
+/// Compares frames against golden PNG files.
+///
+/// A golden file `<dir>/<name>.png` is compared channel by channel. On a mismatch the actual
+/// frame is written to `<diff dir>/<name>.actual.png` and a diff image to
+/// `<diff dir>/<name>.diff.png`, where mismatching pixels are red and matching pixels are a
+/// dimmed grayscale copy of the golden frame.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    dir: PathBuf,
+    diff_dir: Option<PathBuf>,
+    tolerance: u8,
+    update: bool,
+}
+
+/// Result of comparing two frames of the same size.
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    /// Number of pixels with at least one channel differing by more than the tolerance.
+    pub mismatched: usize,
+    /// Largest per-channel difference found.
+    pub max_difference: u8,
+    /// Visualization of the mismatching pixels.
+    pub diff: Frame,
+}
+
+impl Snapshot {
+    /// Compare against golden files in `dir` with zero tolerance.
+    ///
+    /// Golden files are written instead of compared when [`UPDATE_ENV`] is set.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            diff_dir: None,
+            tolerance: 0,
+            update: std::env::var_os(UPDATE_ENV).is_some(),
+        }
+    }
+
+    /// Maximum allowed difference per color channel.
+    pub fn tolerance(mut self, tolerance: u8) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Directory for the actual and diff images of failed comparisons.
+    ///
+    /// Defaults to the golden file directory.
+    pub fn diff_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.diff_dir = Some(dir.into());
+        self
+    }
+
+    /// Write golden files instead of comparing against them.
+    pub fn update(mut self, update: bool) -> Self {
+        self.update = update;
+        self
+    }
+
+    /// Path of the golden file for `name`.
+    pub fn golden_path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.png", name))
+    }
+
+    /// Compare `frame` against the golden file for `name`.
+    pub fn check(&self, name: &str, frame: &Frame) -> anyhow::Result<()> {
+        let golden_path = self.golden_path(name);
+        if self.update {
+            log::info!("updating snapshot {}", golden_path.display());
+            return frame.save_png(&golden_path);
+        }
+        if !golden_path.exists() {
+            bail!(
+                "missing golden file {}, set {} to create it",
+                golden_path.display(),
+                UPDATE_ENV
+            );
+        }
+
+        let golden = Frame::load_png(&golden_path)?;
+        if (golden.width, golden.height) != (frame.width, frame.height) {
+            self.write_failure(name, frame, None)?;
+            bail!(
+                "snapshot {} is {}x{}, but the frame is {}x{}",
+                name,
+                golden.width,
+                golden.height,
+                frame.width,
+                frame.height
+            );
+        }
+
+        let comparison = compare(&golden, frame, self.tolerance);
+        if comparison.mismatched > 0 {
+            let diff_path = self.write_failure(name, frame, Some(&comparison.diff))?;
+            bail!(
+                "snapshot {} differs in {} pixels (max channel difference {}, tolerance {}), see {}",
+                name,
+                comparison.mismatched,
+                comparison.max_difference,
+                self.tolerance,
+                diff_path.display()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Run `A` headlessly until `frame_count` frames were drawn and check the last frame.
+    pub fn check_app<A: PixelsApp>(
+        &self,
+        name: &str,
+        config: &AppConfig,
+        frame_count: u64,
+    ) -> anyhow::Result<()> {
+        let frame = Headless::<A>::new(config).render_frame(frame_count);
+        self.check(name, &frame)
+    }
+
+    fn write_failure(
+        &self,
+        name: &str,
+        actual: &Frame,
+        diff: Option<&Frame>,
+    ) -> anyhow::Result<PathBuf> {
+        let dir: &Path = self.diff_dir.as_deref().unwrap_or(&self.dir);
+        let actual_path = dir.join(format!("{}.actual.png", name));
+        actual.save_png(&actual_path)?;
+
+        match diff {
+            Some(diff) => {
+                let diff_path = dir.join(format!("{}.diff.png", name));
+                diff.save_png(&diff_path)?;
+                Ok(diff_path)
+            }
+            None => Ok(actual_path),
+        }
+    }
+}
+
+/// Compare two frames of equal size channel by channel.
+///
+/// # Panics
+///
+/// Panics if the frames differ in size.
+pub fn compare(expected: &Frame, actual: &Frame, tolerance: u8) -> Comparison {
+    assert_eq!(
+        (expected.width, expected.height),
+        (actual.width, actual.height),
+        "frames differ in size"
+    );
+
+    let mut mismatched = 0;
+    let mut max_difference = 0;
+    let mut diff = vec![0; expected.data.len()];
+
+    for ((expected, actual), diff) in expected
+        .data
+        .chunks_exact(4)
+        .zip(actual.data.chunks_exact(4))
+        .zip(diff.chunks_exact_mut(4))
+    {
+        let difference = expected
+            .iter()
+            .zip(actual)
+            .map(|(e, a)| e.abs_diff(*a))
+            .max()
+            .unwrap_or(0);
+        max_difference = max_difference.max(difference);
+
+        let rgba = if difference > tolerance {
+            mismatched += 1;
+            [0xff, 0x00, 0x00, 0xff]
+        } else {
+            let luma = (expected[0] as u32 * 3 + expected[1] as u32 * 6 + expected[2] as u32) / 10;
+            let dimmed = (luma / 3) as u8;
+            [dimmed, dimmed, dimmed, 0xff]
+        };
+        diff.copy_from_slice(&rgba);
+    }
+
+    Comparison {
+        mismatched,
+        max_difference,
+        diff: Frame {
+            width: expected.width,
+            height: expected.height,
+            data: diff,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory for the files of one test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "pixels-android-snapshot-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn frame(pixels: &[[u8; 4]]) -> Frame {
+        Frame {
+            width: pixels.len() as u32,
+            height: 1,
+            data: pixels.concat(),
+        }
+    }
+
+    #[test]
+    fn compare_counts_mismatches() {
+        let expected = frame(&[[0, 0, 0, 0xff], [100, 100, 100, 0xff], [30, 60, 90, 0xff]]);
+        let actual = frame(&[[0, 0, 0, 0xff], [102, 100, 100, 0xff], [30, 60, 95, 0xff]]);
+
+        let comparison = compare(&expected, &actual, 2);
+        assert_eq!(comparison.mismatched, 1);
+        assert_eq!(comparison.max_difference, 5);
+        // Matching pixels are dimmed grayscale, mismatches red
+        assert_eq!(
+            comparison.diff,
+            frame(&[[0, 0, 0, 0xff], [33, 33, 33, 0xff], [0xff, 0, 0, 0xff]])
+        );
+
+        assert_eq!(compare(&expected, &actual, 5).mismatched, 0);
+    }
+
+    #[test]
+    fn within_tolerance() {
+        let dir = temp_dir("within");
+        let golden = frame(&[[10, 20, 30, 0xff], [40, 50, 60, 0xff]]);
+        let snapshot = Snapshot::new(&dir).update(false).tolerance(3);
+        golden.save_png(snapshot.golden_path("small")).unwrap();
+
+        let actual = frame(&[[13, 20, 30, 0xff], [40, 47, 60, 0xff]]);
+        snapshot.check("small", &actual).unwrap();
+        assert!(!dir.join("small.actual.png").exists());
+        assert!(!dir.join("small.diff.png").exists());
+    }
+
+    #[test]
+    fn outside_tolerance_writes_diff() {
+        let dir = temp_dir("outside");
+        let diff_dir = dir.join("diffs");
+        let golden = frame(&[[10, 20, 30, 0xff], [40, 50, 60, 0xff]]);
+        let snapshot = Snapshot::new(&dir)
+            .update(false)
+            .tolerance(3)
+            .diff_dir(&diff_dir);
+        golden.save_png(snapshot.golden_path("small")).unwrap();
+
+        let actual = frame(&[[14, 20, 30, 0xff], [40, 50, 60, 0xff]]);
+        let error = snapshot.check("small", &actual).unwrap_err().to_string();
+        assert!(error.contains("differs in 1 pixels"), "{}", error);
+
+        assert_eq!(
+            Frame::load_png(diff_dir.join("small.actual.png")).unwrap(),
+            actual
+        );
+        let diff = Frame::load_png(diff_dir.join("small.diff.png")).unwrap();
+        assert_eq!(diff.pixel(0, 0), [0xff, 0, 0, 0xff]);
+        assert_ne!(diff.pixel(1, 0), [0xff, 0, 0, 0xff]);
+        // The golden file is left alone
+        assert_eq!(
+            Frame::load_png(snapshot.golden_path("small")).unwrap(),
+            golden
+        );
+    }
+
+    #[test]
+    fn size_mismatch_writes_actual_only() {
+        let dir = temp_dir("size");
+        let snapshot = Snapshot::new(&dir).update(false);
+        frame(&[[0, 0, 0, 0xff]])
+            .save_png(snapshot.golden_path("small"))
+            .unwrap();
+
+        let actual = frame(&[[0, 0, 0, 0xff], [0, 0, 0, 0xff]]);
+        assert!(snapshot.check("small", &actual).is_err());
+        assert!(dir.join("small.actual.png").exists());
+        assert!(!dir.join("small.diff.png").exists());
+    }
+
+    #[test]
+    fn missing_golden_and_update() {
+        let dir = temp_dir("update");
+        let actual = frame(&[[1, 2, 3, 0xff]]);
+
+        let error = Snapshot::new(&dir)
+            .update(false)
+            .check("new", &actual)
+            .unwrap_err();
+        assert!(error.to_string().contains(UPDATE_ENV), "{}", error);
+
+        Snapshot::new(&dir)
+            .update(true)
+            .check("new", &actual)
+            .unwrap();
+        Snapshot::new(&dir)
+            .update(false)
+            .check("new", &actual)
+            .unwrap();
+    }
+}
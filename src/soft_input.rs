@@ -0,0 +1,443 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use winit::event::TouchPhase;
+
+use crate::ime::ImeOptions;
+
+#[cfg(target_os = "android")]
+mod android;
+
+#[cfg(target_os = "android")]
+pub use android::{show_soft_input, soft_input_frame, AndroidSoftKeyboard};
+
+/// Errors raised while showing or hiding the Android soft keyboard.
+#[derive(Debug)]
+pub enum SoftInputError {
+    /// The Java VM could not be obtained or the current thread could not be attached to it.
+    Attach(jni::errors::Error),
+    /// `getSystemService` returned no input method manager.
+    MissingService,
+    /// A Java method threw. The exception has been cleared.
+    JavaException(String),
+    /// The decor view has no window token, e.g. because it isn't attached to a window.
+    NullWindowToken,
+    /// Any other JNI failure, like a missing class or method.
+    Jni(jni::errors::Error),
+}
+
+impl fmt::Display for SoftInputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Attach(e) => write!(f, "failed to attach to the Java VM: {}", e),
+            Self::MissingService => write!(f, "input method service is not available"),
+            Self::JavaException(message) => write!(f, "Java exception: {}", message),
+            Self::NullWindowToken => write!(f, "decor view has no window token"),
+            Self::Jni(e) => write!(f, "JNI call failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SoftInputError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Attach(e) | Self::Jni(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Area at the bottom of the window hidden by the soft keyboard, in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyboardFrame {
+    /// Height of the hidden area.
+    pub height: u32,
+    /// Height of the whole window.
+    pub window_height: u32,
+}
+
+/// Platform service that shows and hides the on-screen keyboard.
+pub trait SoftKeyboard {
+    /// Request the keyboard to be shown or hidden. `options` only apply when showing it.
+    ///
+    /// Returns whether the platform accepted the request.
+    fn set_visible(&mut self, visible: bool, options: &ImeOptions) -> Result<bool, SoftInputError>;
+
+    /// Measure the area currently hidden by the keyboard.
+    ///
+    /// Returns `None` if the platform can't tell.
+    fn frame(&mut self) -> Result<Option<KeyboardFrame>, SoftInputError> {
+        Ok(None)
+    }
+}
+
+/// Soft keyboard for platforms without one. Every request is ignored and reported as rejected.
+#[derive(Debug, Default)]
+pub struct NoopSoftKeyboard;
+
+impl SoftKeyboard for NoopSoftKeyboard {
+    fn set_visible(
+        &mut self,
+        _visible: bool,
+        _options: &ImeOptions,
+    ) -> Result<bool, SoftInputError> {
+        Ok(false)
+    }
+}
+
+/// Soft keyboard that records every request, for tests.
+#[derive(Debug)]
+pub struct RecordingSoftKeyboard {
+    requests: Vec<(bool, ImeOptions)>,
+    accept: bool,
+    frame: Option<KeyboardFrame>,
+}
+
+impl Default for RecordingSoftKeyboard {
+    fn default() -> Self {
+        Self {
+            requests: Vec::new(),
+            accept: true,
+            frame: None,
+        }
+    }
+}
+
+impl RecordingSoftKeyboard {
+    /// Set the result reported for subsequent requests.
+    pub fn set_accept(&mut self, accept: bool) {
+        self.accept = accept;
+    }
+
+    /// Set the frame reported by [`SoftKeyboard::frame`], `None` to report nothing.
+    pub fn set_frame(&mut self, frame: Option<KeyboardFrame>) {
+        self.frame = frame;
+    }
+
+    /// The requested visibilities and options, oldest first.
+    pub fn requests(&self) -> &[(bool, ImeOptions)] {
+        &self.requests
+    }
+}
+
+impl SoftKeyboard for RecordingSoftKeyboard {
+    fn set_visible(&mut self, visible: bool, options: &ImeOptions) -> Result<bool, SoftInputError> {
+        self.requests.push((visible, *options));
+        Ok(self.accept)
+    }
+
+    fn frame(&mut self) -> Result<Option<KeyboardFrame>, SoftInputError> {
+        Ok(self.frame)
+    }
+}
+
+/// The soft keyboard of the current platform.
+#[cfg(target_os = "android")]
+pub fn platform_soft_keyboard() -> Box<dyn SoftKeyboard> {
+    Box::new(AndroidSoftKeyboard)
+}
+
+/// The soft keyboard of the current platform.
+#[cfg(not(target_os = "android"))]
+pub fn platform_soft_keyboard() -> Box<dyn SoftKeyboard> {
+    Box::new(NoopSoftKeyboard)
+}
+
+/// Change of the soft keyboard's visibility, as tracked by [`KeyboardState`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyboardEvent {
+    /// The keyboard appeared, hiding `height` physical pixels at the bottom of the window.
+    Shown { height: u32 },
+    /// The keyboard changed its height while visible.
+    Resized { height: u32 },
+    /// The keyboard disappeared.
+    Hidden,
+}
+
+/// Tracks the actual visibility of the soft keyboard.
+///
+/// Visibility is measured by polling [`SoftKeyboard::frame`]. A keyboard counts as visible once it
+/// hides more than 15% of the window, which excludes navigation bars. If the keyboard shows or
+/// hides without a request, e.g. because the user dismissed it with the back button, the
+/// requested visibility follows. Platforms that can't measure the keyboard fall back to the
+/// result of the last request.
+#[derive(Debug)]
+pub struct KeyboardState {
+    requested: bool,
+    visible: bool,
+    height: u32,
+    measured: bool,
+    poll_interval: Duration,
+    last_poll: Option<Instant>,
+    events: VecDeque<KeyboardEvent>,
+}
+
+impl Default for KeyboardState {
+    fn default() -> Self {
+        Self {
+            requested: false,
+            visible: false,
+            height: 0,
+            measured: false,
+            poll_interval: Duration::from_millis(200),
+            last_poll: None,
+            events: VecDeque::new(),
+        }
+    }
+}
+
+impl KeyboardState {
+    /// Minimum share of the window, in percent, the keyboard has to cover to count as visible.
+    const MIN_HEIGHT_PERCENT: u32 = 15;
+
+    /// Whether the keyboard should be visible.
+    pub fn requested(&self) -> bool {
+        self.requested
+    }
+
+    /// Whether the keyboard is visible.
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Physical pixels hidden by the keyboard at the bottom of the window, `0` if hidden or
+    /// unknown.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Take the pending visibility changes, oldest first.
+    pub fn drain(&mut self) -> impl Iterator<Item = KeyboardEvent> + '_ {
+        self.events.drain(..)
+    }
+
+    /// Measure the keyboard on the next [`update`](Self::update), e.g. after a window resize.
+    pub fn invalidate(&mut self) {
+        self.last_poll = None;
+    }
+
+    /// Request the keyboard to be shown or hidden. Failures are logged. A rejected or failed
+    /// request leaves the requested visibility as it was, so the next toggle tries again.
+    pub fn request(
+        &mut self,
+        visible: bool,
+        keyboard: &mut dyn SoftKeyboard,
+        options: &ImeOptions,
+    ) {
+        self.invalidate();
+
+        match keyboard.set_visible(visible, options) {
+            Ok(true) => {
+                self.requested = visible;
+                if !self.measured {
+                    self.apply(visible, 0);
+                }
+            }
+            Ok(false) => log::warn!("soft input request (visible: {}) was rejected", visible),
+            Err(e) => log::error!("failed to toggle soft input: {}", e),
+        }
+    }
+
+    /// Toggle the keyboard if `phase` starts a touch.
+    pub fn handle_touch(
+        &mut self,
+        phase: TouchPhase,
+        keyboard: &mut dyn SoftKeyboard,
+        options: &ImeOptions,
+    ) {
+        if phase == TouchPhase::Started {
+            self.request(!self.requested, keyboard, options);
+        }
+    }
+
+    /// Measure the keyboard if the poll interval has passed since the last measurement.
+    pub fn update(&mut self, keyboard: &mut dyn SoftKeyboard, now: Instant) {
+        if let Some(last_poll) = self.last_poll {
+            if now.saturating_duration_since(last_poll) < self.poll_interval {
+                return;
+            }
+        }
+        self.last_poll = Some(now);
+
+        match keyboard.frame() {
+            Ok(Some(frame)) => {
+                self.measured = true;
+                let visible = frame.height * 100 > frame.window_height * Self::MIN_HEIGHT_PERCENT;
+                self.apply(visible, if visible { frame.height } else { 0 });
+            }
+            Ok(None) => (),
+            Err(e) => log::error!("failed to measure soft input: {}", e),
+        }
+    }
+
+    fn apply(&mut self, visible: bool, height: u32) {
+        if visible != self.visible {
+            self.requested = visible;
+            self.events.push_back(if visible {
+                KeyboardEvent::Shown { height }
+            } else {
+                KeyboardEvent::Hidden
+            });
+        } else if visible && height != self.height {
+            self.events.push_back(KeyboardEvent::Resized { height });
+        }
+
+        self.visible = visible;
+        self.height = height;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A keyboard whose requests always fail.
+    struct FailingSoftKeyboard;
+
+    impl SoftKeyboard for FailingSoftKeyboard {
+        fn set_visible(&mut self, _: bool, _: &ImeOptions) -> Result<bool, SoftInputError> {
+            Err(SoftInputError::MissingService)
+        }
+
+        fn frame(&mut self) -> Result<Option<KeyboardFrame>, SoftInputError> {
+            Err(SoftInputError::MissingService)
+        }
+    }
+
+    fn visibilities(keyboard: &RecordingSoftKeyboard) -> Vec<bool> {
+        keyboard
+            .requests()
+            .iter()
+            .map(|(visible, _)| *visible)
+            .collect()
+    }
+
+    #[test]
+    fn touch_start_toggles() {
+        let mut state = KeyboardState::default();
+        let mut keyboard = RecordingSoftKeyboard::default();
+        let options = ImeOptions::default();
+
+        state.handle_touch(TouchPhase::Started, &mut keyboard, &options);
+        assert!(state.requested());
+        assert!(state.visible());
+        assert_eq!(
+            state.drain().collect::<Vec<_>>(),
+            [KeyboardEvent::Shown { height: 0 }]
+        );
+
+        state.handle_touch(TouchPhase::Started, &mut keyboard, &options);
+        assert!(!state.requested());
+        assert!(!state.visible());
+        assert_eq!(state.drain().collect::<Vec<_>>(), [KeyboardEvent::Hidden]);
+        assert_eq!(visibilities(&keyboard), [true, false]);
+    }
+
+    #[test]
+    fn other_phases_dont_toggle() {
+        let mut state = KeyboardState::default();
+        let mut keyboard = RecordingSoftKeyboard::default();
+        let options = ImeOptions::default();
+
+        for phase in [TouchPhase::Moved, TouchPhase::Ended, TouchPhase::Cancelled] {
+            state.handle_touch(phase, &mut keyboard, &options);
+        }
+        assert!(keyboard.requests().is_empty());
+        assert!(!state.requested());
+        assert_eq!(state.drain().count(), 0);
+    }
+
+    #[test]
+    fn options_are_passed_on() {
+        let mut state = KeyboardState::default();
+        let mut keyboard = RecordingSoftKeyboard::default();
+        let options = ImeOptions::new(crate::ime::KeyboardType::Number);
+
+        state.handle_touch(TouchPhase::Started, &mut keyboard, &options);
+        assert_eq!(keyboard.requests(), [(true, options)]);
+    }
+
+    #[test]
+    fn rejected_request_is_retried() {
+        let mut state = KeyboardState::default();
+        let mut keyboard = RecordingSoftKeyboard::default();
+        let options = ImeOptions::default();
+
+        keyboard.set_accept(false);
+        state.handle_touch(TouchPhase::Started, &mut keyboard, &options);
+        assert!(!state.requested());
+        assert!(!state.visible());
+        assert_eq!(state.drain().count(), 0);
+
+        // The next touch asks to show the keyboard again rather than to hide it
+        keyboard.set_accept(true);
+        state.handle_touch(TouchPhase::Started, &mut keyboard, &options);
+        assert!(state.visible());
+        assert_eq!(visibilities(&keyboard), [true, true]);
+    }
+
+    #[test]
+    fn rejected_hide_keeps_keyboard_visible() {
+        let mut state = KeyboardState::default();
+        let mut keyboard = RecordingSoftKeyboard::default();
+        let options = ImeOptions::default();
+
+        state.handle_touch(TouchPhase::Started, &mut keyboard, &options);
+        state.drain().for_each(drop);
+        keyboard.set_accept(false);
+        state.handle_touch(TouchPhase::Started, &mut keyboard, &options);
+        assert!(state.requested());
+        assert!(state.visible());
+        assert_eq!(state.drain().count(), 0);
+    }
+
+    #[test]
+    fn failed_request_changes_nothing() {
+        let mut state = KeyboardState::default();
+        state.handle_touch(
+            TouchPhase::Started,
+            &mut FailingSoftKeyboard,
+            &ImeOptions::default(),
+        );
+        assert!(!state.requested());
+        assert!(!state.visible());
+        assert_eq!(state.drain().count(), 0);
+
+        // Measuring fails as well and is only logged
+        state.update(&mut FailingSoftKeyboard, Instant::now());
+        assert!(!state.visible());
+    }
+
+    #[test]
+    fn measured_visibility_wins() {
+        let mut state = KeyboardState::default();
+        let mut keyboard = RecordingSoftKeyboard::default();
+        let now = Instant::now();
+
+        keyboard.set_frame(Some(KeyboardFrame {
+            height: 900,
+            window_height: 2400,
+        }));
+        state.update(&mut keyboard, now);
+        assert!(state.visible());
+        assert!(state.requested());
+        assert_eq!(
+            state.drain().collect::<Vec<_>>(),
+            [KeyboardEvent::Shown { height: 900 }]
+        );
+
+        // Dismissed with the back button; a navigation bar alone doesn't count
+        keyboard.set_frame(Some(KeyboardFrame {
+            height: 120,
+            window_height: 2400,
+        }));
+        state.update(&mut keyboard, now + Duration::from_millis(100));
+        assert!(state.visible(), "polled before the interval passed");
+        state.update(&mut keyboard, now + Duration::from_millis(200));
+        assert!(!state.visible());
+        assert!(!state.requested());
+        assert_eq!(state.drain().collect::<Vec<_>>(), [KeyboardEvent::Hidden]);
+    }
+}
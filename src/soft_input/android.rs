@@ -0,0 +1,233 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use jni::objects::{JObject, JString};
+use jni::JNIEnv;
+
+use super::{KeyboardFrame, SoftInputError, SoftKeyboard};
+use crate::ime::ImeOptions;
+
+/// Soft keyboard of the `NativeActivity`, controlled through the `InputMethodManager`.
+#[derive(Debug, Default)]
+pub struct AndroidSoftKeyboard;
+
+impl SoftKeyboard for AndroidSoftKeyboard {
+    fn set_visible(&mut self, visible: bool, options: &ImeOptions) -> Result<bool, SoftInputError> {
+        show_soft_input(visible, options)
+    }
+
+    fn frame(&mut self) -> Result<Option<KeyboardFrame>, SoftInputError> {
+        soft_input_frame().map(Some)
+    }
+}
+
+/// Convert a JNI result, clearing and capturing a pending Java exception.
+fn check<T>(env: &JNIEnv, result: jni::errors::Result<T>) -> Result<T, SoftInputError> {
+    result.map_err(|e| match e {
+        jni::errors::Error::JavaException => SoftInputError::JavaException(take_exception(env)),
+        e => SoftInputError::Jni(e),
+    })
+}
+
+/// Clear the pending Java exception and return its `toString()`.
+fn take_exception(env: &JNIEnv) -> String {
+    const UNKNOWN: &str = "unknown exception";
+
+    let throwable = match env.exception_occurred() {
+        Ok(throwable) if !throwable.is_null() => throwable,
+        _ => return UNKNOWN.to_string(),
+    };
+    let _ = env.exception_clear();
+
+    let message = env
+        .call_method(throwable, "toString", "()Ljava/lang/String;", &[])
+        .and_then(|value| value.l())
+        .and_then(|string| env.get_string(JString::from(string)).map(String::from));
+    match message {
+        Ok(message) => message,
+        Err(_) => {
+            // `toString` itself may have thrown
+            let _ = env.exception_clear();
+            UNKNOWN.to_string()
+        }
+    }
+}
+
+/// Pass `options` to the activity's `setImeOptions(int inputType, int imeOptions)`.
+///
+/// `NativeActivity` has no way to change the `EditorInfo` of its view, so this only takes effect
+/// if the app's activity subclass provides the method and applies the values in
+/// `onCreateInputConnection`. Returns whether the method exists.
+fn set_ime_options(
+    env: &JNIEnv,
+    activity: JObject,
+    options: &ImeOptions,
+) -> Result<bool, SoftInputError> {
+    let class = check(env, env.get_object_class(activity))?;
+    if env.get_method_id(class, "setImeOptions", "(II)V").is_err() {
+        // `NoSuchMethodError` is pending
+        let _ = env.exception_clear();
+        return Ok(false);
+    }
+
+    check(
+        env,
+        env.call_method(
+            activity,
+            "setImeOptions",
+            "(II)V",
+            &[options.input_type().into(), options.ime_options().into()],
+        ),
+    )?;
+    Ok(true)
+}
+
+/// Warn once that `options` can't be applied, so a missing activity subclass is noticed without
+/// flooding the log each time the keyboard is shown.
+fn warn_missing_set_ime_options(options: &ImeOptions) {
+    static WARNED: AtomicBool = AtomicBool::new(false);
+
+    if !WARNED.swap(true, Ordering::Relaxed) {
+        log::warn!(
+            "activity has no setImeOptions(int, int), ignoring {:?}; see the ImeOptions docs",
+            options
+        );
+    }
+}
+
+/// Attach to the Java VM and run `f` with the native activity.
+fn with_activity<T>(
+    f: impl FnOnce(&JNIEnv, JObject) -> Result<T, SoftInputError>,
+) -> Result<T, SoftInputError> {
+    let ctx = ndk_glue::native_activity();
+
+    let vm = unsafe { jni::JavaVM::from_raw(ctx.vm().cast()) }.map_err(SoftInputError::Attach)?;
+    let env = vm.attach_current_thread().map_err(SoftInputError::Attach)?;
+    f(&env, JObject::from(ctx.activity()))
+}
+
+/// The decor view of the activity's window.
+fn decor_view<'a>(env: &JNIEnv<'a>, activity: JObject<'a>) -> Result<JObject<'a>, SoftInputError> {
+    let jni_window = check(
+        env,
+        env.call_method(activity, "getWindow", "()Landroid/view/Window;", &[])
+            .and_then(|value| value.l()),
+    )?;
+    check(
+        env,
+        env.call_method(jni_window, "getDecorView", "()Landroid/view/View;", &[])
+            .and_then(|value| value.l()),
+    )
+}
+
+/// Show or hide the soft keyboard of the native activity.
+///
+/// Returns the result reported by the `InputMethodManager`.
+pub fn show_soft_input(show: bool, options: &ImeOptions) -> Result<bool, SoftInputError> {
+    with_activity(|env, activity| {
+        let class_ctxt = check(env, env.find_class("android/content/Context"))?;
+        let ime = check(
+            env,
+            env.get_static_field(class_ctxt, "INPUT_METHOD_SERVICE", "Ljava/lang/String;"),
+        )?;
+        let ime_manager = check(
+            env,
+            env.call_method(
+                activity,
+                "getSystemService",
+                "(Ljava/lang/String;)Ljava/lang/Object;",
+                &[ime],
+            )
+            .and_then(|value| value.l()),
+        )?;
+        if ime_manager.is_null() {
+            return Err(SoftInputError::MissingService);
+        }
+
+        let view = decor_view(env, activity)?;
+
+        if show {
+            if set_ime_options(env, activity, options)? {
+                check(
+                    env,
+                    env.call_method(
+                        ime_manager,
+                        "restartInput",
+                        "(Landroid/view/View;)V",
+                        &[view.into()],
+                    ),
+                )?;
+            } else if *options != ImeOptions::default() {
+                warn_missing_set_ime_options(options);
+            }
+
+            let result = check(
+                env,
+                env.call_method(
+                    ime_manager,
+                    "showSoftInput",
+                    "(Landroid/view/View;I)Z",
+                    &[view.into(), 0i32.into()],
+                )
+                .and_then(|value| value.z()),
+            )?;
+            log::info!("show input: {}", result);
+            Ok(result)
+        } else {
+            let window_token = check(
+                env,
+                env.call_method(view, "getWindowToken", "()Landroid/os/IBinder;", &[])
+                    .and_then(|value| value.l()),
+            )?;
+            if window_token.is_null() {
+                return Err(SoftInputError::NullWindowToken);
+            }
+            let result = check(
+                env,
+                env.call_method(
+                    ime_manager,
+                    "hideSoftInputFromWindow",
+                    "(Landroid/os/IBinder;I)Z",
+                    &[window_token.into(), 0i32.into()],
+                )
+                .and_then(|value| value.z()),
+            )?;
+            log::info!("hide input: {}", result);
+            Ok(result)
+        }
+    })
+}
+
+/// Measure the part of the window hidden at the bottom, using `getWindowVisibleDisplayFrame`.
+///
+/// Besides the soft keyboard this includes the navigation bar, if it overlaps the window.
+pub fn soft_input_frame() -> Result<KeyboardFrame, SoftInputError> {
+    with_activity(|env, activity| {
+        let view = decor_view(env, activity)?;
+
+        let rect = check(env, env.new_object("android/graphics/Rect", "()V", &[]))?;
+        check(
+            env,
+            env.call_method(
+                view,
+                "getWindowVisibleDisplayFrame",
+                "(Landroid/graphics/Rect;)V",
+                &[rect.into()],
+            ),
+        )?;
+        let visible_bottom = check(
+            env,
+            env.get_field(rect, "bottom", "I")
+                .and_then(|value| value.i()),
+        )?;
+        let window_height = check(
+            env,
+            env.call_method(view, "getHeight", "()I", &[])
+                .and_then(|value| value.i()),
+        )?;
+
+        Ok(KeyboardFrame {
+            height: (window_height - visible_bottom).max(0) as u32,
+            window_height: window_height.max(0) as u32,
+        })
+    })
+}
@@ -0,0 +1,883 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+use anyhow::{bail, ensure, Context};
+use serde::de::{self, Deserializer, MapAccess, SeqAccess};
+use serde::Deserialize;
+
+use crate::canvas::{BlitOptions, Canvas, Rect};
+use crate::image::Image;
+
+/// Duration of frames sliced from a grid.
+const DEFAULT_FRAME_DURATION: Duration = Duration::from_millis(100);
+
+/// One frame of a [`SpriteSheet`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpriteFrame {
+    /// Area of the frame in the atlas image.
+    pub rect: Rect,
+    /// How long the frame is shown when animated.
+    pub duration: Duration,
+}
+
+/// Order in which the frames of a [`FrameTag`] play.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TagDirection {
+    #[default]
+    Forward,
+    Reverse,
+    PingPong,
+    /// Ping-pong starting from the last frame.
+    PingPongReverse,
+}
+
+/// A named range of frames, e.g. `walk` or `jump`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameTag {
+    pub name: String,
+    /// First frame of the range.
+    pub from: usize,
+    /// Last frame of the range, included.
+    pub to: usize,
+    pub direction: TagDirection,
+    /// Number of times the range plays, `None` to repeat forever. For ping-pong tags a pass
+    /// forwards and back counts once.
+    pub repeat: Option<u32>,
+}
+
+/// An atlas image sliced into frames.
+#[derive(Debug, Clone)]
+pub struct SpriteSheet {
+    image: Image,
+    frames: Vec<SpriteFrame>,
+    tags: Vec<FrameTag>,
+}
+
+impl SpriteSheet {
+    /// Slice `image` into frames of `frame_width` × `frame_height`, row by row. Partial frames
+    /// at the right and bottom edges are ignored.
+    pub fn grid(image: Image, frame_width: u32, frame_height: u32) -> anyhow::Result<Self> {
+        ensure!(
+            frame_width > 0 && frame_height > 0,
+            "frame size must not be zero"
+        );
+        let columns = image.width() / frame_width;
+        let rows = image.height() / frame_height;
+        ensure!(
+            columns > 0 && rows > 0,
+            "{}x{} frames do not fit into a {}x{} image",
+            frame_width,
+            frame_height,
+            image.width(),
+            image.height()
+        );
+
+        let frames = (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (column, row)))
+            .map(|(column, row)| SpriteFrame {
+                rect: Rect::new(
+                    (column * frame_width) as i32,
+                    (row * frame_height) as i32,
+                    frame_width,
+                    frame_height,
+                ),
+                duration: DEFAULT_FRAME_DURATION,
+            })
+            .collect();
+
+        Ok(Self {
+            image,
+            frames,
+            tags: Vec::new(),
+        })
+    }
+
+    /// Slice `image` by the JSON data exported by Aseprite, in either the hash or the array
+    /// layout. Frame durations and tags are taken from the export.
+    pub fn from_aseprite(image: Image, json: &str) -> anyhow::Result<Self> {
+        let export: AsepriteExport = serde_json::from_str(json).context("invalid Aseprite JSON")?;
+        let bounds = image.rect();
+
+        let frames = export
+            .frames
+            .0
+            .into_iter()
+            .enumerate()
+            .map(|(i, frame)| {
+                let rect = Rect::new(frame.frame.x, frame.frame.y, frame.frame.w, frame.frame.h);
+                ensure!(
+                    bounds.intersect(&rect) == rect,
+                    "frame {} at {:?} exceeds the {}x{} image",
+                    i,
+                    rect,
+                    image.width(),
+                    image.height()
+                );
+                Ok(SpriteFrame {
+                    rect,
+                    duration: Duration::from_millis(frame.duration),
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let tags = export
+            .meta
+            .frame_tags
+            .into_iter()
+            .map(|tag| {
+                ensure!(
+                    tag.from <= tag.to && tag.to < frames.len(),
+                    "tag {:?} covers missing frames {}..={}",
+                    tag.name,
+                    tag.from,
+                    tag.to
+                );
+                let direction = match tag.direction.as_str() {
+                    "reverse" => TagDirection::Reverse,
+                    "pingpong" => TagDirection::PingPong,
+                    "pingpong_reverse" => TagDirection::PingPongReverse,
+                    _ => TagDirection::Forward,
+                };
+                let repeat = match tag.repeat.as_deref().map(str::parse) {
+                    Some(Ok(0)) | None => None,
+                    Some(Ok(count)) => Some(count),
+                    Some(Err(_)) => bail!("invalid repeat count of tag {:?}", tag.name),
+                };
+                Ok(FrameTag {
+                    name: tag.name,
+                    from: tag.from,
+                    to: tag.to,
+                    direction,
+                    repeat,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            image,
+            frames,
+            tags,
+        })
+    }
+
+    /// Load an Aseprite JSON export and the atlas image it refers to, relative to the JSON file.
+    pub fn load_aseprite(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        #[derive(Deserialize)]
+        struct Export {
+            meta: Meta,
+        }
+        #[derive(Deserialize)]
+        struct Meta {
+            image: String,
+        }
+
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let export: Export = serde_json::from_str(&json)
+            .with_context(|| format!("invalid Aseprite JSON in {}", path.display()))?;
+        let image_path = path
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(export.meta.image);
+
+        Self::from_aseprite(Image::load(image_path)?, &json)
+            .with_context(|| format!("failed to load {}", path.display()))
+    }
+
+    /// The atlas image.
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    /// All frames, in order.
+    pub fn frames(&self) -> &[SpriteFrame] {
+        &self.frames
+    }
+
+    /// The frame at `index`.
+    pub fn frame(&self, index: usize) -> Option<&SpriteFrame> {
+        self.frames.get(index)
+    }
+
+    /// All tags, in order.
+    pub fn tags(&self) -> &[FrameTag] {
+        &self.tags
+    }
+
+    /// The tag called `name`.
+    pub fn tag(&self, name: &str) -> Option<&FrameTag> {
+        self.tags.iter().find(|tag| tag.name == name)
+    }
+
+    /// Set the duration of every frame, e.g. for a sheet sliced from a grid.
+    pub fn set_frame_duration(&mut self, duration: Duration) {
+        for frame in &mut self.frames {
+            frame.duration = duration;
+        }
+    }
+
+    /// An animation over all frames, repeating forever.
+    pub fn animation(&self) -> Animation {
+        Animation::new(
+            self.frames
+                .iter()
+                .enumerate()
+                .map(|(i, frame)| (i, frame.duration))
+                .collect(),
+            LoopMode::Loop,
+        )
+    }
+
+    /// An animation over the frames of the tag called `name`, played as the tag describes.
+    /// Tags with a repeat count stop after playing that many times, see [`LoopMode::Count`] and
+    /// [`LoopMode::PingPongCount`].
+    pub fn tag_animation(&self, name: &str) -> Option<Animation> {
+        let tag = self.tag(name)?;
+        let mut frames: Vec<_> = (tag.from..=tag.to)
+            .map(|i| (i, self.frames[i].duration))
+            .collect();
+        if let TagDirection::Reverse | TagDirection::PingPongReverse = tag.direction {
+            frames.reverse();
+        }
+
+        let mode = match (tag.direction, tag.repeat) {
+            (TagDirection::PingPong | TagDirection::PingPongReverse, None) => LoopMode::PingPong,
+            (TagDirection::PingPong | TagDirection::PingPongReverse, Some(count)) => {
+                LoopMode::PingPongCount(count)
+            }
+            (_, Some(1)) => LoopMode::Once,
+            (_, Some(count)) => LoopMode::Count(count),
+            (_, None) => LoopMode::Loop,
+        };
+        Some(Animation::new(frames, mode))
+    }
+
+    /// Draw the frame at `index` with its top left corner at `(x, y)`. `options.source` is
+    /// replaced by the frame.
+    pub fn draw(&self, canvas: &mut Canvas, index: usize, x: i32, y: i32, options: &BlitOptions) {
+        if let Some(frame) = self.frames.get(index) {
+            canvas.blit(&self.image, x, y, &options.with_source(frame.rect));
+        }
+    }
+}
+
+/// What an [`Animation`] does after its last frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoopMode {
+    /// Stop on the last frame.
+    Once,
+    /// Start over from the first frame.
+    #[default]
+    Loop,
+    /// Play backwards to the first frame, then forwards again.
+    PingPong,
+    /// Play the sequence this many times, then stop on the last frame.
+    Count(u32),
+    /// Play forwards and back to the first frame this many times, then stop there.
+    PingPongCount(u32),
+}
+
+/// Reported by [`Animation::drain`] and to [`Animation::on_event`] callbacks as the animation
+/// advances.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationEvent {
+    /// The frame at this sheet index is now shown.
+    Frame(usize),
+    /// The animation started another cycle.
+    Looped,
+    /// The animation played all its cycles and stopped.
+    Finished,
+}
+
+/// A callback registered with [`Animation::on_event`].
+type EventCallback = Rc<RefCell<dyn FnMut(&AnimationEvent)>>;
+
+/// Plays a sequence of sprite frames with per-frame durations.
+///
+/// Advance it from [`update`](crate::app::PixelsApp::update) by the tick duration, so the animation
+/// runs at the same speed regardless of the frame rate, then draw [`frame`](Self::frame).
+///
+/// Events are queued for [`drain`](Self::drain) until a callback is registered with
+/// [`on_event`](Self::on_event); from then on they are passed to the callbacks instead.
+#[derive(Clone)]
+pub struct Animation {
+    /// Sheet index and duration of each step.
+    frames: Vec<(usize, Duration)>,
+    mode: LoopMode,
+    position: usize,
+    backwards: bool,
+    /// Cycles played so far, to stop counted modes.
+    cycles: u32,
+    elapsed: Duration,
+    finished: bool,
+    events: VecDeque<AnimationEvent>,
+    callbacks: Vec<EventCallback>,
+}
+
+impl fmt::Debug for Animation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Animation")
+            .field("frames", &self.frames)
+            .field("mode", &self.mode)
+            .field("position", &self.position)
+            .field("backwards", &self.backwards)
+            .field("cycles", &self.cycles)
+            .field("elapsed", &self.elapsed)
+            .field("finished", &self.finished)
+            .field("events", &self.events)
+            .field("callbacks", &self.callbacks.len())
+            .finish()
+    }
+}
+
+impl Animation {
+    /// Play `frames`, pairs of a sheet index and how long it is shown.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frames` is empty.
+    pub fn new(frames: Vec<(usize, Duration)>, mode: LoopMode) -> Self {
+        assert!(!frames.is_empty(), "animation must have frames");
+
+        Self {
+            frames,
+            mode,
+            position: 0,
+            backwards: false,
+            cycles: 0,
+            elapsed: Duration::ZERO,
+            finished: false,
+            events: VecDeque::new(),
+            callbacks: Vec::new(),
+        }
+    }
+
+    /// Sheet index of the current frame.
+    pub fn frame(&self) -> usize {
+        self.frames[self.position].0
+    }
+
+    /// Position of the current frame in the sequence.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn mode(&self) -> LoopMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: LoopMode) {
+        self.mode = mode;
+    }
+
+    /// Whether the animation played all the cycles of a [`LoopMode::Once`] or counted mode.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Call `callback` with every event from now on, while [`advance`](Self::advance) runs.
+    ///
+    /// Events are no longer queued for [`drain`](Self::drain) once a callback is registered.
+    /// Clones of the animation share its callbacks.
+    pub fn on_event(&mut self, callback: impl FnMut(&AnimationEvent) + 'static) {
+        self.callbacks.push(Rc::new(RefCell::new(callback)));
+    }
+
+    /// Take the events since the last call, oldest first.
+    pub fn drain(&mut self) -> impl Iterator<Item = AnimationEvent> + '_ {
+        self.events.drain(..)
+    }
+
+    /// Restart from the first frame.
+    pub fn reset(&mut self) {
+        self.position = 0;
+        self.backwards = false;
+        self.cycles = 0;
+        self.elapsed = Duration::ZERO;
+        self.finished = false;
+        self.events.clear();
+    }
+
+    /// Advance the animation by `dt`, moving through as many frames as elapsed.
+    pub fn advance(&mut self, dt: Duration) {
+        if self.finished {
+            return;
+        }
+        self.elapsed += dt;
+
+        loop {
+            // Zero length frames still show for a moment instead of spinning forever
+            let duration = self.frames[self.position].1.max(Duration::from_millis(1));
+            if self.elapsed < duration {
+                break;
+            }
+            self.elapsed -= duration;
+            self.step();
+            if self.finished {
+                self.elapsed = Duration::ZERO;
+                break;
+            }
+        }
+    }
+
+    fn emit(&mut self, event: AnimationEvent) {
+        if self.callbacks.is_empty() {
+            self.events.push_back(event);
+        }
+        for callback in &self.callbacks {
+            (callback.borrow_mut())(&event);
+        }
+    }
+
+    /// Count a finished cycle. Stops the animation and returns `true` if it was the last one.
+    fn end_cycle(&mut self) -> bool {
+        self.cycles = self.cycles.saturating_add(1);
+        let count = match self.mode {
+            LoopMode::Once => Some(1),
+            LoopMode::Count(count) | LoopMode::PingPongCount(count) => Some(count.max(1)),
+            LoopMode::Loop | LoopMode::PingPong => None,
+        };
+        if matches!(count, Some(count) if self.cycles >= count) {
+            self.finished = true;
+            self.emit(AnimationEvent::Finished);
+            true
+        } else {
+            self.emit(AnimationEvent::Looped);
+            false
+        }
+    }
+
+    fn step(&mut self) {
+        let last = self.frames.len() - 1;
+        let position = match self.mode {
+            LoopMode::Once | LoopMode::Loop | LoopMode::Count(_) if self.position == last => {
+                if self.end_cycle() {
+                    return;
+                }
+                0
+            }
+            LoopMode::Once | LoopMode::Loop | LoopMode::Count(_) => self.position + 1,
+            LoopMode::PingPong | LoopMode::PingPongCount(_) if last == 0 => {
+                if self.end_cycle() {
+                    return;
+                }
+                0
+            }
+            LoopMode::PingPong | LoopMode::PingPongCount(_) => {
+                if self.backwards && self.position == 0 {
+                    if self.end_cycle() {
+                        return;
+                    }
+                    self.backwards = false;
+                } else if !self.backwards && self.position == last {
+                    self.backwards = true;
+                }
+                if self.backwards {
+                    self.position - 1
+                } else {
+                    self.position + 1
+                }
+            }
+        };
+
+        if position != self.position {
+            self.position = position;
+            self.emit(AnimationEvent::Frame(self.frame()));
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AsepriteExport {
+    frames: AsepriteFrames,
+    #[serde(default)]
+    meta: AsepriteMeta,
+}
+
+#[derive(Deserialize)]
+struct AsepriteFrame {
+    frame: AsepriteRect,
+    #[serde(default = "default_duration")]
+    duration: u64,
+}
+
+#[derive(Deserialize)]
+struct AsepriteRect {
+    x: i32,
+    y: i32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize, Default)]
+struct AsepriteMeta {
+    #[serde(default, rename = "frameTags")]
+    frame_tags: Vec<AsepriteTag>,
+}
+
+#[derive(Deserialize)]
+struct AsepriteTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: String,
+    repeat: Option<String>,
+}
+
+fn default_duration() -> u64 {
+    DEFAULT_FRAME_DURATION.as_millis() as u64
+}
+
+/// Frames of an Aseprite export, either an array or a map keyed by name, in file order.
+struct AsepriteFrames(Vec<AsepriteFrame>);
+
+impl<'de> Deserialize<'de> for AsepriteFrames {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = Vec<AsepriteFrame>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an array or map of frames")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut frames = Vec::new();
+                while let Some(frame) = seq.next_element()? {
+                    frames.push(frame);
+                }
+                Ok(frames)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut frames = Vec::new();
+                while let Some((_, frame)) = map.next_entry::<de::IgnoredAny, _>()? {
+                    frames.push(frame);
+                }
+                Ok(frames)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor).map(AsepriteFrames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    /// An animation over `frame_count` frames of 10ms.
+    fn sequence(frame_count: usize, mode: LoopMode) -> Animation {
+        Animation::new((0..frame_count).map(|i| (i, ms(10))).collect(), mode)
+    }
+
+    /// Sheet indices shown after each of `steps` advances by one frame duration.
+    fn play(animation: &mut Animation, steps: usize) -> Vec<usize> {
+        (0..steps)
+            .map(|_| {
+                animation.advance(ms(10));
+                animation.frame()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn frame_timing() {
+        let mut animation = Animation::new(
+            vec![(0, ms(100)), (1, ms(50)), (2, ms(200))],
+            LoopMode::Loop,
+        );
+        animation.advance(ms(99));
+        assert_eq!(animation.frame(), 0);
+        animation.advance(ms(1));
+        assert_eq!(animation.frame(), 1);
+        animation.advance(ms(49));
+        assert_eq!(animation.frame(), 1);
+        // Leftover time carries over into the next frame
+        animation.advance(ms(51));
+        assert_eq!(animation.frame(), 2);
+        animation.advance(ms(149));
+        assert_eq!(animation.frame(), 2);
+        animation.advance(ms(1));
+        assert_eq!(animation.frame(), 0);
+
+        // One long advance moves through several frames
+        animation.drain().for_each(drop);
+        animation.advance(ms(350));
+        assert_eq!(animation.frame(), 0);
+        assert_eq!(
+            animation.drain().collect::<Vec<_>>(),
+            [
+                AnimationEvent::Frame(1),
+                AnimationEvent::Frame(2),
+                AnimationEvent::Looped,
+                AnimationEvent::Frame(0),
+            ]
+        );
+    }
+
+    #[test]
+    fn zero_length_frames_advance() {
+        let mut animation = Animation::new(vec![(0, ms(0)), (1, ms(0))], LoopMode::Loop);
+        animation.advance(ms(3));
+        assert_eq!(animation.frame(), 1);
+    }
+
+    #[test]
+    fn loop_mode() {
+        let mut animation = sequence(3, LoopMode::Loop);
+        assert_eq!(play(&mut animation, 5), [1, 2, 0, 1, 2]);
+        assert!(!animation.is_finished());
+    }
+
+    #[test]
+    fn once_mode() {
+        let mut animation = sequence(3, LoopMode::Once);
+        assert_eq!(play(&mut animation, 4), [1, 2, 2, 2]);
+        assert!(animation.is_finished());
+        assert_eq!(
+            animation.drain().collect::<Vec<_>>(),
+            [
+                AnimationEvent::Frame(1),
+                AnimationEvent::Frame(2),
+                AnimationEvent::Finished,
+            ]
+        );
+
+        animation.reset();
+        assert!(!animation.is_finished());
+        assert_eq!(animation.frame(), 0);
+    }
+
+    #[test]
+    fn ping_pong_mode() {
+        let mut animation = sequence(3, LoopMode::PingPong);
+        assert_eq!(play(&mut animation, 7), [1, 2, 1, 0, 1, 2, 1]);
+        let events: Vec<_> = animation.drain().collect();
+        assert_eq!(
+            events[..5],
+            [
+                AnimationEvent::Frame(1),
+                AnimationEvent::Frame(2),
+                AnimationEvent::Frame(1),
+                AnimationEvent::Frame(0),
+                AnimationEvent::Looped,
+            ]
+        );
+
+        // A single frame loops in place
+        let mut animation = sequence(1, LoopMode::PingPong);
+        assert_eq!(play(&mut animation, 2), [0, 0]);
+        assert_eq!(
+            animation.drain().collect::<Vec<_>>(),
+            [AnimationEvent::Looped, AnimationEvent::Looped]
+        );
+    }
+
+    #[test]
+    fn counted_modes() {
+        let mut animation = sequence(3, LoopMode::Count(2));
+        assert_eq!(play(&mut animation, 7), [1, 2, 0, 1, 2, 2, 2]);
+        assert!(animation.is_finished());
+        assert_eq!(
+            animation.drain().collect::<Vec<_>>(),
+            [
+                AnimationEvent::Frame(1),
+                AnimationEvent::Frame(2),
+                AnimationEvent::Looped,
+                AnimationEvent::Frame(0),
+                AnimationEvent::Frame(1),
+                AnimationEvent::Frame(2),
+                AnimationEvent::Finished,
+            ]
+        );
+
+        // Resetting starts counting again
+        animation.reset();
+        assert_eq!(play(&mut animation, 3), [1, 2, 0]);
+        assert!(!animation.is_finished());
+
+        // A ping-pong cycle is a pass forwards and back, ending on the first frame
+        let mut animation = sequence(3, LoopMode::PingPongCount(2));
+        assert_eq!(play(&mut animation, 9), [1, 2, 1, 0, 1, 2, 1, 0, 0]);
+        assert!(animation.is_finished());
+        let events: Vec<_> = animation.drain().collect();
+        assert_eq!(events[4], AnimationEvent::Looped);
+        assert_eq!(events.last(), Some(&AnimationEvent::Finished));
+
+        let mut animation = sequence(1, LoopMode::PingPongCount(2));
+        assert_eq!(play(&mut animation, 3), [0, 0, 0]);
+        assert_eq!(
+            animation.drain().collect::<Vec<_>>(),
+            [AnimationEvent::Looped, AnimationEvent::Finished]
+        );
+    }
+
+    #[test]
+    fn event_callbacks() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut animation = sequence(2, LoopMode::Loop);
+        let seen = events.clone();
+        animation.on_event(move |event| seen.borrow_mut().push(*event));
+
+        play(&mut animation, 2);
+        assert_eq!(
+            *events.borrow(),
+            [
+                AnimationEvent::Frame(1),
+                AnimationEvent::Looped,
+                AnimationEvent::Frame(0),
+            ]
+        );
+        // Events go to the callbacks instead of the queue
+        assert_eq!(animation.drain().count(), 0);
+    }
+
+    #[test]
+    fn grid() {
+        let sheet = SpriteSheet::grid(Image::new(50, 20, [0; 4]), 16, 10).unwrap();
+        assert_eq!(sheet.frames().len(), 6);
+        assert_eq!(sheet.frame(4).unwrap().rect, Rect::new(16, 10, 16, 10));
+        assert_eq!(sheet.frame(0).unwrap().duration, DEFAULT_FRAME_DURATION);
+
+        assert!(SpriteSheet::grid(Image::new(8, 8, [0; 4]), 16, 16).is_err());
+        assert!(SpriteSheet::grid(Image::new(8, 8, [0; 4]), 0, 8).is_err());
+    }
+
+    const ASEPRITE_HASH: &str = r#"{
+        "frames": {
+            "hero 0.aseprite": { "frame": { "x": 0, "y": 0, "w": 16, "h": 16 }, "duration": 100 },
+            "hero 1.aseprite": { "frame": { "x": 16, "y": 0, "w": 16, "h": 16 }, "duration": 150 },
+            "hero 2.aseprite": { "frame": { "x": 32, "y": 0, "w": 16, "h": 16 }, "duration": 200 }
+        },
+        "meta": {
+            "image": "hero.png",
+            "frameTags": [
+                { "name": "walk", "from": 0, "to": 2, "direction": "pingpong" },
+                { "name": "back", "from": 1, "to": 2, "direction": "reverse", "repeat": "1" },
+                { "name": "bounce", "from": 0, "to": 1, "direction": "pingpong_reverse", "repeat": "3" }
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn aseprite_hash() {
+        let sheet = SpriteSheet::from_aseprite(Image::new(48, 16, [0; 4]), ASEPRITE_HASH).unwrap();
+        assert_eq!(
+            sheet.frames(),
+            [
+                SpriteFrame {
+                    rect: Rect::new(0, 0, 16, 16),
+                    duration: ms(100),
+                },
+                SpriteFrame {
+                    rect: Rect::new(16, 0, 16, 16),
+                    duration: ms(150),
+                },
+                SpriteFrame {
+                    rect: Rect::new(32, 0, 16, 16),
+                    duration: ms(200),
+                },
+            ]
+        );
+        assert_eq!(
+            sheet.tag("back"),
+            Some(&FrameTag {
+                name: "back".to_string(),
+                from: 1,
+                to: 2,
+                direction: TagDirection::Reverse,
+                repeat: Some(1),
+            })
+        );
+        assert_eq!(sheet.tag("walk").unwrap().direction, TagDirection::PingPong);
+        assert_eq!(sheet.tag("walk").unwrap().repeat, None);
+        assert_eq!(sheet.tag("bounce").unwrap().repeat, Some(3));
+    }
+
+    #[test]
+    fn aseprite_array() {
+        let json = r#"{
+            "frames": [
+                { "filename": "0", "frame": { "x": 0, "y": 0, "w": 8, "h": 8 } },
+                { "filename": "1", "frame": { "x": 8, "y": 0, "w": 8, "h": 8 }, "duration": 40 }
+            ]
+        }"#;
+        let sheet = SpriteSheet::from_aseprite(Image::new(16, 8, [0; 4]), json).unwrap();
+        assert_eq!(sheet.frames().len(), 2);
+        assert_eq!(sheet.frame(0).unwrap().duration, DEFAULT_FRAME_DURATION);
+        assert_eq!(sheet.frame(1).unwrap().duration, ms(40));
+        assert!(sheet.tags().is_empty());
+    }
+
+    #[test]
+    fn aseprite_errors() {
+        let image = || Image::new(48, 16, [0; 4]);
+        // Frames outside the image
+        assert!(SpriteSheet::from_aseprite(Image::new(32, 16, [0; 4]), ASEPRITE_HASH).is_err());
+        // Tags covering missing frames
+        let json = ASEPRITE_HASH.replace(r#""to": 2, "direction": "pingpong""#, r#""to": 3"#);
+        assert!(SpriteSheet::from_aseprite(image(), &json).is_err());
+        // Invalid repeat counts
+        let json = ASEPRITE_HASH.replace(r#""repeat": "3""#, r#""repeat": "often""#);
+        assert!(SpriteSheet::from_aseprite(image(), &json).is_err());
+        assert!(SpriteSheet::from_aseprite(image(), "{}").is_err());
+        assert!(SpriteSheet::from_aseprite(image(), r#"{ "frames": 3 }"#).is_err());
+    }
+
+    #[test]
+    fn tag_animations() {
+        let sheet = SpriteSheet::from_aseprite(Image::new(48, 16, [0; 4]), ASEPRITE_HASH).unwrap();
+
+        let walk = sheet.tag_animation("walk").unwrap();
+        assert_eq!(walk.mode(), LoopMode::PingPong);
+        assert_eq!(walk.frame(), 0);
+
+        // Reverse tags start from their last frame, and play once with a repeat count of 1
+        let mut back = sheet.tag_animation("back").unwrap();
+        assert_eq!(back.mode(), LoopMode::Once);
+        assert_eq!(back.frame(), 2);
+        back.advance(ms(200));
+        assert_eq!(back.frame(), 1);
+
+        let bounce = sheet.tag_animation("bounce").unwrap();
+        assert_eq!(bounce.mode(), LoopMode::PingPongCount(3));
+        assert_eq!(bounce.frame(), 1);
+
+        // Ping-pong tags playing once still go forwards and back
+        let json = ASEPRITE_HASH.replace(r#""repeat": "3""#, r#""repeat": "1""#);
+        let sheet = SpriteSheet::from_aseprite(Image::new(48, 16, [0; 4]), &json).unwrap();
+        let mut bounce = sheet.tag_animation("bounce").unwrap();
+        assert_eq!(bounce.mode(), LoopMode::PingPongCount(1));
+        bounce.advance(ms(150));
+        assert_eq!(bounce.frame(), 0);
+        assert!(!bounce.is_finished());
+        bounce.advance(ms(100));
+        assert_eq!(bounce.frame(), 1);
+        assert!(!bounce.is_finished());
+        bounce.advance(ms(150));
+        assert_eq!(bounce.frame(), 1);
+        assert!(bounce.is_finished());
+
+        // Other repeat counts stop after that many passes
+        let json = ASEPRITE_HASH.replace(r#""repeat": "1""#, r#""repeat": "2""#);
+        let sheet = SpriteSheet::from_aseprite(Image::new(48, 16, [0; 4]), &json).unwrap();
+        assert_eq!(
+            sheet.tag_animation("back").unwrap().mode(),
+            LoopMode::Count(2)
+        );
+
+        assert!(sheet.tag_animation("run").is_none());
+    }
+}
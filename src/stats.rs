@@ -0,0 +1,313 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::canvas::Canvas;
+
+/// Timings of one frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameSample {
+    /// Time since the previous frame was presented.
+    pub interval: Duration,
+    /// Time spent in all updates of the frame.
+    pub update: Duration,
+    /// Time spent drawing into the frame buffer.
+    pub draw: Duration,
+    /// Time spent uploading and presenting the frame buffer.
+    pub render: Duration,
+}
+
+/// Distribution of a duration over the recorded frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Percentiles {
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl Percentiles {
+    fn new(mut durations: Vec<Duration>) -> Self {
+        durations.sort_unstable();
+        let at = |p: usize| durations[(durations.len() - 1) * p / 100];
+        let total: Duration = durations.iter().sum();
+
+        Self {
+            mean: total / durations.len() as u32,
+            p50: at(50),
+            p95: at(95),
+            p99: at(99),
+            max: durations[durations.len() - 1],
+        }
+    }
+}
+
+/// Summary of the recorded frames.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatsSummary {
+    /// Frames per second, from the mean frame interval.
+    pub fps: f64,
+    pub interval: Percentiles,
+    pub update: Percentiles,
+    pub draw: Percentiles,
+    pub render: Percentiles,
+    /// Frames missed since the statistics were created.
+    pub dropped: u64,
+}
+
+/// Collects frame timings over a sliding window of frames.
+///
+/// A frame counts as dropped when the interval since the previous frame exceeds one and a half
+/// times the target frame time; a long stall counts as several dropped frames.
+#[derive(Debug)]
+pub struct FrameStats {
+    samples: VecDeque<FrameSample>,
+    capacity: usize,
+    target: Duration,
+    last_frame: Option<Instant>,
+    dropped: u64,
+}
+
+impl FrameStats {
+    /// Keep the last `capacity` frames, aiming for one frame every `target`.
+    pub fn new(capacity: usize, target: Duration) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+            target,
+            last_frame: None,
+            dropped: 0,
+        }
+    }
+
+    /// The recorded frames, oldest first.
+    pub fn samples(&self) -> impl Iterator<Item = &FrameSample> {
+        self.samples.iter()
+    }
+
+    /// Number of frames missed.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Record a frame presented at `now`.
+    pub fn record(&mut self, now: Instant, update: Duration, draw: Duration, render: Duration) {
+        let interval = match self.last_frame.replace(now) {
+            Some(last_frame) => now.saturating_duration_since(last_frame),
+            // The first frame has nothing to compare with
+            None => return,
+        };
+
+        if !self.target.is_zero() && interval > self.target * 3 / 2 {
+            let missed = interval.as_secs_f64() / self.target.as_secs_f64();
+            self.dropped += missed.round().max(2.0) as u64 - 1;
+        }
+
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(FrameSample {
+            interval,
+            update,
+            draw,
+            render,
+        });
+    }
+
+    /// Forget the previous frame time, e.g. after the app was suspended.
+    pub fn reset_interval(&mut self) {
+        self.last_frame = None;
+    }
+
+    /// Summarize the recorded frames. Returns `None` before two frames were recorded.
+    pub fn summary(&self) -> Option<StatsSummary> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let collect = |f: fn(&FrameSample) -> Duration| -> Vec<Duration> {
+            self.samples.iter().map(f).collect()
+        };
+
+        let interval = Percentiles::new(collect(|sample| sample.interval));
+        Some(StatsSummary {
+            fps: 1.0 / interval.mean.as_secs_f64().max(f64::EPSILON),
+            interval,
+            update: Percentiles::new(collect(|sample| sample.update)),
+            draw: Percentiles::new(collect(|sample| sample.draw)),
+            render: Percentiles::new(collect(|sample| sample.render)),
+            dropped: self.dropped,
+        })
+    }
+
+    /// Log a summary at info level.
+    pub fn log(&self) {
+        if let Some(summary) = self.summary() {
+            log::info!(
+                "{:.1} fps, frame {:.2?} (p95 {:.2?}, max {:.2?}), update {:.2?}, draw {:.2?}, render {:.2?}, {} dropped",
+                summary.fps,
+                summary.interval.mean,
+                summary.interval.p95,
+                summary.interval.max,
+                summary.update.mean,
+                summary.draw.mean,
+                summary.render.mean,
+                summary.dropped
+            );
+        }
+    }
+
+    /// Draw a bar graph of the recent frame intervals into the bottom left corner of `frame`.
+    ///
+    /// Each frame is one pixel wide bar; the horizontal line marks the target frame time. Bars
+    /// are green when on target, yellow up to twice the target and red beyond.
+    pub fn draw_overlay(&self, frame: &mut [u8], width: u32, height: u32) {
+        const GRAPH_HEIGHT: u32 = 32;
+        const TARGET_HEIGHT: u32 = 12;
+
+        let graph_width = (self.capacity as u32).min(width);
+        let graph_height = GRAPH_HEIGHT.min(height);
+        let top = (height - graph_height) as i32;
+        let bottom = height as i32;
+        let target = self.target.as_secs_f64().max(f64::EPSILON);
+
+        let mut canvas = Canvas::new(frame, width, height);
+        canvas.fill_rect(0, top, graph_width, graph_height, [0x00, 0x00, 0x00, 0xa0]);
+
+        let skip = self.samples.len().saturating_sub(graph_width as usize);
+        for (x, sample) in self.samples.iter().skip(skip).enumerate() {
+            let ratio = sample.interval.as_secs_f64() / target;
+            let bar = ((ratio * TARGET_HEIGHT as f64).round() as u32).clamp(1, graph_height);
+            let color = if ratio <= 1.05 {
+                [0x40, 0xe0, 0x40, 0xff]
+            } else if ratio <= 2.0 {
+                [0xe0, 0xe0, 0x40, 0xff]
+            } else {
+                [0xe0, 0x40, 0x40, 0xff]
+            };
+            canvas.fill_rect(x as i32, bottom - bar as i32, 1, bar, color);
+        }
+
+        if TARGET_HEIGHT < graph_height {
+            let y = bottom - TARGET_HEIGHT as i32;
+            canvas.fill_rect(0, y, graph_width, 1, [0xff, 0xff, 0xff, 0x80]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    /// Record frames `intervals` apart with fixed update, draw and render times.
+    fn record(stats: &mut FrameStats, intervals: &[u64]) {
+        let mut now = Instant::now();
+        stats.record(now, ms(1), ms(2), ms(3));
+        for &interval in intervals {
+            now += ms(interval);
+            stats.record(now, ms(1), ms(2), ms(3));
+        }
+    }
+
+    #[test]
+    fn percentiles() {
+        let percentiles = Percentiles::new((1..=100).rev().map(ms).collect());
+        assert_eq!(
+            percentiles,
+            Percentiles {
+                mean: Duration::from_micros(50_500),
+                p50: ms(50),
+                p95: ms(95),
+                p99: ms(99),
+                max: ms(100),
+            }
+        );
+
+        let single = Percentiles::new(vec![ms(7)]);
+        assert_eq!(
+            (single.mean, single.p50, single.p99, single.max),
+            (ms(7), ms(7), ms(7), ms(7))
+        );
+    }
+
+    #[test]
+    fn summary() {
+        let mut stats = FrameStats::new(10, ms(20));
+        assert!(stats.summary().is_none());
+        stats.record(Instant::now(), ms(1), ms(2), ms(3));
+        // The first frame only starts the interval
+        assert!(stats.summary().is_none());
+
+        let mut stats = FrameStats::new(10, ms(20));
+        record(&mut stats, &[20, 20, 20, 20]);
+        let summary = stats.summary().unwrap();
+        assert!((summary.fps - 50.0).abs() < 1e-6);
+        assert_eq!(summary.interval.p50, ms(20));
+        assert_eq!(summary.update.mean, ms(1));
+        assert_eq!(summary.draw.max, ms(2));
+        assert_eq!(summary.render.p95, ms(3));
+        assert_eq!(summary.dropped, 0);
+    }
+
+    #[test]
+    fn ring_buffer_keeps_the_latest_frames() {
+        let mut stats = FrameStats::new(3, ms(20));
+        record(&mut stats, &[10, 20, 30, 40, 50]);
+        let intervals: Vec<_> = stats.samples().map(|sample| sample.interval).collect();
+        assert_eq!(intervals, [ms(30), ms(40), ms(50)]);
+        assert_eq!(stats.summary().unwrap().interval.mean, ms(40));
+
+        // A capacity of zero still keeps the last frame
+        let mut stats = FrameStats::new(0, ms(20));
+        record(&mut stats, &[10, 20]);
+        assert_eq!(stats.samples().count(), 1);
+    }
+
+    #[test]
+    fn dropped_frames() {
+        let mut stats = FrameStats::new(10, ms(16));
+        // Up to one and a half target frames is on time
+        record(&mut stats, &[16, 24]);
+        assert_eq!(stats.dropped(), 0);
+
+        let mut stats = FrameStats::new(10, ms(16));
+        // 25ms is one late frame, 48ms two missed frames
+        record(&mut stats, &[25, 48]);
+        assert_eq!(stats.dropped(), 3);
+
+        // Suspensions don't count
+        stats.reset_interval();
+        stats.record(Instant::now() + ms(10_000), ms(0), ms(0), ms(0));
+        assert_eq!(stats.dropped(), 3);
+        assert_eq!(stats.samples().count(), 2);
+    }
+
+    #[test]
+    fn overlay() {
+        let (width, height) = (8, 40);
+        let mut frame = vec![0; width * height * 4];
+        let mut stats = FrameStats::new(4, ms(20));
+        record(&mut stats, &[20, 40, 100]);
+        stats.draw_overlay(&mut frame, width as u32, height as u32);
+
+        let pixel = |x: usize, y: usize| {
+            let i = (y * width + x) * 4;
+            [frame[i], frame[i + 1], frame[i + 2]]
+        };
+        let yellow = [0xe0, 0xe0, 0x40];
+        let red = [0xe0, 0x40, 0x40];
+        assert_eq!(pixel(0, height - 1), [0x40, 0xe0, 0x40]);
+        // Bars are 12 pixels per target frame time, capped at the graph height
+        assert_eq!(pixel(1, height - 1), yellow);
+        assert_eq!(pixel(1, height - 24), yellow);
+        assert_ne!(pixel(1, height - 25), yellow);
+        assert_eq!(pixel(2, height - 32), red);
+        assert_ne!(pixel(2, height - 33), red);
+        // Nothing is drawn right of the graph or above it
+        assert_eq!(pixel(4, height - 1), [0, 0, 0]);
+        assert_eq!(pixel(0, 0), [0, 0, 0]);
+    }
+}
@@ -0,0 +1,493 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+use winit::event::{ElementState, Ime, ModifiersState, VirtualKeyCode, WindowEvent};
+
+/// Text editing operation delivered to the app.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextInput {
+    /// Insert text at the cursor. Ends a running composition.
+    Commit(String),
+    /// Delete the character before the cursor.
+    Backspace,
+    /// Delete the character after the cursor.
+    Delete,
+    /// Replace the composition (preedit) text. An empty `text` ends the composition.
+    ///
+    /// `cursor` is the selected byte range within `text`, if any.
+    Preedit {
+        text: String,
+        cursor: Option<(usize, usize)>,
+    },
+    /// Move the cursor.
+    Cursor(CursorMove),
+}
+
+/// Direction of a [`TextInput::Cursor`] move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CursorMove {
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+}
+
+/// Editing key, independent of the windowing backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditKey {
+    Backspace,
+    Delete,
+    Enter,
+    Cursor(CursorMove),
+}
+
+/// Turns key presses, received characters and IME events into [`TextInput`] events.
+///
+/// Editing keys (backspace, delete, enter, arrows) are taken from key presses; the control
+/// characters some platforms additionally deliver for them are dropped so every edit is
+/// reported once. While the IME is composing, editing keys belong to the IME and are ignored.
+///
+/// Platforms that don't deliver characters, like Android's `NativeActivity`, can enable
+/// [`key_characters`](Self::key_characters) to derive text from the key codes instead.
+#[derive(Debug, Default)]
+pub struct TextInputState {
+    events: VecDeque<TextInput>,
+    preedit: String,
+    modifiers: ModifiersState,
+    key_characters: bool,
+}
+
+impl TextInputState {
+    /// Create a state machine that takes text only from received characters and the IME.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a state machine suited for the current platform.
+    pub fn for_platform() -> Self {
+        Self::new().key_characters(cfg!(target_os = "android"))
+    }
+
+    /// Derive committed text from printable key presses.
+    pub fn key_characters(mut self, enabled: bool) -> Self {
+        self.key_characters = enabled;
+        self
+    }
+
+    /// Whether the IME is currently composing text.
+    pub fn is_composing(&self) -> bool {
+        !self.preedit.is_empty()
+    }
+
+    /// The current composition text.
+    pub fn preedit(&self) -> &str {
+        &self.preedit
+    }
+
+    /// Take the pending events, oldest first.
+    pub fn drain(&mut self) -> impl Iterator<Item = TextInput> + '_ {
+        self.events.drain(..)
+    }
+
+    /// Feed a window event. Events unrelated to text input are ignored.
+    pub fn handle_window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = *modifiers,
+            WindowEvent::ReceivedCharacter(c) => self.character(*c),
+            WindowEvent::KeyboardInput { input, .. } => {
+                if input.state != ElementState::Pressed {
+                    return;
+                }
+                if let Some(key) = input.virtual_keycode {
+                    if let Some(edit_key) = edit_key(key) {
+                        self.key(edit_key);
+                    } else if self.key_characters {
+                        if let Some(c) = key_character(key, self.modifiers.shift()) {
+                            self.character(c);
+                        }
+                    }
+                }
+            }
+            WindowEvent::Ime(Ime::Preedit(text, cursor)) => self.compose(text, *cursor),
+            WindowEvent::Ime(Ime::Commit(text)) => self.commit(text),
+            WindowEvent::Ime(Ime::Disabled) => self.compose("", None),
+            _ => (),
+        }
+    }
+
+    /// Feed a pressed editing key.
+    pub fn key(&mut self, key: EditKey) {
+        if self.is_composing() {
+            return;
+        }
+
+        let event = match key {
+            EditKey::Backspace => TextInput::Backspace,
+            EditKey::Delete => TextInput::Delete,
+            EditKey::Enter => TextInput::Commit("\n".to_string()),
+            EditKey::Cursor(direction) => TextInput::Cursor(direction),
+        };
+        self.events.push_back(event);
+    }
+
+    /// Feed a received character. Control characters and shortcuts are ignored.
+    pub fn character(&mut self, c: char) {
+        if c.is_control() || self.modifiers.ctrl() || self.modifiers.logo() {
+            return;
+        }
+        self.commit(c.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Feed an IME composition update.
+    pub fn compose(&mut self, text: &str, cursor: Option<(usize, usize)>) {
+        if text == self.preedit {
+            return;
+        }
+
+        self.preedit.clear();
+        self.preedit.push_str(text);
+        self.events.push_back(TextInput::Preedit {
+            text: text.to_string(),
+            cursor,
+        });
+    }
+
+    /// Feed committed text. A running composition ends without a separate event.
+    pub fn commit(&mut self, text: &str) {
+        self.preedit.clear();
+        if !text.is_empty() {
+            self.events.push_back(TextInput::Commit(text.to_string()));
+        }
+    }
+}
+
+fn edit_key(key: VirtualKeyCode) -> Option<EditKey> {
+    use VirtualKeyCode::*;
+
+    let edit_key = match key {
+        Back => EditKey::Backspace,
+        Delete => EditKey::Delete,
+        Return | NumpadEnter => EditKey::Enter,
+        Left => EditKey::Cursor(CursorMove::Left),
+        Right => EditKey::Cursor(CursorMove::Right),
+        Up => EditKey::Cursor(CursorMove::Up),
+        Down => EditKey::Cursor(CursorMove::Down),
+        Home => EditKey::Cursor(CursorMove::Home),
+        End => EditKey::Cursor(CursorMove::End),
+        _ => return None,
+    };
+    Some(edit_key)
+}
+
+/// Character produced by `key` on a US layout.
+fn key_character(key: VirtualKeyCode, shift: bool) -> Option<char> {
+    use VirtualKeyCode::*;
+
+    let letter = match key {
+        A => Some('a'),
+        B => Some('b'),
+        C => Some('c'),
+        D => Some('d'),
+        E => Some('e'),
+        F => Some('f'),
+        G => Some('g'),
+        H => Some('h'),
+        I => Some('i'),
+        J => Some('j'),
+        K => Some('k'),
+        L => Some('l'),
+        M => Some('m'),
+        N => Some('n'),
+        O => Some('o'),
+        P => Some('p'),
+        Q => Some('q'),
+        R => Some('r'),
+        S => Some('s'),
+        T => Some('t'),
+        U => Some('u'),
+        V => Some('v'),
+        W => Some('w'),
+        X => Some('x'),
+        Y => Some('y'),
+        Z => Some('z'),
+        _ => None,
+    };
+    if let Some(letter) = letter {
+        return Some(if shift {
+            letter.to_ascii_uppercase()
+        } else {
+            letter
+        });
+    }
+
+    let (plain, shifted) = match key {
+        Key1 => ('1', '!'),
+        Key2 => ('2', '@'),
+        Key3 => ('3', '#'),
+        Key4 => ('4', '$'),
+        Key5 => ('5', '%'),
+        Key6 => ('6', '^'),
+        Key7 => ('7', '&'),
+        Key8 => ('8', '*'),
+        Key9 => ('9', '('),
+        Key0 => ('0', ')'),
+        Space => (' ', ' '),
+        Minus => ('-', '_'),
+        Equals => ('=', '+'),
+        LBracket => ('[', '{'),
+        RBracket => (']', '}'),
+        Backslash => ('\\', '|'),
+        Semicolon => (';', ':'),
+        Apostrophe => ('\'', '"'),
+        Grave => ('`', '~'),
+        Comma => (',', '<'),
+        Period => ('.', '>'),
+        Slash => ('/', '?'),
+        Plus => ('+', '+'),
+        At => ('@', '@'),
+        Colon => (':', ':'),
+        Asterisk => ('*', '*'),
+        Numpad0 => ('0', '0'),
+        Numpad1 => ('1', '1'),
+        Numpad2 => ('2', '2'),
+        Numpad3 => ('3', '3'),
+        Numpad4 => ('4', '4'),
+        Numpad5 => ('5', '5'),
+        Numpad6 => ('6', '6'),
+        Numpad7 => ('7', '7'),
+        Numpad8 => ('8', '8'),
+        Numpad9 => ('9', '9'),
+        NumpadAdd => ('+', '+'),
+        NumpadSubtract => ('-', '-'),
+        NumpadMultiply => ('*', '*'),
+        NumpadDivide => ('/', '/'),
+        NumpadDecimal => ('.', '.'),
+        _ => return None,
+    };
+    Some(if shift { shifted } else { plain })
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::event::{DeviceId, KeyboardInput};
+
+    use super::*;
+
+    fn key(state: ElementState, key: VirtualKeyCode) -> WindowEvent<'static> {
+        #[allow(deprecated)]
+        WindowEvent::KeyboardInput {
+            // SAFETY: the id is only compared, never passed to the platform
+            device_id: unsafe { DeviceId::dummy() },
+            input: KeyboardInput {
+                scancode: 0,
+                state,
+                virtual_keycode: Some(key),
+                modifiers: ModifiersState::empty(),
+            },
+            is_synthetic: false,
+        }
+    }
+
+    fn press(key_code: VirtualKeyCode) -> WindowEvent<'static> {
+        key(ElementState::Pressed, key_code)
+    }
+
+    fn preedit(text: &str, cursor: Option<(usize, usize)>) -> WindowEvent<'static> {
+        WindowEvent::Ime(Ime::Preedit(text.to_string(), cursor))
+    }
+
+    fn feed(state: &mut TextInputState, events: &[WindowEvent]) -> Vec<TextInput> {
+        for event in events {
+            state.handle_window_event(event);
+        }
+        state.drain().collect()
+    }
+
+    fn commit(text: &str) -> TextInput {
+        TextInput::Commit(text.to_string())
+    }
+
+    #[test]
+    fn commit_characters_and_ime_text() {
+        let mut state = TextInputState::new();
+        let events = [
+            WindowEvent::ReceivedCharacter('a'),
+            WindowEvent::Ime(Ime::Commit("bc".to_string())),
+            WindowEvent::Ime(Ime::Commit(String::new())),
+        ];
+        assert_eq!(feed(&mut state, &events), [commit("a"), commit("bc")]);
+    }
+
+    #[test]
+    fn control_characters_and_shortcuts_are_dropped() {
+        let mut state = TextInputState::new();
+        let events = [
+            press(VirtualKeyCode::Back),
+            // Delivered alongside the key press on some platforms
+            WindowEvent::ReceivedCharacter('\u{8}'),
+            WindowEvent::ModifiersChanged(ModifiersState::CTRL),
+            WindowEvent::ReceivedCharacter('c'),
+        ];
+        assert_eq!(feed(&mut state, &events), [TextInput::Backspace]);
+    }
+
+    #[test]
+    fn backspace_on_empty_buffer() {
+        // The state machine has no buffer; the app ignores edits that don't apply
+        let mut state = TextInputState::new();
+        let events = [press(VirtualKeyCode::Back), press(VirtualKeyCode::Delete)];
+        assert_eq!(
+            feed(&mut state, &events),
+            [TextInput::Backspace, TextInput::Delete]
+        );
+    }
+
+    #[test]
+    fn key_releases_are_ignored() {
+        let mut state = TextInputState::new().key_characters(true);
+        let events = [
+            key(ElementState::Released, VirtualKeyCode::Back),
+            key(ElementState::Released, VirtualKeyCode::A),
+        ];
+        assert_eq!(feed(&mut state, &events), []);
+    }
+
+    #[test]
+    fn cursor_moves() {
+        // Moves are reported at any position, including past the ends of the text; the app
+        // clamps them to its buffer
+        let mut state = TextInputState::new();
+        let events = [
+            press(VirtualKeyCode::Home),
+            press(VirtualKeyCode::Left),
+            press(VirtualKeyCode::Up),
+            press(VirtualKeyCode::End),
+            press(VirtualKeyCode::Right),
+            press(VirtualKeyCode::Down),
+        ];
+        assert_eq!(
+            feed(&mut state, &events),
+            [
+                TextInput::Cursor(CursorMove::Home),
+                TextInput::Cursor(CursorMove::Left),
+                TextInput::Cursor(CursorMove::Up),
+                TextInput::Cursor(CursorMove::End),
+                TextInput::Cursor(CursorMove::Right),
+                TextInput::Cursor(CursorMove::Down),
+            ]
+        );
+    }
+
+    #[test]
+    fn preedit_start_update_commit() {
+        let mut state = TextInputState::new();
+        let events = [
+            WindowEvent::Ime(Ime::Enabled),
+            preedit("k", Some((1, 1))),
+            // Repeated updates without a change are reported once
+            preedit("k", Some((1, 1))),
+            preedit("ka", Some((2, 2))),
+        ];
+        assert_eq!(
+            feed(&mut state, &events),
+            [
+                TextInput::Preedit {
+                    text: "k".to_string(),
+                    cursor: Some((1, 1))
+                },
+                TextInput::Preedit {
+                    text: "ka".to_string(),
+                    cursor: Some((2, 2))
+                },
+            ]
+        );
+        assert!(state.is_composing());
+        assert_eq!(state.preedit(), "ka");
+
+        let events = [WindowEvent::Ime(Ime::Commit("か".to_string()))];
+        assert_eq!(feed(&mut state, &events), [commit("か")]);
+        assert!(!state.is_composing());
+    }
+
+    #[test]
+    fn preedit_cancel() {
+        let mut state = TextInputState::new();
+        feed(&mut state, &[preedit("ka", None)]);
+
+        let events = [preedit("", None)];
+        assert_eq!(
+            feed(&mut state, &events),
+            [TextInput::Preedit {
+                text: String::new(),
+                cursor: None
+            }]
+        );
+        assert!(!state.is_composing());
+
+        // Disabling the IME mid-composition cancels it as well
+        feed(&mut state, &[preedit("ka", None)]);
+        let events = [WindowEvent::Ime(Ime::Disabled)];
+        assert_eq!(
+            feed(&mut state, &events),
+            [TextInput::Preedit {
+                text: String::new(),
+                cursor: None
+            }]
+        );
+    }
+
+    #[test]
+    fn mixed_key_and_ime_order() {
+        let mut state = TextInputState::new();
+        let events = [
+            WindowEvent::ReceivedCharacter('a'),
+            preedit("k", None),
+            // Editing keys belong to the IME while composing
+            press(VirtualKeyCode::Back),
+            press(VirtualKeyCode::Left),
+            WindowEvent::Ime(Ime::Commit("か".to_string())),
+            press(VirtualKeyCode::Back),
+            press(VirtualKeyCode::Return),
+            WindowEvent::ReceivedCharacter('b'),
+        ];
+        assert_eq!(
+            feed(&mut state, &events),
+            [
+                commit("a"),
+                TextInput::Preedit {
+                    text: "k".to_string(),
+                    cursor: None
+                },
+                commit("か"),
+                TextInput::Backspace,
+                commit("\n"),
+                commit("b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn key_characters() {
+        let mut state = TextInputState::new().key_characters(true);
+        let events = [
+            press(VirtualKeyCode::H),
+            WindowEvent::ModifiersChanged(ModifiersState::SHIFT),
+            press(VirtualKeyCode::I),
+            press(VirtualKeyCode::Key1),
+            WindowEvent::ModifiersChanged(ModifiersState::empty()),
+            press(VirtualKeyCode::Space),
+            // Not a printable key
+            press(VirtualKeyCode::Escape),
+        ];
+        assert_eq!(
+            feed(&mut state, &events),
+            [commit("h"), commit("I"), commit("!"), commit(" ")]
+        );
+
+        // Disabled by default
+        let mut state = TextInputState::new();
+        assert_eq!(feed(&mut state, &[press(VirtualKeyCode::H)]), []);
+    }
+}
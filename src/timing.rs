@@ -0,0 +1,165 @@
+use std::time::{Duration, Instant};
+
+/// How the event loop waits between frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlFlowPolicy {
+    /// Run continuously and redraw as often as possible. Smoothest, but keeps the CPU busy.
+    Poll,
+    /// Sleep until the next window event. Updates and redraws only happen in response to input.
+    Wait,
+    /// Sleep until the next update tick is due, then update and redraw.
+    WaitUntilNextFrame,
+}
+
+/// Scheduler running updates at a fixed rate, independent of the display refresh rate.
+///
+/// Each frame, [`advance`](Self::advance) returns how many ticks have elapsed. The time left
+/// over is exposed as [`alpha`](Self::alpha), the fraction of a tick to interpolate by when
+/// drawing. After a long stall at most `max_steps` ticks are run and the rest is dropped, so a
+/// slow device falls behind instead of spiraling into ever longer frames.
+#[derive(Debug, Clone)]
+pub struct FixedTimestep {
+    tick: Duration,
+    max_steps: u32,
+    accumulator: Duration,
+    last: Option<Instant>,
+}
+
+impl FixedTimestep {
+    /// Run `tick_rate` updates per second, at most `max_steps` per frame.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tick_rate` or `max_steps` is zero.
+    pub fn new(tick_rate: u32, max_steps: u32) -> Self {
+        assert!(tick_rate > 0, "tick rate must not be zero");
+        assert!(max_steps > 0, "max steps must not be zero");
+
+        Self {
+            tick: Duration::from_secs(1) / tick_rate,
+            max_steps,
+            accumulator: Duration::ZERO,
+            last: None,
+        }
+    }
+
+    /// Duration of one tick.
+    pub fn tick(&self) -> Duration {
+        self.tick
+    }
+
+    /// Add the time passed since the last call and return the number of ticks to run.
+    ///
+    /// The first call after creation or [`reset`](Self::reset) runs one tick.
+    pub fn advance(&mut self, now: Instant) -> u32 {
+        let elapsed = match self.last {
+            Some(last) => now.saturating_duration_since(last),
+            None => self.tick,
+        };
+        self.last = Some(now);
+        self.accumulator += elapsed;
+
+        let mut steps = 0;
+        while self.accumulator >= self.tick && steps < self.max_steps {
+            self.accumulator -= self.tick;
+            steps += 1;
+        }
+        if self.accumulator >= self.tick {
+            // Drop the time we can't catch up with, keeping the progress into the next tick
+            let remainder = self.accumulator.as_nanos() % self.tick.as_nanos();
+            self.accumulator = Duration::from_nanos(remainder as u64);
+        }
+        steps
+    }
+
+    /// Progress towards the next tick, in `0.0..1.0`.
+    pub fn alpha(&self) -> f64 {
+        self.accumulator.as_secs_f64() / self.tick.as_secs_f64()
+    }
+
+    /// Time at which the next tick is due.
+    pub fn next_tick(&self, now: Instant) -> Instant {
+        now + (self.tick - self.accumulator)
+    }
+
+    /// Forget the elapsed time, e.g. after the app was suspended.
+    pub fn reset(&mut self) {
+        self.accumulator = Duration::ZERO;
+        self.last = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn first_advance_runs_one_tick() {
+        let mut timestep = FixedTimestep::new(50, 5);
+        assert_eq!(timestep.tick(), ms(20));
+        assert_eq!(timestep.advance(Instant::now()), 1);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn accumulates_partial_ticks() {
+        let start = Instant::now();
+        let mut timestep = FixedTimestep::new(50, 5);
+        timestep.advance(start);
+
+        assert_eq!(timestep.advance(start + ms(15)), 0);
+        assert!((timestep.alpha() - 0.75).abs() < 1e-9);
+        assert_eq!(timestep.next_tick(start + ms(15)), start + ms(20));
+
+        // 15ms + 15ms is one tick with 10ms left over
+        assert_eq!(timestep.advance(start + ms(30)), 1);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-9);
+
+        assert_eq!(timestep.advance(start + ms(70)), 2);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn catch_up_is_clamped() {
+        let start = Instant::now();
+        let mut timestep = FixedTimestep::new(50, 5);
+        timestep.advance(start);
+
+        // A 1s stall is 50 ticks, only 5 run and the rest is dropped
+        assert_eq!(timestep.advance(start + ms(1005)), 5);
+        assert!((timestep.alpha() - 0.25).abs() < 1e-9);
+        assert_eq!(timestep.advance(start + ms(1020)), 1);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn time_going_backwards_runs_nothing() {
+        let start = Instant::now() + ms(100);
+        let mut timestep = FixedTimestep::new(50, 5);
+        timestep.advance(start);
+        assert_eq!(timestep.advance(start - ms(50)), 0);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn reset() {
+        let start = Instant::now();
+        let mut timestep = FixedTimestep::new(50, 5);
+        timestep.advance(start);
+        timestep.advance(start + ms(10));
+        timestep.reset();
+        assert_eq!(timestep.alpha(), 0.0);
+        // Like the first advance, regardless of the time passed
+        assert_eq!(timestep.advance(start + ms(10_000)), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "tick rate must not be zero")]
+    fn zero_tick_rate() {
+        FixedTimestep::new(0, 5);
+    }
+}
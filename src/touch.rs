@@ -0,0 +1,276 @@
+use std::time::Instant;
+
+use winit::dpi::PhysicalPosition;
+use winit::event::{Touch, TouchPhase, WindowEvent};
+
+use crate::viewport::Viewport;
+
+/// State of one finger on the screen. Positions are in buffer coordinates and may lie outside
+/// of the buffer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pointer {
+    /// Touch id assigned by the platform, unique while the finger is down.
+    pub id: u64,
+    /// Position where the finger went down.
+    pub start: (f64, f64),
+    /// Latest position.
+    pub position: (f64, f64),
+    /// Smoothed velocity in buffer pixels per second.
+    pub velocity: (f64, f64),
+    /// Time the finger went down.
+    pub start_time: Instant,
+    /// Time of the latest update.
+    pub last_time: Instant,
+}
+
+impl Pointer {
+    /// Offset from the start position.
+    pub fn delta(&self) -> (f64, f64) {
+        (
+            self.position.0 - self.start.0,
+            self.position.1 - self.start.1,
+        )
+    }
+}
+
+/// Tracks all fingers on the screen, keyed by winit's touch id.
+///
+/// Feed it touch events as they arrive and hand it to the app once per frame. Pointers lifted or
+/// cancelled since the last frame are kept in [`released`](Self::released) and
+/// [`cancelled`](Self::cancelled) until [`end_frame`](Self::end_frame).
+#[derive(Debug, Default)]
+pub struct TouchTracker {
+    active: Vec<Pointer>,
+    released: Vec<Pointer>,
+    cancelled: Vec<Pointer>,
+}
+
+impl TouchTracker {
+    /// Weight of the newest sample in the velocity estimate.
+    const VELOCITY_SMOOTHING: f64 = 0.5;
+
+    /// Fingers currently on the screen, in the order they went down.
+    pub fn pointers(&self) -> &[Pointer] {
+        &self.active
+    }
+
+    /// The active pointer with `id`.
+    pub fn get(&self, id: u64) -> Option<&Pointer> {
+        self.active.iter().find(|pointer| pointer.id == id)
+    }
+
+    /// Pointers lifted since the last frame.
+    pub fn released(&self) -> &[Pointer] {
+        &self.released
+    }
+
+    /// Pointers cancelled by the system since the last frame, e.g. because a system gesture took
+    /// over. Their movement should not be acted upon.
+    pub fn cancelled(&self) -> &[Pointer] {
+        &self.cancelled
+    }
+
+    /// Feed a window event. Events other than touches are ignored.
+    pub fn handle_window_event(&mut self, event: &WindowEvent, viewport: &Viewport, now: Instant) {
+        if let WindowEvent::Touch(Touch {
+            id,
+            phase,
+            location,
+            ..
+        }) = event
+        {
+            self.handle_touch(*id, *phase, *location, viewport, now);
+        }
+    }
+
+    /// Feed a touch at a physical window position.
+    pub fn handle_touch(
+        &mut self,
+        id: u64,
+        phase: TouchPhase,
+        location: PhysicalPosition<f64>,
+        viewport: &Viewport,
+        now: Instant,
+    ) {
+        let position = viewport.to_buffer_unclamped(location);
+        let index = self.active.iter().position(|pointer| pointer.id == id);
+
+        match (phase, index) {
+            (TouchPhase::Started, _) | (TouchPhase::Moved, None) => {
+                if let Some(index) = index {
+                    // The end of the previous touch with this id got lost
+                    self.cancelled.push(self.active.remove(index));
+                }
+                self.active.push(Pointer {
+                    id,
+                    start: position,
+                    position,
+                    velocity: (0.0, 0.0),
+                    start_time: now,
+                    last_time: now,
+                });
+            }
+            (TouchPhase::Moved, Some(index)) => {
+                Self::move_pointer(&mut self.active[index], position, now)
+            }
+            (TouchPhase::Ended, Some(index)) => {
+                let mut pointer = self.active.remove(index);
+                Self::move_pointer(&mut pointer, position, now);
+                self.released.push(pointer);
+            }
+            (TouchPhase::Cancelled, Some(index)) => {
+                self.cancelled.push(self.active.remove(index));
+            }
+            (TouchPhase::Ended | TouchPhase::Cancelled, None) => (),
+        }
+    }
+
+    /// Forget the pointers released or cancelled during the frame.
+    pub fn end_frame(&mut self) {
+        self.released.clear();
+        self.cancelled.clear();
+    }
+
+    /// Drop all pointers, e.g. when the app is suspended.
+    pub fn clear(&mut self) {
+        self.cancelled.append(&mut self.active);
+    }
+
+    fn move_pointer(pointer: &mut Pointer, position: (f64, f64), now: Instant) {
+        let dt = now
+            .saturating_duration_since(pointer.last_time)
+            .as_secs_f64();
+        if dt > 0.0 {
+            let velocity = (
+                (position.0 - pointer.position.0) / dt,
+                (position.1 - pointer.position.1) / dt,
+            );
+            let a = Self::VELOCITY_SMOOTHING;
+            pointer.velocity = (
+                pointer.velocity.0 * (1.0 - a) + velocity.0 * a,
+                pointer.velocity.1 * (1.0 - a) + velocity.1 * a,
+            );
+        }
+        pointer.position = position;
+        pointer.last_time = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use winit::dpi::PhysicalSize;
+
+    use super::*;
+    use crate::viewport::ScalingMode;
+
+    struct Touches {
+        tracker: TouchTracker,
+        viewport: Viewport,
+        start: Instant,
+    }
+
+    impl Touches {
+        /// A tracker for a 100x100 buffer shown at twice its size.
+        fn new() -> Self {
+            Self {
+                tracker: TouchTracker::default(),
+                viewport: Viewport::new(
+                    ScalingMode::Integer,
+                    PhysicalSize::new(100, 100),
+                    PhysicalSize::new(200, 200),
+                ),
+                start: Instant::now(),
+            }
+        }
+
+        fn touch(&mut self, id: u64, phase: TouchPhase, x: f64, y: f64, millis: u64) {
+            let now = self.start + Duration::from_millis(millis);
+            let location = PhysicalPosition::new(x, y);
+            self.tracker
+                .handle_touch(id, phase, location, &self.viewport, now);
+        }
+
+        fn ids(pointers: &[Pointer]) -> Vec<u64> {
+            pointers.iter().map(|pointer| pointer.id).collect()
+        }
+    }
+
+    #[test]
+    fn tracks_fingers() {
+        let mut touches = Touches::new();
+        touches.touch(1, TouchPhase::Started, 20.0, 40.0, 0);
+        touches.touch(2, TouchPhase::Started, 100.0, 100.0, 0);
+        touches.touch(1, TouchPhase::Moved, 40.0, 40.0, 100);
+
+        let tracker = &touches.tracker;
+        assert_eq!(Touches::ids(tracker.pointers()), [1, 2]);
+        let pointer = tracker.get(1).unwrap();
+        assert_eq!(pointer.start, (10.0, 20.0));
+        assert_eq!(pointer.position, (20.0, 20.0));
+        assert_eq!(pointer.delta(), (10.0, 0.0));
+        // 10 buffer pixels in 0.1s, smoothed from zero
+        assert!((pointer.velocity.0 - 50.0).abs() < 1e-6);
+        assert_eq!(pointer.velocity.1, 0.0);
+
+        touches.touch(1, TouchPhase::Ended, 60.0, 40.0, 200);
+        assert_eq!(Touches::ids(touches.tracker.pointers()), [2]);
+        assert_eq!(touches.tracker.released()[0].position, (30.0, 20.0));
+        touches.tracker.end_frame();
+        assert!(touches.tracker.released().is_empty());
+    }
+
+    #[test]
+    fn cancel() {
+        let mut touches = Touches::new();
+        touches.touch(1, TouchPhase::Started, 20.0, 40.0, 0);
+        touches.touch(1, TouchPhase::Cancelled, 30.0, 40.0, 10);
+        assert!(touches.tracker.pointers().is_empty());
+        assert!(touches.tracker.released().is_empty());
+        assert_eq!(Touches::ids(touches.tracker.cancelled()), [1]);
+        // Cancelled pointers keep their last position
+        assert_eq!(touches.tracker.cancelled()[0].position, (10.0, 20.0));
+
+        // Ends and cancels of unknown ids are ignored
+        touches.touch(7, TouchPhase::Ended, 0.0, 0.0, 20);
+        touches.touch(7, TouchPhase::Cancelled, 0.0, 0.0, 20);
+        assert_eq!(Touches::ids(touches.tracker.cancelled()), [1]);
+        assert!(touches.tracker.released().is_empty());
+
+        touches.touch(2, TouchPhase::Started, 0.0, 0.0, 30);
+        touches.tracker.clear();
+        assert!(touches.tracker.pointers().is_empty());
+        assert_eq!(Touches::ids(touches.tracker.cancelled()), [1, 2]);
+    }
+
+    #[test]
+    fn id_reuse() {
+        let mut touches = Touches::new();
+        touches.touch(1, TouchPhase::Started, 20.0, 20.0, 0);
+        touches.touch(1, TouchPhase::Ended, 20.0, 20.0, 10);
+        // The platform reuses the id for the next finger
+        touches.touch(1, TouchPhase::Started, 100.0, 100.0, 20);
+        let pointer = touches.tracker.get(1).unwrap();
+        assert_eq!(pointer.start, (50.0, 50.0));
+        assert_eq!(pointer.velocity, (0.0, 0.0));
+        assert_eq!(Touches::ids(touches.tracker.released()), [1]);
+
+        // A start for an id that is still down cancels the old pointer
+        touches.touch(1, TouchPhase::Started, 0.0, 0.0, 30);
+        assert_eq!(Touches::ids(touches.tracker.pointers()), [1]);
+        assert_eq!(touches.tracker.get(1).unwrap().start, (0.0, 0.0));
+        assert_eq!(touches.tracker.cancelled()[0].start, (50.0, 50.0));
+
+        // A move for an unknown id starts a pointer, e.g. after the start was lost
+        touches.touch(3, TouchPhase::Moved, 40.0, 60.0, 40);
+        assert_eq!(touches.tracker.get(3).unwrap().start, (20.0, 30.0));
+    }
+
+    #[test]
+    fn positions_outside_the_buffer() {
+        let mut touches = Touches::new();
+        touches.touch(1, TouchPhase::Started, -20.0, 260.0, 0);
+        assert_eq!(touches.tracker.get(1).unwrap().position, (-10.0, 130.0));
+    }
+}
@@ -0,0 +1,328 @@
+use serde::{Deserialize, Serialize};
+use winit::dpi::{LogicalPosition, PhysicalPosition, PhysicalSize};
+
+/// How the pixel buffer is scaled to the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ScalingMode {
+    /// The largest integer scale that fits, so every buffer pixel is the same size. The rest of
+    /// the window is letterboxed.
+    #[default]
+    Integer,
+    /// The largest scale that fits, keeping the aspect ratio. Pixels may differ in size by one
+    /// window pixel.
+    Fit,
+    /// The smallest scale that covers the whole window, keeping the aspect ratio. The edges of
+    /// the buffer that don't fit are cropped.
+    Fill,
+    /// Scale each axis to the window, distorting the aspect ratio.
+    Stretch,
+}
+
+/// Area of the window the pixel buffer is drawn to.
+///
+/// Converts between physical window coordinates, as reported by touch and cursor events, and
+/// buffer coordinates, where `(0.0, 0.0)` is the top left corner of the top left pixel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    /// Left edge of the buffer in physical window pixels. Negative if the buffer is cropped.
+    pub x: f64,
+    /// Top edge of the buffer in physical window pixels. Negative if the buffer is cropped.
+    pub y: f64,
+    /// Width of the scaled buffer in physical window pixels.
+    pub width: f64,
+    /// Height of the scaled buffer in physical window pixels.
+    pub height: f64,
+    /// Size of the window in physical pixels.
+    pub window_size: PhysicalSize<u32>,
+    /// Size of the pixel buffer.
+    pub buffer_size: PhysicalSize<u32>,
+}
+
+impl Viewport {
+    /// The viewport of the buffer in a window of `window_size` with the given scaling mode.
+    pub fn new(
+        mode: ScalingMode,
+        buffer_size: PhysicalSize<u32>,
+        window_size: PhysicalSize<u32>,
+    ) -> Self {
+        let width_ratio = window_size.width as f64 / buffer_size.width as f64;
+        let height_ratio = window_size.height as f64 / buffer_size.height as f64;
+
+        match mode {
+            ScalingMode::Integer => Self::integer(buffer_size, window_size),
+            ScalingMode::Fit => {
+                let scale = width_ratio.min(height_ratio);
+                Self::centered(buffer_size, window_size, scale, scale)
+            }
+            ScalingMode::Fill => {
+                let scale = width_ratio.max(height_ratio);
+                Self::centered(buffer_size, window_size, scale, scale)
+            }
+            ScalingMode::Stretch => {
+                Self::centered(buffer_size, window_size, width_ratio, height_ratio)
+            }
+        }
+    }
+
+    /// The viewport `Pixels` uses by default: the largest integer scale that fits the window,
+    /// but at least 1, centered.
+    pub fn integer(buffer_size: PhysicalSize<u32>, window_size: PhysicalSize<u32>) -> Self {
+        let (buffer_width, buffer_height) = (buffer_size.width as f64, buffer_size.height as f64);
+        let (window_width, window_height) = (window_size.width as f64, window_size.height as f64);
+
+        let width_ratio = (window_width / buffer_width).max(1.0);
+        let height_ratio = (window_height / buffer_height).max(1.0);
+        let scale = width_ratio.min(height_ratio).floor();
+
+        Self::centered(buffer_size, window_size, scale, scale)
+    }
+
+    /// A viewport scaling the buffer by `scale_x`/`scale_y`, centered in the window.
+    pub fn centered(
+        buffer_size: PhysicalSize<u32>,
+        window_size: PhysicalSize<u32>,
+        scale_x: f64,
+        scale_y: f64,
+    ) -> Self {
+        let width = buffer_size.width as f64 * scale_x;
+        let height = buffer_size.height as f64 * scale_y;
+
+        Self {
+            x: (window_size.width as f64 - width) / 2.0,
+            y: (window_size.height as f64 - height) / 2.0,
+            width,
+            height,
+            window_size,
+            buffer_size,
+        }
+    }
+
+    /// Horizontal and vertical size of a buffer pixel in physical window pixels.
+    pub fn scale(&self) -> (f64, f64) {
+        (
+            self.width / self.buffer_size.width as f64,
+            self.height / self.buffer_size.height as f64,
+        )
+    }
+
+    /// Convert a physical window position to buffer coordinates.
+    ///
+    /// Returns `None` if the position is outside of the buffer, e.g. in the letterbox.
+    pub fn to_buffer(&self, position: PhysicalPosition<f64>) -> Option<(f64, f64)> {
+        let (x, y) = self.to_buffer_unclamped(position);
+        let inside = x >= 0.0
+            && y >= 0.0
+            && x < self.buffer_size.width as f64
+            && y < self.buffer_size.height as f64;
+        inside.then_some((x, y))
+    }
+
+    /// Convert a physical window position to buffer coordinates, even if it's outside of the
+    /// buffer.
+    pub fn to_buffer_unclamped(&self, position: PhysicalPosition<f64>) -> (f64, f64) {
+        let (scale_x, scale_y) = self.scale();
+        (
+            (position.x - self.x) / scale_x,
+            (position.y - self.y) / scale_y,
+        )
+    }
+
+    /// Convert a logical window position to buffer coordinates, given the window's DPI scale
+    /// factor.
+    pub fn logical_to_buffer(
+        &self,
+        position: LogicalPosition<f64>,
+        scale_factor: f64,
+    ) -> Option<(f64, f64)> {
+        self.to_buffer(position.to_physical(scale_factor))
+    }
+
+    /// The buffer pixel at a physical window position.
+    pub fn to_pixel(&self, position: PhysicalPosition<f64>) -> Option<(u32, u32)> {
+        self.to_buffer(position)
+            .map(|(x, y)| (x.floor() as u32, y.floor() as u32))
+    }
+
+    /// Convert buffer coordinates to a physical window position.
+    pub fn to_window(&self, x: f64, y: f64) -> PhysicalPosition<f64> {
+        let (scale_x, scale_y) = self.scale();
+        PhysicalPosition::new(self.x + x * scale_x, self.y + y * scale_y)
+    }
+
+    /// Physical window position of the center of the buffer pixel at `(x, y)`.
+    pub fn pixel_to_window(&self, x: u32, y: u32) -> PhysicalPosition<f64> {
+        self.to_window(x as f64 + 0.5, y as f64 + 0.5)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUFFER: PhysicalSize<u32> = PhysicalSize::new(320, 240);
+
+    fn assert_close(actual: (f64, f64), expected: (f64, f64)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-9 && (actual.1 - expected.1).abs() < 1e-9,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    fn position(x: f64, y: f64) -> PhysicalPosition<f64> {
+        PhysicalPosition::new(x, y)
+    }
+
+    #[test]
+    fn integer_portrait() {
+        let viewport = Viewport::integer(BUFFER, PhysicalSize::new(1080, 2400));
+        assert_eq!(viewport.scale(), (3.0, 3.0));
+        assert_eq!(
+            (viewport.x, viewport.y, viewport.width, viewport.height),
+            (60.0, 840.0, 960.0, 720.0)
+        );
+
+        assert_eq!(viewport.to_buffer(position(60.0, 840.0)), Some((0.0, 0.0)));
+        assert_eq!(
+            viewport.to_pixel(position(1019.9, 1559.9)),
+            Some((319, 239))
+        );
+        // Letterbox on each side of the buffer
+        assert_eq!(viewport.to_buffer(position(30.0, 1000.0)), None);
+        assert_eq!(viewport.to_buffer(position(1050.0, 1000.0)), None);
+        assert_eq!(viewport.to_buffer(position(500.0, 100.0)), None);
+        assert_eq!(viewport.to_buffer(position(500.0, 2000.0)), None);
+        // The right and bottom edges are outside
+        assert_eq!(viewport.to_buffer(position(1020.0, 1000.0)), None);
+        assert_eq!(viewport.to_buffer(position(500.0, 1560.0)), None);
+        // Still mapped for gestures that leave the buffer
+        assert_close(
+            viewport.to_buffer_unclamped(position(30.0, 100.0)),
+            (-10.0, -740.0 / 3.0),
+        );
+    }
+
+    #[test]
+    fn integer_landscape() {
+        let viewport = Viewport::integer(BUFFER, PhysicalSize::new(2400, 1080));
+        assert_eq!(viewport.scale(), (4.0, 4.0));
+        assert_eq!(
+            (viewport.x, viewport.y, viewport.width, viewport.height),
+            (560.0, 60.0, 1280.0, 960.0)
+        );
+        assert_eq!(viewport.to_pixel(position(562.0, 62.0)), Some((0, 0)));
+        assert_eq!(viewport.to_buffer(position(100.0, 500.0)), None);
+        assert_eq!(viewport.to_buffer(position(1000.0, 1050.0)), None);
+    }
+
+    #[test]
+    fn integer_scale_is_at_least_one() {
+        let viewport = Viewport::integer(BUFFER, PhysicalSize::new(200, 100));
+        assert_eq!(viewport.scale(), (1.0, 1.0));
+        assert_eq!((viewport.x, viewport.y), (-60.0, -70.0));
+    }
+
+    #[test]
+    fn round_trip() {
+        for window_size in [PhysicalSize::new(1080, 2400), PhysicalSize::new(2400, 1080)] {
+            let viewport = Viewport::integer(BUFFER, window_size);
+            for (x, y) in [
+                (0, 0),
+                (319, 0),
+                (0, 239),
+                (319, 239),
+                (160, 120),
+                (17, 201),
+            ] {
+                let window = viewport.pixel_to_window(x, y);
+                assert_eq!(viewport.to_pixel(window), Some((x, y)), "{:?}", window_size);
+            }
+            for (x, y) in [(0.0, 0.0), (0.25, 239.75), (123.5, 45.125)] {
+                let window = viewport.to_window(x, y);
+                assert_close(viewport.to_buffer(window).unwrap(), (x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn logical_positions() {
+        let viewport = Viewport::integer(BUFFER, PhysicalSize::new(1080, 2400));
+        // Common Android densities, none of them integers
+        for scale_factor in [1.5, 2.625, 2.75, 3.5] {
+            for (x, y) in [(0.5, 0.5), (1.0, 1.0), (160.5, 120.5), (319.5, 239.5)] {
+                let logical = viewport.to_window(x, y).to_logical::<f64>(scale_factor);
+                assert_close(
+                    viewport.logical_to_buffer(logical, scale_factor).unwrap(),
+                    (x, y),
+                );
+            }
+            // The letterbox stays outside at any density
+            let letterbox = LogicalPosition::new(10.0 / scale_factor, 10.0 / scale_factor);
+            assert_eq!(viewport.logical_to_buffer(letterbox, scale_factor), None);
+        }
+
+        let logical = LogicalPosition::new(100.0, 400.0);
+        assert_close(
+            viewport.logical_to_buffer(logical, 2.625).unwrap(),
+            (67.5, 70.0),
+        );
+    }
+
+    fn rect(viewport: &Viewport) -> (f64, f64, f64, f64) {
+        (viewport.x, viewport.y, viewport.width, viewport.height)
+    }
+
+    #[test]
+    fn scaling_mode_rects() {
+        let portrait = PhysicalSize::new(1080, 2400);
+        let landscape = PhysicalSize::new(2400, 1080);
+        let cases = [
+            (ScalingMode::Integer, portrait, (60.0, 840.0, 960.0, 720.0)),
+            (ScalingMode::Fit, portrait, (0.0, 795.0, 1080.0, 810.0)),
+            (ScalingMode::Fill, portrait, (-1060.0, 0.0, 3200.0, 2400.0)),
+            (ScalingMode::Stretch, portrait, (0.0, 0.0, 1080.0, 2400.0)),
+            (
+                ScalingMode::Integer,
+                landscape,
+                (560.0, 60.0, 1280.0, 960.0),
+            ),
+            (ScalingMode::Fit, landscape, (480.0, 0.0, 1440.0, 1080.0)),
+            (ScalingMode::Fill, landscape, (0.0, -360.0, 2400.0, 1800.0)),
+            (ScalingMode::Stretch, landscape, (0.0, 0.0, 2400.0, 1080.0)),
+        ];
+        for (mode, window_size, expected) in cases {
+            let viewport = Viewport::new(mode, BUFFER, window_size);
+            assert_eq!(rect(&viewport), expected, "{:?} in {:?}", mode, window_size);
+        }
+    }
+
+    #[test]
+    fn scaling_mode_mapping() {
+        let window_size = PhysicalSize::new(1080, 2400);
+
+        let fit = Viewport::new(ScalingMode::Fit, BUFFER, window_size);
+        assert_eq!(fit.to_buffer(position(0.0, 795.0)), Some((0.0, 0.0)));
+        assert_eq!(fit.to_buffer(position(540.0, 700.0)), None);
+
+        // The whole window is covered; the cropped edges map outside of the buffer
+        let fill = Viewport::new(ScalingMode::Fill, BUFFER, window_size);
+        assert_eq!(fill.to_buffer(position(0.0, 0.0)), Some((106.0, 0.0)));
+        assert_eq!(
+            fill.to_buffer(position(1079.0, 2399.0)),
+            Some((213.9, 239.9))
+        );
+
+        let stretch = Viewport::new(ScalingMode::Stretch, BUFFER, window_size);
+        assert_eq!(stretch.scale(), (3.375, 10.0));
+        assert_eq!(stretch.to_pixel(position(1079.0, 2399.0)), Some((319, 239)));
+    }
+
+    #[test]
+    fn empty_window() {
+        for mode in [ScalingMode::Fit, ScalingMode::Fill, ScalingMode::Stretch] {
+            let viewport = Viewport::new(mode, BUFFER, PhysicalSize::new(0, 0));
+            assert_eq!(viewport.to_buffer(position(0.0, 0.0)), None, "{:?}", mode);
+        }
+    }
+}